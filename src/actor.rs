@@ -0,0 +1,38 @@
+use roead::byml::Byml;
+
+/// Globs for the files belonging to an actor: its pack, its row in the actor info table, and
+/// its messages in every language's message pack.
+pub fn globs(actor: &str) -> Vec<String> {
+    vec![
+        format!("Pack/Actor/{actor}.pack.zs"),
+        "RSDB/ActorInfo.*".into(),
+        format!("**/ActorMsg/{actor}.msbt"),
+    ]
+}
+
+/// Whether a file is the actor info table, e.g. `RSDB/ActorInfo.Product.110.rstbl.byml.zs`.
+pub fn is_actor_info(name: &str) -> bool {
+    name.rsplit(['/', '\\'])
+        .next()
+        .map(|file| file.starts_with("ActorInfo."))
+        .unwrap_or(false)
+}
+
+/// Keeps only an actor's own rows of the actor info table, which are keyed by `__RowId`.
+/// Tables that aren't a list of rows are kept whole.
+pub fn rows_for(table: Byml, actor: &str) -> Byml {
+    match table {
+        Byml::Array(rows) => Byml::Array(
+            rows.into_iter()
+                .filter(|row| match row {
+                    Byml::Map(fields) => matches!(
+                        fields.get("__RowId"),
+                        Some(Byml::String(id)) if id.as_str() == actor
+                    ),
+                    _ => false,
+                })
+                .collect(),
+        ),
+        table => table,
+    }
+}
@@ -0,0 +1,179 @@
+use eyre::{ensure, ContextCompat, Result};
+use serde::Serialize;
+
+const HEADER_SIZE: usize = 0x74;
+const COMMAND_SIZE: usize = 0x18;
+const NODE_SIZE: usize = 0x3C;
+const GLOBAL_PARAM_SIZE: usize = 0xC;
+/// Global parameter types, in the order their tables are stored
+const GLOBAL_PARAM_TYPES: &[&str] = &["int", "bool", "float", "string", "vec3f", "userdefined"];
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .context("Unexpected end of AINB data")?;
+    Ok(u16::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .context("Unexpected end of AINB data")?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_guid(data: &[u8], offset: usize) -> Result<String> {
+    let b = data
+        .get(offset..offset + 16)
+        .context("Unexpected end of AINB data")?;
+    Ok(format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{}",
+        u32::from_le_bytes(b[0..4].try_into()?),
+        u16::from_le_bytes(b[4..6].try_into()?),
+        u16::from_le_bytes(b[6..8].try_into()?),
+        b[8],
+        b[9],
+        b[10..]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>()
+    ))
+}
+
+fn node_type(kind: u16) -> String {
+    match kind {
+        0 => "UserDefined",
+        1 => "Element_S32Selector",
+        2 => "Element_Sequential",
+        3 => "Element_Simultaneous",
+        4 => "Element_F32Selector",
+        5 => "Element_StringSelector",
+        6 => "Element_RandomSelector",
+        7 => "Element_BoolSelector",
+        8 => "Element_Fork",
+        9 => "Element_Join",
+        10 => "Element_Alert",
+        20 => "Element_Expression",
+        100 => "Element_ModuleIF_Input_S32",
+        101 => "Element_ModuleIF_Input_F32",
+        102 => "Element_ModuleIF_Input_Vec3f",
+        103 => "Element_ModuleIF_Input_String",
+        104 => "Element_ModuleIF_Input_Bool",
+        105 => "Element_ModuleIF_Input_Ptr",
+        200 => "Element_ModuleIF_Output_S32",
+        201 => "Element_ModuleIF_Output_F32",
+        202 => "Element_ModuleIF_Output_Vec3f",
+        203 => "Element_ModuleIF_Output_String",
+        204 => "Element_ModuleIF_Output_Bool",
+        205 => "Element_ModuleIF_Output_Ptr",
+        300 => "Element_ModuleIF_Child",
+        400 => "Element_StateEnd",
+        401 => "Element_SplitTiming",
+        _ => return format!("Unknown({kind})"),
+    }
+    .into()
+}
+
+/// Read-only view of an AI node graph, covering its commands, nodes, and globals but not
+/// the per-node parameters
+#[derive(Debug, Serialize)]
+pub struct Ainb {
+    pub version: u32,
+    pub filename: String,
+    pub category: Option<String>,
+    pub commands: Vec<Command>,
+    pub nodes: Vec<Node>,
+    pub globals: Vec<GlobalParam>,
+}
+
+/// An entry point into the graph
+#[derive(Debug, Serialize)]
+pub struct Command {
+    pub name: String,
+    pub guid: String,
+    pub root_node: u16,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Node {
+    pub index: u16,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub guid: String,
+    pub attachments: u16,
+    pub preconditions: u16,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GlobalParam {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub name: String,
+}
+
+impl Ainb {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"AIB "), "Not an AINB file");
+        let strings = read_u32(data, 0x24)? as usize;
+        // Strings are null-terminated and addressed by offset into the pool
+        let string = |offset: u32| -> Result<String> {
+            let start = strings + offset as usize;
+            let rest = data.get(start..).context("AINB string out of bounds")?;
+            let len = rest
+                .iter()
+                .position(|b| *b == 0)
+                .context("Unterminated AINB string")?;
+            Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+        };
+        let command_count = read_u32(data, 0xC)? as usize;
+        let node_count = read_u32(data, 0x10)? as usize;
+        let commands = (0..command_count)
+            .map(|i| -> Result<Command> {
+                let base = HEADER_SIZE + i * COMMAND_SIZE;
+                Ok(Command {
+                    name: string(read_u32(data, base)?)?,
+                    guid: read_guid(data, base + 0x4)?,
+                    root_node: read_u16(data, base + 0x14)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let nodes_start = HEADER_SIZE + command_count * COMMAND_SIZE;
+        let nodes = (0..node_count)
+            .map(|i| -> Result<Node> {
+                let base = nodes_start + i * NODE_SIZE;
+                Ok(Node {
+                    kind: node_type(read_u16(data, base)?),
+                    index: read_u16(data, base + 0x2)?,
+                    attachments: read_u16(data, base + 0x4)?,
+                    name: string(read_u32(data, base + 0x8)?)?,
+                    preconditions: read_u16(data, base + 0x26)?,
+                    guid: read_guid(data, base + 0x2C)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let globals_start = read_u32(data, 0x20)? as usize;
+        let mut globals = vec![];
+        let entries_start = globals_start + GLOBAL_PARAM_TYPES.len() * 8;
+        for (i, &kind) in GLOBAL_PARAM_TYPES.iter().enumerate() {
+            let count = read_u16(data, globals_start + i * 8)? as usize;
+            let first = read_u16(data, globals_start + i * 8 + 2)? as usize;
+            for j in first..first + count {
+                // The low 22 bits hold the name offset, the rest are flags
+                let name = read_u32(data, entries_start + j * GLOBAL_PARAM_SIZE)? & 0x3FFFFF;
+                globals.push(GlobalParam {
+                    kind,
+                    name: string(name)?,
+                });
+            }
+        }
+        Ok(Self {
+            version: read_u32(data, 0x4)?,
+            filename: string(read_u32(data, 0x8)?)?,
+            category: read_u32(data, 0x60).and_then(string).ok(),
+            commands,
+            nodes,
+            globals,
+        })
+    }
+}
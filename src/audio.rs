@@ -0,0 +1,151 @@
+use eyre::{ensure, ContextCompat, Result};
+use serde::Serialize;
+
+const BWAV_CHANNEL_SIZE: usize = 0x4C;
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .context("Unexpected end of audio data")?;
+    Ok(u16::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .context("Unexpected end of audio data")?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+fn check_bom(data: &[u8], offset: usize) -> Result<()> {
+    ensure!(
+        data.get(offset..offset + 2) == Some(&[0xFF, 0xFE][..]),
+        "Only little endian audio files are supported"
+    );
+    Ok(())
+}
+
+/// Summary of a BARS sound bank, leaving out the waveform data
+#[derive(Debug, Serialize)]
+pub struct Bars {
+    pub version: u16,
+    pub entries: Vec<BarsEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BarsEntry {
+    /// CRC32 of the entry's name
+    pub hash: u32,
+    pub amta: Option<Amta>,
+    pub bwav: Option<Bwav>,
+}
+
+/// Header of the AMTA metadata block describing a BARS entry
+#[derive(Debug, Serialize)]
+pub struct Amta {
+    pub version: u16,
+    pub size: u32,
+    pub name: Option<String>,
+}
+
+/// Header of a BWAV waveform, with one entry per channel
+#[derive(Debug, Serialize)]
+pub struct Bwav {
+    pub version: u16,
+    /// Whether this only holds the start of a waveform streamed from elsewhere
+    pub prefetch: bool,
+    pub channels: Vec<Channel>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Channel {
+    pub codec: String,
+    pub sample_rate: u32,
+    /// Samples in the full waveform, even when only a prefetch is stored here
+    pub sample_count: u32,
+    /// Where the channel's samples start in the BWAV
+    pub samples_offset: u32,
+    pub loop_start: Option<u32>,
+    pub loop_end: Option<u32>,
+}
+
+/// Follows an offset stored in a BARS, where unused entries are zero or all ones.
+fn slot(data: &[u8], offset: u32) -> Option<&[u8]> {
+    if offset == 0 || offset == u32::MAX {
+        None
+    } else {
+        data.get(offset as usize..)
+    }
+}
+
+impl Bars {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"BARS"), "Not a BARS file");
+        check_bom(data, 0x8)?;
+        let version = read_u16(data, 0xA)?;
+        let count = read_u32(data, 0xC)? as usize;
+        let offsets = 0x10 + count * 4;
+        let entries = (0..count)
+            .map(|i| -> Result<BarsEntry> {
+                let hash = read_u32(data, 0x10 + i * 4)?;
+                let amta = slot(data, read_u32(data, offsets + i * 8)?)
+                    .map(Amta::from_binary)
+                    .transpose()?;
+                let bwav = slot(data, read_u32(data, offsets + i * 8 + 4)?)
+                    .map(Bwav::from_binary)
+                    .transpose()?;
+                Ok(BarsEntry { hash, amta, bwav })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { version, entries })
+    }
+}
+
+impl Amta {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"AMTA"), "Not an AMTA block");
+        check_bom(data, 0x4)?;
+        // The name is stored as an offset relative to its own field
+        let name = read_u32(data, 0x24).ok().and_then(|offset| {
+            let name = data.get(0x24 + offset as usize..)?;
+            let len = name.iter().position(|b| *b == 0)?;
+            std::str::from_utf8(&name[..len]).ok().map(str::to_owned)
+        });
+        Ok(Self {
+            version: read_u16(data, 0x6)?,
+            size: read_u32(data, 0x8)?,
+            name,
+        })
+    }
+}
+
+impl Bwav {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"BWAV"), "Not a BWAV file");
+        check_bom(data, 0x4)?;
+        let count = read_u16(data, 0xE)? as usize;
+        let channels = (0..count)
+            .map(|i| -> Result<Channel> {
+                let base = 0x10 + i * BWAV_CHANNEL_SIZE;
+                let looping = read_u32(data, base + 0x38)? != 0;
+                Ok(Channel {
+                    codec: match read_u16(data, base)? {
+                        0 => "pcm16".into(),
+                        1 => "dsp-adpcm".into(),
+                        other => format!("unknown ({other})"),
+                    },
+                    sample_rate: read_u32(data, base + 0x4)?,
+                    sample_count: read_u32(data, base + 0x8)?,
+                    samples_offset: read_u32(data, base + 0x34)?,
+                    loop_start: looping.then(|| read_u32(data, base + 0x40)).transpose()?,
+                    loop_end: looping.then(|| read_u32(data, base + 0x3C)).transpose()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: read_u16(data, 0x6)?,
+            prefetch: read_u16(data, 0xC)? != 0,
+            channels,
+        })
+    }
+}
@@ -0,0 +1,238 @@
+use eyre::{ensure, ContextCompat, Result};
+use serde::Serialize;
+
+const ACTOR_SIZE: usize = 0x38;
+const EVENT_SIZE: usize = 0x28;
+const ENTRY_POINT_SIZE: usize = 0x18;
+const DICT_ENTRY_SIZE: usize = 0x10;
+
+/// Reads little endian values from an event flow, where pointers are offsets into the file
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        Ok(self
+            .0
+            .get(offset..offset + N)
+            .context("Unexpected end of BFEVFL data")?
+            .try_into()?)
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8> {
+        Ok(self.bytes::<1>(offset)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn ptr(&self, offset: usize) -> Result<usize> {
+        Ok(u64::from_le_bytes(self.bytes(offset)?) as usize)
+    }
+
+    /// Strings are stored with a u16 length before them.
+    fn string(&self, ptr: usize) -> Result<String> {
+        let len = self.u16(ptr)? as usize;
+        let bytes = self
+            .0
+            .get(ptr + 2..ptr + 2 + len)
+            .context("BFEVFL string out of bounds")?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn string_at(&self, offset: usize) -> Result<Option<String>> {
+        match self.ptr(offset)? {
+            0 => Ok(None),
+            ptr => self.string(ptr).map(Some),
+        }
+    }
+
+    /// Names from a dictionary, skipping the root entry.
+    fn dict_names(&self, ptr: usize) -> Result<Vec<String>> {
+        ensure!(
+            self.0.get(ptr..ptr + 4) == Some(&b"DIC "[..]),
+            "Bad BFEVFL dictionary"
+        );
+        let count = u32::from_le_bytes(self.bytes(ptr + 4)?) as usize;
+        (1..=count)
+            .map(|i| self.string(self.ptr(ptr + 8 + i * DICT_ENTRY_SIZE + 8)?))
+            .collect()
+    }
+}
+
+/// Read-only view of an event flow file's flowcharts
+#[derive(Debug, Serialize)]
+pub struct EventFlow {
+    pub flowcharts: Vec<Flowchart>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Flowchart {
+    pub name: String,
+    pub actors: Vec<Actor>,
+    pub events: Vec<Event>,
+    pub entry_points: Vec<EntryPoint>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Actor {
+    pub name: String,
+    pub secondary_name: Option<String>,
+    pub actions: Vec<String>,
+    pub queries: Vec<String>,
+}
+
+/// A node in a flowchart. Event indices refer to the flowchart's event list.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Event {
+    Action {
+        name: String,
+        next: Option<u16>,
+        actor: u16,
+        action: u16,
+    },
+    Switch {
+        name: String,
+        actor: u16,
+        query: u16,
+        cases: u16,
+    },
+    Fork {
+        name: String,
+        branches: Vec<u16>,
+        join: u16,
+    },
+    Join {
+        name: String,
+        next: Option<u16>,
+    },
+    Subflow {
+        name: String,
+        next: Option<u16>,
+        flowchart: Option<String>,
+        entry_point: Option<String>,
+    },
+    Unknown {
+        name: String,
+        kind: u8,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntryPoint {
+    pub name: String,
+    pub main_event: Option<u16>,
+}
+
+/// Event indices of all ones mean there is no event.
+fn index(value: u16) -> Option<u16> {
+    (value != u16::MAX).then_some(value)
+}
+
+impl EventFlow {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"BFEVFL\0\0"), "Not a BFEVFL file");
+        let reader = Reader(data);
+        let count = reader.u16(0x20)? as usize;
+        let offsets = reader.ptr(0x28)?;
+        let flowcharts = (0..count)
+            .map(|i| Flowchart::read(&reader, reader.ptr(offsets + i * 8)?))
+            .collect::<Result<_>>()?;
+        Ok(Self { flowcharts })
+    }
+}
+
+impl Flowchart {
+    fn read(reader: &Reader, base: usize) -> Result<Self> {
+        ensure!(
+            reader.0.get(base..base + 4) == Some(&b"EVFL"[..]),
+            "Bad BFEVFL flowchart"
+        );
+        let actor_count = reader.u16(base + 0x10)? as usize;
+        let event_count = reader.u16(base + 0x16)? as usize;
+        let entry_count = reader.u16(base + 0x18)? as usize;
+        let actors_start = reader.ptr(base + 0x28)?;
+        let actors = (0..actor_count)
+            .map(|i| -> Result<Actor> {
+                let actor = actors_start + i * ACTOR_SIZE;
+                let names = |ptr_offset: usize, count_offset: usize| -> Result<Vec<String>> {
+                    let start = reader.ptr(actor + ptr_offset)?;
+                    (0..reader.u16(actor + count_offset)? as usize)
+                        .map(|j| reader.string(reader.ptr(start + j * 8)?))
+                        .collect()
+                };
+                Ok(Actor {
+                    name: reader.string(reader.ptr(actor)?)?,
+                    secondary_name: reader.string_at(actor + 0x8)?.filter(|s| !s.is_empty()),
+                    actions: names(0x18, 0x30)?,
+                    queries: names(0x20, 0x32)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let events_start = reader.ptr(base + 0x30)?;
+        let events = (0..event_count)
+            .map(|i| -> Result<Event> {
+                let event = events_start + i * EVENT_SIZE;
+                let name = reader.string(reader.ptr(event)?)?;
+                let field = |offset: usize| reader.u16(event + offset);
+                Ok(match reader.u8(event + 0x8)? {
+                    0 => Event::Action {
+                        name,
+                        next: index(field(0xA)?),
+                        actor: field(0xC)?,
+                        action: field(0xE)?,
+                    },
+                    1 => Event::Switch {
+                        name,
+                        cases: field(0xA)?,
+                        actor: field(0xC)?,
+                        query: field(0xE)?,
+                    },
+                    2 => {
+                        let forks = reader.ptr(event + 0x10)?;
+                        Event::Fork {
+                            name,
+                            branches: (0..field(0xA)? as usize)
+                                .map(|j| reader.u16(forks + j * 2))
+                                .collect::<Result<_>>()?,
+                            join: field(0xC)?,
+                        }
+                    }
+                    3 => Event::Join {
+                        name,
+                        next: index(field(0xA)?),
+                    },
+                    4 => Event::Subflow {
+                        name,
+                        next: index(field(0xA)?),
+                        flowchart: reader.string_at(event + 0x18)?,
+                        entry_point: reader.string_at(event + 0x20)?,
+                    },
+                    kind => Event::Unknown { name, kind },
+                })
+            })
+            .collect::<Result<_>>()?;
+        let entry_names = match entry_count {
+            0 => vec![],
+            _ => reader.dict_names(reader.ptr(base + 0x38)?)?,
+        };
+        let entries_start = reader.ptr(base + 0x40)?;
+        let entry_points = entry_names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| -> Result<EntryPoint> {
+                Ok(EntryPoint {
+                    name,
+                    main_event: index(reader.u16(entries_start + i * ENTRY_POINT_SIZE + 0x14)?),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            name: reader.string(reader.ptr(base + 0x20)?)?,
+            actors,
+            events,
+            entry_points,
+        })
+    }
+}
@@ -0,0 +1,54 @@
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Component, Path, PathBuf},
+};
+
+/// Tracks output content by hash so identical files are only written once
+#[derive(Debug, Default)]
+pub(crate) struct Dedupe {
+    seen: Mutex<HashMap<blake3::Hash, PathBuf>>,
+    /// Duplicate output paths, mapped to the first path written with the same content
+    pub duplicates: Mutex<BTreeMap<PathBuf, PathBuf>>,
+}
+
+impl Dedupe {
+    /// Marks `relative` as written with `data`, returning the canonical copy if the same
+    /// content has been written before.
+    pub fn check(&self, relative: &Path, data: &[u8]) -> Option<PathBuf> {
+        let hash = blake3::hash(data);
+        let canonical = {
+            let mut seen = self.seen.lock();
+            match seen.get(&hash) {
+                Some(canonical) if canonical != relative => canonical.clone(),
+                Some(_) => return None,
+                None => {
+                    seen.insert(hash, relative.to_path_buf());
+                    return None;
+                }
+            }
+        };
+        self.duplicates
+            .lock()
+            .insert(relative.to_path_buf(), canonical.clone());
+        Some(canonical)
+    }
+}
+
+/// Builds a path to `target` relative to the folder containing `from`, where both are
+/// relative to the same root.
+pub(crate) fn relative_link(from: &Path, target: &Path) -> PathBuf {
+    let depth = from
+        .parent()
+        .map(|p| {
+            p.components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .count()
+        })
+        .unwrap_or(0);
+    let mut link = PathBuf::new();
+    for _ in 0..depth {
+        link.push("..");
+    }
+    link.join(target)
+}
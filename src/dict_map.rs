@@ -0,0 +1,24 @@
+use eyre::{Context, Result};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Loads extra `suffix: dictionary file` associations from a YAML map, returning each
+/// suffix with its dictionary data. Longer suffixes come first so the most specific one
+/// matches, and dictionary paths are relative to the map file.
+pub(crate) fn load(path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read dictionary map at {}", path.display()))?;
+    let map: BTreeMap<String, String> = serde_yaml::from_str(&text)
+        .with_context(|| format!("Failed to parse dictionary map at {}", path.display()))?;
+    let root = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut dicts = map
+        .into_iter()
+        .map(|(suffix, dict)| {
+            let dict = root.join(dict);
+            let data = fs::read(&dict)
+                .with_context(|| format!("Failed to read dictionary at {}", dict.display()))?;
+            Ok((suffix, data))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    dicts.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+    Ok(dicts)
+}
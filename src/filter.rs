@@ -0,0 +1,81 @@
+use eyre::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+/// Include and exclude globs matched against ROM-relative paths
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    /// Patterns from an ignore file, which exclude like the globs do
+    ignore: Option<Gitignore>,
+}
+
+fn build(globs: &[String]) -> Result<Option<GlobSet>> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Reads a list of globs, one per line. Blank lines and those starting with `#` are left
+/// out.
+pub fn read_globs(path: &Path) -> Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: build(include)?,
+            exclude: build(exclude)?,
+            ignore: None,
+        })
+    }
+
+    /// Also excludes paths matched by a file in gitignore syntax.
+    pub fn with_ignore_file(mut self, path: &Path) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new("");
+        if let Some(e) = builder.add(path) {
+            return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+        }
+        self.ignore = Some(builder.build()?);
+        Ok(self)
+    }
+
+    /// Whether the path matches an include glob, or there are no include globs.
+    pub fn is_included(&self, path: &Path) -> bool {
+        self.include
+            .as_ref()
+            .map(|set| set.is_match(path))
+            .unwrap_or(true)
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.exclude
+            .as_ref()
+            .map(|set| set.is_match(path))
+            .unwrap_or(false)
+            || self
+                .ignore
+                .as_ref()
+                .map(|ignore| ignore.matched_path_or_any_parents(path, false).is_ignore())
+                .unwrap_or(false)
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.is_included(path) && !self.is_excluded(path)
+    }
+}
@@ -0,0 +1,21 @@
+use crate::options::FloatStyle;
+use roead::byml::Byml;
+
+/// Rewrites every float in a BYML document to the one value its text is written as for a
+/// style, so dumps made with other settings or serializers don't differ by how floats are
+/// spelled.
+pub(crate) fn canonicalize(mut byml: Byml, style: FloatStyle) -> Byml {
+    visit(&mut byml, style);
+    byml
+}
+
+fn visit(byml: &mut Byml, style: FloatStyle) {
+    match byml {
+        Byml::Float(value) => *value = style.apply(*value as f64) as f32,
+        Byml::Double(value) => *value = style.apply(*value),
+        Byml::Array(array) => array.iter_mut().for_each(|item| visit(item, style)),
+        Byml::Map(map) => map.values_mut().for_each(|value| visit(value, style)),
+        Byml::HashMap(map) => map.values_mut().for_each(|value| visit(value, style)),
+        _ => {}
+    }
+}
@@ -0,0 +1,213 @@
+use crate::format::Endian;
+use eyre::{bail, ensure, ContextCompat, Result};
+use serde::Serialize;
+
+const HEADER_SIZE: usize = 0x14;
+/// Each block starts with its magic and size, which the pointers to it skip
+const BLOCK_HEADER_SIZE: usize = 8;
+
+/// Reads values from a BFFNT, in the byte order its header marks. Pointers to blocks are
+/// absolute, but point past the block's magic and size.
+struct Reader<'a> {
+    data: &'a [u8],
+    big: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        Ok(self
+            .data
+            .get(offset..offset + N)
+            .context("Unexpected end of BFFNT data")?
+            .try_into()?)
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8> {
+        Ok(self.bytes::<1>(offset)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Follows the block pointer stored at `offset`, checking the block has `magic`.
+    fn block(&self, offset: usize, magic: &[u8; 4]) -> Result<usize> {
+        let ptr = self.u32(offset)? as usize;
+        ensure!(
+            ptr >= BLOCK_HEADER_SIZE
+                && self.data.get(ptr - BLOCK_HEADER_SIZE..ptr - 4) == Some(magic.as_slice()),
+            "BFFNT pointer does not lead to a {} block",
+            String::from_utf8_lossy(magic)
+        );
+        Ok(ptr)
+    }
+}
+
+/// Read-only view of a BFFNT font, with the metrics of its glyphs and where their sheets
+/// are. The sheets themselves are left in the raw file.
+#[derive(Debug, Serialize)]
+pub struct Bffnt {
+    pub endian: Endian,
+    pub version: u32,
+    pub font_type: u8,
+    pub height: u8,
+    pub width: u8,
+    pub ascent: u8,
+    pub line_feed: u16,
+    /// Glyph drawn for characters the font has none for
+    pub alternate_glyph: u16,
+    pub default_width: Widths,
+    pub encoding: Encoding,
+    /// Glyphs with widths, summed over the font's width blocks
+    pub glyph_count: usize,
+    pub glyphs: Glyphs,
+}
+
+/// How a glyph is placed: its left margin, the width of what it draws, and how far it
+/// advances
+#[derive(Debug, Serialize)]
+pub struct Widths {
+    pub left: u8,
+    pub glyph: u8,
+    pub character: u8,
+}
+
+/// The encoding character codes are mapped to glyphs in
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Utf8,
+    Utf16,
+    ShiftJis,
+    Cp1252,
+}
+
+/// The texture sheets glyphs are drawn from, each a grid of cells
+#[derive(Debug, Serialize)]
+pub struct Glyphs {
+    pub cell_width: u8,
+    pub cell_height: u8,
+    pub max_character_width: u8,
+    pub baseline: u16,
+    /// Texture format of the sheets, as the game numbers them
+    pub image_format: u16,
+    pub columns: u16,
+    pub rows: u16,
+    pub sheet_width: u16,
+    pub sheet_height: u16,
+    pub sheets: Vec<Sheet>,
+}
+
+/// Where a sheet's texture data is in the raw file
+#[derive(Debug, Serialize)]
+pub struct Sheet {
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl Bffnt {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"FFNT"), "Not a BFFNT file");
+        ensure!(data.len() >= HEADER_SIZE, "BFFNT header is truncated");
+        let endian = match &data[4..6] {
+            [0xFE, 0xFF] => Endian::Big,
+            [0xFF, 0xFE] => Endian::Little,
+            _ => bail!("Bad BFFNT byte order mark"),
+        };
+        let reader = Reader {
+            data,
+            big: endian == Endian::Big,
+        };
+        let info = reader.u16(0x6)? as usize;
+        ensure!(
+            data.get(info..info + 4) == Some(b"FINF".as_slice()),
+            "BFFNT has no font info block"
+        );
+        let info = info + BLOCK_HEADER_SIZE;
+        Ok(Self {
+            endian,
+            version: reader.u32(0x8)?,
+            font_type: reader.u8(info)?,
+            height: reader.u8(info + 0x1)?,
+            width: reader.u8(info + 0x2)?,
+            ascent: reader.u8(info + 0x3)?,
+            line_feed: reader.u16(info + 0x4)?,
+            alternate_glyph: reader.u16(info + 0x6)?,
+            default_width: Widths {
+                left: reader.u8(info + 0x8)?,
+                glyph: reader.u8(info + 0x9)?,
+                character: reader.u8(info + 0xA)?,
+            },
+            encoding: match reader.u8(info + 0xB)? {
+                0 => Encoding::Utf8,
+                1 => Encoding::Utf16,
+                2 => Encoding::ShiftJis,
+                3 => Encoding::Cp1252,
+                other => bail!("Unknown BFFNT encoding {other}"),
+            },
+            glyph_count: glyph_count(&reader, info + 0x10)?,
+            glyphs: Glyphs::read(&reader, reader.block(info + 0xC, b"TGLP")?)?,
+        })
+    }
+}
+
+/// Sums the glyph ranges of the chain of width blocks the pointer at `offset` starts.
+fn glyph_count(reader: &Reader, mut offset: usize) -> Result<usize> {
+    let mut count = 0;
+    // Each block points to the next, so a bad chain could loop
+    for _ in 0..=reader.data.len() / BLOCK_HEADER_SIZE {
+        if reader.u32(offset)? == 0 {
+            return Ok(count);
+        }
+        let block = reader.block(offset, b"CWDH")?;
+        let (start, end) = (reader.u16(block)?, reader.u16(block + 0x2)?);
+        ensure!(start <= end, "Bad BFFNT width range");
+        count += (end - start) as usize + 1;
+        offset = block + 0x4;
+    }
+    bail!("BFFNT width blocks loop")
+}
+
+impl Glyphs {
+    fn read(reader: &Reader, base: usize) -> Result<Self> {
+        let count = reader.u8(base + 0x2)? as usize;
+        let size = reader.u32(base + 0x4)? as usize;
+        let start = reader.u32(base + 0x14)? as usize;
+        let sheets = (0..count)
+            .map(|i| Sheet {
+                offset: start + i * size,
+                size,
+            })
+            .collect::<Vec<_>>();
+        ensure!(
+            start + count * size <= reader.data.len(),
+            "BFFNT sheets run past the end of the file"
+        );
+        Ok(Self {
+            cell_width: reader.u8(base)?,
+            cell_height: reader.u8(base + 0x1)?,
+            max_character_width: reader.u8(base + 0x3)?,
+            baseline: reader.u16(base + 0x8)?,
+            image_format: reader.u16(base + 0xA)?,
+            columns: reader.u16(base + 0xC)?,
+            rows: reader.u16(base + 0xE)?,
+            sheet_width: reader.u16(base + 0x10)?,
+            sheet_height: reader.u16(base + 0x12)?,
+            sheets,
+        })
+    }
+}
@@ -0,0 +1,272 @@
+use eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, str::FromStr};
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Whether data starts with a zstd frame, or a skippable frame before one. Names aren't
+/// reliable for this, as some `.zs` archive members are stored uncompressed and the
+/// other way around.
+pub fn is_zstd(data: &[u8]) -> bool {
+    match data.get(..4) {
+        Some(&[0x50..=0x5F, 0x2A, 0x4D, 0x18]) => true,
+        _ => data.starts_with(&ZSTD_MAGIC),
+    }
+}
+/// Extensions used by AAMP parameter files
+const AAMP_EXTS: &[&str] = &[
+    ".aamp",
+    ".bxml",
+    ".bas",
+    ".baslist",
+    ".bgparamlist",
+    ".bphysics",
+];
+
+/// Extensions used by physics files: shapes, cloth, navigation meshes, and height fields
+pub(crate) const PHIVE_EXTS: &[&str] = &[".bphsh", ".bphcl", ".bphnm", ".bphhb"];
+
+/// Extensions used by resource databases, like the actor and tag tables in `RSDB`
+pub(crate) const RSDB_EXTS: &[&str] = &[".rsdb", ".rstbl"];
+
+/// Extensions used by environment files, the lighting, sky, and fog settings of each area.
+/// These are BYML, though AAMP ones still convert by their magic.
+const ENV_EXTS: &[&str] = &[".genvb", ".bgenv"];
+
+/// The kind of data a dumped file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Byml,
+    Aamp,
+    Msbt,
+    Rstb,
+    Bars,
+    Bwav,
+    Ainb,
+    Bfevfl,
+    Bntx,
+    Tscb,
+    /// Phive and Havok physics files, like collision shapes and cloth
+    Phive,
+    /// Fonts, as glyph metrics and sheets
+    Bffnt,
+    Sarc,
+    Raw,
+}
+
+impl Format {
+    /// A typical extension for files of this format, without the leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Byml => "byml",
+            Self::Aamp => "aamp",
+            Self::Msbt => "msbt",
+            Self::Rstb => "rsizetable",
+            Self::Bars => "bars",
+            Self::Bwav => "bwav",
+            Self::Ainb => "ainb",
+            Self::Bfevfl => "bfevfl",
+            Self::Bntx => "bntx",
+            Self::Tscb => "tscb",
+            Self::Phive => "bphsh",
+            Self::Bffnt => "bffnt",
+            Self::Sarc => "sarc",
+            Self::Raw => "bin",
+        }
+    }
+
+    /// Whether files of this format get a read-only summary next to them, instead of being
+    /// converted.
+    pub fn is_summarized(self) -> bool {
+        matches!(
+            self,
+            Self::Bars
+                | Self::Bwav
+                | Self::Ainb
+                | Self::Bfevfl
+                | Self::Bntx
+                | Self::Tscb
+                | Self::Phive
+                | Self::Bffnt
+        )
+    }
+
+    /// Guesses the format of a file from its name alone, if the name is a known one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.ends_with(".byml.zs") || name.ends_with(".byml") || name.ends_with(".bgyml") {
+            Some(Self::Byml)
+        } else if RSDB_EXTS
+            .iter()
+            .any(|ext| name.trim_end_matches(".zs").ends_with(ext))
+        {
+            // Resource databases are BYML tables of rows under their own extension
+            Some(Self::Byml)
+        } else if ENV_EXTS
+            .iter()
+            .any(|ext| name.trim_end_matches(".zs").ends_with(ext))
+        {
+            Some(Self::Byml)
+        } else if AAMP_EXTS
+            .iter()
+            .any(|ext| name.trim_end_matches(".zs").ends_with(ext))
+        {
+            Some(Self::Aamp)
+        } else if name.ends_with(".rsizetable.zs") {
+            Some(Self::Rstb)
+        } else if name.trim_end_matches(".zs").ends_with(".msbt") {
+            Some(Self::Msbt)
+        } else if name.trim_end_matches(".zs").ends_with(".bars") {
+            Some(Self::Bars)
+        } else if name.trim_end_matches(".zs").ends_with(".bwav") {
+            Some(Self::Bwav)
+        } else if name.trim_end_matches(".zs").ends_with(".ainb") {
+            Some(Self::Ainb)
+        } else if name.trim_end_matches(".zs").ends_with(".bfevfl") {
+            Some(Self::Bfevfl)
+        } else if name.trim_end_matches(".zs").ends_with(".bntx") {
+            Some(Self::Bntx)
+        } else if name.trim_end_matches(".zs").ends_with(".tscb") {
+            Some(Self::Tscb)
+        } else if PHIVE_EXTS
+            .iter()
+            .any(|ext| name.trim_end_matches(".zs").ends_with(ext))
+        {
+            Some(Self::Phive)
+        } else if name.trim_end_matches(".zs").ends_with(".bffnt") {
+            Some(Self::Bffnt)
+        } else if crate::is_archive(name) {
+            Some(Self::Sarc)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "byml" => Self::Byml,
+            "aamp" => Self::Aamp,
+            "msbt" => Self::Msbt,
+            "rstb" => Self::Rstb,
+            "bars" => Self::Bars,
+            "bwav" => Self::Bwav,
+            "ainb" => Self::Ainb,
+            "bfevfl" => Self::Bfevfl,
+            "bntx" => Self::Bntx,
+            "tscb" => Self::Tscb,
+            "phive" => Self::Phive,
+            "bffnt" => Self::Bffnt,
+            "sarc" => Self::Sarc,
+            "raw" => Self::Raw,
+            _ => bail!("Unknown format {s}"),
+        })
+    }
+}
+
+/// A set of formats, parsed from a comma separated list like `byml,msbt`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Formats(pub HashSet<Format>);
+
+impl Formats {
+    pub fn contains(&self, format: Format) -> bool {
+        self.0.contains(&format)
+    }
+}
+
+impl FromStr for Formats {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|format| !format.is_empty())
+            .map(str::parse)
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+/// Byte order of a file, from its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+impl From<Endian> for roead::Endian {
+    fn from(endian: Endian) -> Self {
+        match endian {
+            Endian::Big => Self::Big,
+            Endian::Little => Self::Little,
+        }
+    }
+}
+
+/// What a BYML header says about the rest of the file
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BymlHeader {
+    pub endian: Endian,
+    pub version: u16,
+}
+
+/// Reads a BYML header, where the magic's byte order gives the order of everything after
+/// it. Versions outside those that exist mean the data isn't really BYML.
+pub(crate) fn byml_header(data: &[u8]) -> Option<BymlHeader> {
+    let header = match data.get(..4)? {
+        [b'B', b'Y', hi, lo] => BymlHeader {
+            endian: Endian::Big,
+            version: u16::from_be_bytes([*hi, *lo]),
+        },
+        [b'Y', b'B', lo, hi] => BymlHeader {
+            endian: Endian::Little,
+            version: u16::from_le_bytes([*lo, *hi]),
+        },
+        _ => return None,
+    };
+    (1..=10).contains(&header.version).then_some(header)
+}
+
+/// Whether data starts with an AAMP header, of the only version there is. The magic alone
+/// is four bytes that any file could start with, and parsing those that aren't AAMP
+/// fails, so the version is checked too, as with BYML.
+fn is_aamp_header(data: &[u8]) -> bool {
+    matches!(data.get(..8), Some([b'A', b'A', b'M', b'P', 2, 0, 0, 0]))
+}
+
+/// Detects the format of (decompressed) data from its magic bytes.
+pub fn detect_format(data: &[u8]) -> Format {
+    if data.starts_with(b"SARC") {
+        Format::Sarc
+    } else if is_aamp_header(data) {
+        Format::Aamp
+    } else if data.starts_with(b"MsgStdBn") {
+        Format::Msbt
+    } else if data.starts_with(b"RESTBL") {
+        Format::Rstb
+    } else if data.starts_with(b"BARS") {
+        Format::Bars
+    } else if data.starts_with(b"BWAV") {
+        Format::Bwav
+    } else if data.starts_with(b"AIB ") {
+        Format::Ainb
+    } else if data.starts_with(b"BFEVFL") {
+        Format::Bfevfl
+    } else if data.starts_with(b"BNTX") {
+        Format::Bntx
+    } else if data.starts_with(b"TSCB") {
+        Format::Tscb
+    } else if crate::physics::is_physics(data) {
+        Format::Phive
+    } else if data.starts_with(b"FFNT") {
+        Format::Bffnt
+    } else if byml_header(data).is_some() {
+        Format::Byml
+    } else {
+        Format::Raw
+    }
+}
@@ -0,0 +1,120 @@
+use crate::names::ActorNames;
+use roead::byml::Byml;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// Whether a file is one of the save data flag lists, e.g.
+/// `GameData/GameDataList.Product.110.byml.zs`.
+pub fn is_gamedata_list(name: &str) -> bool {
+    name.rsplit(['/', '\\'])
+        .next()
+        .map(|file| file.starts_with("GameDataList.") && file.contains(".byml"))
+        .unwrap_or(false)
+}
+
+/// Read-only view of a GameDataList. Flags are grouped by type and keyed by their name
+/// hash, as the names themselves aren't stored.
+#[derive(Debug, Serialize)]
+pub struct GameDataList<'a> {
+    pub metadata: Option<Plain<'a>>,
+    pub flags: BTreeMap<&'a str, BTreeMap<String, Flag<'a>>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Flag<'a> {
+    /// The actor whose name has the flag's hash, when names are resolved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<&'a str>,
+    /// Indices of the bits set in `ResetTypeValue`, each a condition that resets the flag
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reset_types: Vec<u32>,
+    /// Every other field, like `DefaultValue` and `SaveFileIndex`
+    #[serde(flatten)]
+    pub fields: BTreeMap<&'a str, Plain<'a>>,
+}
+
+impl<'a> GameDataList<'a> {
+    /// Regroups the flag tables of a parsed list, or returns `None` if it doesn't have the
+    /// expected `Data` map of flag arrays. Flags hashed like an actor's name are given it
+    /// from `names`.
+    pub fn new(byml: &'a Byml, names: Option<&'a ActorNames>) -> Option<Self> {
+        let Byml::Map(root) = byml else {
+            return None;
+        };
+        let Some(Byml::Map(data)) = root.get("Data") else {
+            return None;
+        };
+        let mut flags = BTreeMap::new();
+        for (kind, table) in data.iter() {
+            let Byml::Array(entries) = table else {
+                return None;
+            };
+            let mut table = BTreeMap::new();
+            for entry in entries {
+                let Byml::Map(entry) = entry else {
+                    return None;
+                };
+                let (hash, short) = match entry.get("Hash") {
+                    Some(Byml::U32(hash)) => (format!("0x{hash:08x}"), Some(*hash)),
+                    Some(Byml::I32(hash)) => {
+                        (format!("0x{:08x}", *hash as u32), Some(*hash as u32))
+                    }
+                    Some(Byml::U64(hash)) => (format!("0x{hash:016x}"), None),
+                    Some(Byml::I64(hash)) => (format!("0x{:016x}", *hash as u64), None),
+                    _ => return None,
+                };
+                let reset = match entry.get("ResetTypeValue") {
+                    Some(Byml::U32(value)) => *value,
+                    Some(Byml::I32(value)) => *value as u32,
+                    _ => 0,
+                };
+                let fields = entry
+                    .iter()
+                    .filter(|(key, _)| !matches!(key.as_str(), "Hash" | "ResetTypeValue"))
+                    .map(|(key, value)| (key.as_str(), Plain(value)))
+                    .collect();
+                table.insert(
+                    hash,
+                    Flag {
+                        actor: names.zip(short).and_then(|(names, hash)| names.get(hash)),
+                        reset_types: (0..32).filter(|bit| reset & (1 << *bit) != 0).collect(),
+                        fields,
+                    },
+                );
+            }
+            flags.insert(kind.as_str(), table);
+        }
+        Some(Self {
+            metadata: root.get("MetaData").map(Plain),
+            flags,
+        })
+    }
+}
+
+/// Serializes BYML without the type tags on every node, for views of it that don't need
+/// to be read back exactly
+#[derive(Debug)]
+pub struct Plain<'a>(pub &'a Byml);
+
+impl Serialize for Plain<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Byml::String(value) => serializer.serialize_str(value),
+            Byml::Bool(value) => serializer.serialize_bool(*value),
+            Byml::I32(value) => serializer.serialize_i32(*value),
+            Byml::U32(value) => serializer.serialize_u32(*value),
+            Byml::I64(value) => serializer.serialize_i64(*value),
+            Byml::U64(value) => serializer.serialize_u64(*value),
+            Byml::Float(value) => serializer.serialize_f32(*value),
+            Byml::Double(value) => serializer.serialize_f64(*value),
+            Byml::Null => serializer.serialize_unit(),
+            Byml::Array(array) => serializer.collect_seq(array.iter().map(Plain)),
+            Byml::Map(map) => {
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                serializer.collect_map(entries.into_iter().map(|(k, v)| (k, Plain(v))))
+            }
+            other => other.serialize(serializer),
+        }
+    }
+}
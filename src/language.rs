@@ -0,0 +1,169 @@
+use eyre::{bail, Result};
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::Path,
+    str::FromStr,
+};
+
+/// The game's language codes, as used to name the message packs in `Mals`, and the usual
+/// code for each language
+const LANGUAGES: &[(&str, &str)] = &[
+    ("USen", "en"),
+    ("EUen", "en"),
+    ("USfr", "fr"),
+    ("EUfr", "fr"),
+    ("USes", "es"),
+    ("EUes", "es"),
+    ("EUde", "de"),
+    ("EUit", "it"),
+    ("EUnl", "nl"),
+    ("EUru", "ru"),
+    ("JPja", "ja"),
+    ("KRko", "ko"),
+    ("CNzh", "zh-cn"),
+    ("TWzh", "zh-tw"),
+];
+
+/// The game language code a path is localized for, from the first folder or file whose
+/// name starts with one, like `Mals/USen.Product.110.sarc.zs`.
+pub fn language_of(path: &Path) -> Option<&'static str> {
+    find_code(path, LANGUAGES.iter().map(|(code, _)| *code))
+}
+
+fn find_code<'a>(path: &Path, codes: impl Iterator<Item = &'a str> + Clone) -> Option<&'a str> {
+    path.components().find_map(|c| {
+        let name = c.as_os_str().to_str()?;
+        codes.clone().find(|code| {
+            name.strip_prefix(code)
+                .map(|rest| !rest.starts_with(|c: char| c.is_ascii_alphanumeric()))
+                .unwrap_or(false)
+        })
+    })
+}
+
+/// The game's language codes with the usual code for each: the built-in table, plus any
+/// added for languages it doesn't know yet
+#[derive(Debug, Clone)]
+pub struct LanguageMap(Vec<(String, String)>);
+
+impl Default for LanguageMap {
+    fn default() -> Self {
+        Self(
+            LANGUAGES
+                .iter()
+                .map(|(code, human)| (code.to_string(), human.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl LanguageMap {
+    /// Maps a game code to a usual code, replacing what the table had for it.
+    pub fn with(mut self, code: &str, human: &str) -> Self {
+        let human = human.to_ascii_lowercase();
+        match self.0.iter_mut().find(|(known, _)| known == code) {
+            Some((_, known)) => *known = human,
+            None => self.0.push((code.to_owned(), human)),
+        }
+        self
+    }
+
+    /// Adds entries from a list like `EUpt=pt,USpt=pt`.
+    pub fn with_entries(self, entries: &str) -> Result<Self> {
+        entries
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .try_fold(self, |map, entry| match entry.split_once('=') {
+                Some((code, human)) => Ok(map.with(code.trim(), human.trim())),
+                None => bail!("Language mapping {entry} should be <code>=<locale>"),
+            })
+    }
+
+    /// The game language code a path is localized for, as with [`language_of`].
+    pub fn language_of(&self, path: &Path) -> Option<&str> {
+        find_code(path, self.0.iter().map(|(code, _)| code.as_str()))
+    }
+
+    /// Parses a comma separated list of languages, as for [`Languages`]. Unknown ones are
+    /// taken as game codes as they are, with a warning, in case they are for a language
+    /// added since.
+    pub fn languages(&self, s: &str) -> Languages {
+        let mut map = self.clone();
+        let mut codes = HashSet::new();
+        for lang in s.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+            let matching = self.matching(lang);
+            if matching.is_empty() {
+                log::warn!(
+                    "Unknown language {lang}, selecting files named for it as it is. Known ones \
+                     are {}",
+                    self.known()
+                );
+                map = map.with(lang, lang);
+                codes.insert(lang.to_owned());
+            }
+            codes.extend(matching);
+        }
+        Languages { codes, map }
+    }
+
+    /// The game codes a language selects: the code itself, or every code for a usual code
+    /// like `en` or `zh-tw`. Chinese can be given as just `zh` for both scripts.
+    fn matching(&self, lang: &str) -> Vec<String> {
+        let lower = lang.to_ascii_lowercase();
+        self.0
+            .iter()
+            .filter(|(code, human)| {
+                code.eq_ignore_ascii_case(lang)
+                    || *human == lower
+                    || human.split('-').next() == Some(lower.as_str())
+            })
+            .map(|(code, _)| code.clone())
+            .collect()
+    }
+
+    fn known(&self) -> String {
+        self.0
+            .iter()
+            .map(|(_, human)| human.as_str())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The game languages to unpack localized files for
+#[derive(Debug, Clone, Default)]
+pub struct Languages {
+    codes: HashSet<String>,
+    map: LanguageMap,
+}
+
+impl Languages {
+    /// Whether a path is for one of the languages, or isn't localized at all.
+    pub fn is_selected(&self, path: &Path) -> bool {
+        self.map
+            .language_of(path)
+            .map(|code| self.codes.contains(code))
+            .unwrap_or(true)
+    }
+}
+
+impl FromStr for Languages {
+    type Err = eyre::Report;
+
+    /// Parses a comma separated list of usual codes like `en` or `zh-tw`, which select every
+    /// region's version of the language, or the game's own codes like `EUen`. Chinese can
+    /// be given as just `zh` for both scripts. Only the built-in table is used, and unknown
+    /// languages are an error.
+    fn from_str(s: &str) -> Result<Self> {
+        let map = LanguageMap::default();
+        for lang in s.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+            if map.matching(lang).is_empty() {
+                bail!("Unknown language {lang}, expected one of {}", map.known());
+            }
+        }
+        Ok(map.languages(s))
+    }
+}
@@ -0,0 +1,3035 @@
+use eyre::{bail, ensure, Context, ContextCompat, Result};
+use format::{byml_header, detect_format, is_zstd, BymlHeader, Endian, Format};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use parking_lot::{Mutex, RwLock};
+use rayon::prelude::*;
+use roead::{byml::Byml, sarc::Sarc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use stats::{FileTimes, Stage};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use zip::ZipWriter;
+
+pub mod actor;
+pub mod ainb;
+pub mod audio;
+pub mod bfevfl;
+mod dedupe;
+mod dict_map;
+pub mod filter;
+mod floats;
+pub mod font;
+pub mod format;
+pub mod gamedata;
+pub mod language;
+pub mod magic_table;
+pub mod manifest;
+pub mod map;
+pub mod names;
+mod nested;
+mod options;
+pub mod physics;
+mod pool;
+mod repack;
+mod retry;
+pub mod rstb;
+mod since;
+mod sort;
+mod split;
+pub mod stats;
+mod style;
+pub mod terrain;
+pub mod texture;
+mod ticker;
+pub mod verify;
+pub mod version;
+mod writer;
+mod zsdic;
+pub use options::{
+    BinaryNodes, ExtensionStyle, FloatStyle, Options, OutputCompression, OutputFormat, SarcLayout,
+};
+pub use repack::Repacker;
+pub use zsdic::{decompress_auto, ZsDic};
+
+const COMPRESSION_LEVEL: usize = 15;
+/// How many archives deep nested SARCs are unpacked before giving up
+const MAX_SARC_DEPTH: usize = 8;
+/// Compressed files at least this big are decompressed straight from disk as a stream,
+/// instead of being read whole and decompressed into guessed buffer sizes
+const STREAM_THRESHOLD: u64 = 64 * 1024 * 1024;
+/// BYML version used by TOTK, which roead may only read after it is patched down
+const BYML_VERSION: u16 = 7;
+/// Folder in the output that decompressed copies of converted files are kept under, with
+/// `extract_raw_also`
+pub(crate) const RAW_FOLDER: &str = "_raw";
+/// Extensions of the text files a dump writes, as opposed to raw files from the ROM
+const TEXT_EXTENSIONS: &[&str] = &["yml", "json", "toml", "txt"];
+
+/// Whether a file is a SARC archive by its name. Layout archives (`.blarc`) and font
+/// archives (`.bfarc`) are SARCs too, with no LArc or FArc format of their own in TOTK.
+fn is_archive(name: &str) -> bool {
+    [
+        ".pack.zs",
+        ".sarc.zs",
+        ".blarc.zs",
+        ".bfarc.zs",
+        ".pack",
+        ".sarc",
+        ".blarc",
+        ".bfarc",
+    ]
+    .iter()
+    .any(|ext| name.ends_with(ext))
+}
+
+/// Which zstd dictionary a file is compressed with, chosen by its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Dictionary {
+    None,
+    Common,
+    Pack,
+    Map,
+    /// One loaded from a dictionary map, by its index there
+    Custom(usize),
+}
+
+/// Dictionaries for file name patterns, checked in order before falling back to the common
+/// one. Patterns starting with a dot match a suffix, and others the whole name.
+const DICTIONARIES: &[(&str, Dictionary)] = &[
+    (".bcett.byml.zs", Dictionary::Map),
+    ("ZsDic.pack.zs", Dictionary::None),
+    (".rsizetable.zs", Dictionary::None),
+    (".pack.zs", Dictionary::Pack),
+];
+
+impl Dictionary {
+    fn for_name(name: &str) -> Self {
+        DICTIONARIES
+            .iter()
+            .find(|(pattern, _)| {
+                if pattern.starts_with('.') {
+                    name.ends_with(pattern)
+                } else {
+                    name == *pattern
+                }
+            })
+            .map(|(_, dict)| *dict)
+            .unwrap_or(Self::Common)
+    }
+
+    /// Picks a dictionary for a name, preferring the longest matching custom suffix.
+    fn for_name_with<T>(name: &str, custom: &[(String, T)]) -> Self {
+        custom
+            .iter()
+            .position(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(Self::Custom)
+            .unwrap_or_else(|| Self::for_name(name))
+    }
+}
+
+/// Parses a BYML file at its own version, only rewriting the header's version to one
+/// roead knows if that fails.
+fn parse_byml(data: &[u8]) -> Result<Byml> {
+    match Byml::from_binary(data) {
+        Ok(byml) => Ok(byml),
+        Err(e) => {
+            let Some(header) = byml_header(data) else {
+                return Err(e.into());
+            };
+            let mut patched = data.to_vec();
+            patched[2..4].copy_from_slice(&match header.endian {
+                Endian::Big => 4u16.to_be_bytes(),
+                Endian::Little => 4u16.to_le_bytes(),
+            });
+            Byml::from_binary(&patched).with_context(|| {
+                format!(
+                    "Failed to parse {:?} endian BYML v{}",
+                    header.endian, header.version
+                )
+            })
+        }
+    }
+}
+
+/// Parses an MSBT file, keeping the whole chain of error messages.
+fn parse_msbt(data: &[u8]) -> Result<msyt::Msyt> {
+    msyt::Msyt::from_msbt_bytes(data).map_err(|e| {
+        e.chain()
+            .rev()
+            .fold(eyre::eyre!("Failed to parse MSBT"), |acc, e| {
+                acc.wrap_err(eyre::eyre!("{e}"))
+            })
+    })
+}
+
+/// The content size recorded in the first frame's header, if any, to size output buffers.
+/// Later frames may add more.
+fn frame_size(data: &[u8]) -> usize {
+    zstd::zstd_safe::get_frame_content_size(data)
+        .ok()
+        .flatten()
+        .unwrap_or(0) as usize
+}
+
+/// Finds the romfs folder for a ROM: the given folder if it is one, or else a `romfs`
+/// folder inside it, next to it, or next to its parent.
+pub fn find_romfs(path: &Path) -> Result<PathBuf> {
+    if !path.exists() {
+        bail!("Source folder {} does not exist", path.display());
+    }
+    let mut source = path.canonicalize()?;
+    if !source.ends_with("romfs") {
+        let mut candidates = vec![source.join("romfs")];
+        if let Some(parent) = source.parent() {
+            candidates.push(parent.join("romfs"));
+            candidates.push(parent.with_file_name("romfs"));
+        }
+        match candidates.iter().find(|c| c.exists()) {
+            Some(romfs) => source = romfs.clone(),
+            None => bail!(
+                "No romfs folder found for {}. Tried:\n{}",
+                source.display(),
+                candidates
+                    .iter()
+                    .map(|c| format!("  {}", c.display()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        }
+    }
+    Ok(source)
+}
+
+/// Converts a value for a member of a bundled archive.
+fn to_value<T: Serialize>(value: &T) -> Result<serde_yaml::Value> {
+    Ok(serde_yaml::to_value(value)?)
+}
+
+/// Stands in for a bundled archive member that can't be converted, as its bytes in base64.
+fn binary_value(data: &[u8]) -> serde_yaml::Value {
+    use base64::Engine;
+    serde_yaml::Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
+        tag: serde_yaml::value::Tag::new("binary"),
+        value: base64::engine::general_purpose::STANDARD
+            .encode(data)
+            .into(),
+    }))
+}
+
+/// A path as text with forward slashes, so lists and reports of paths read the same on
+/// every platform.
+pub fn display_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Finds the ZsDic pack for a file taken out of a ROM, in the `Pack` folder of the nearest
+/// folder above it that has one, or next to it.
+pub fn find_zsdic(file: &Path) -> Option<PathBuf> {
+    file.ancestors().skip(1).find_map(|dir| {
+        [dir.join("Pack/ZsDic.pack.zs"), dir.join("ZsDic.pack.zs")]
+            .into_iter()
+            .find(|path| path.is_file())
+    })
+}
+
+/// Writes a file under a temporary name next to it and then renames it into place, so an
+/// interrupted dump never leaves a truncated file that looks complete.
+fn write_atomic(out: &Path, data: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let temp = temp_path(out);
+    if let Err(e) = fs::write(&temp, data).and_then(|_| fs::rename(&temp, out)) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Everything after the first dot of a file's name, leaving out compression, like
+/// `bgyml` or `bcett.byml`.
+pub(crate) fn extension_of(relative: &Path) -> String {
+    let name = relative
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let name = name.strip_suffix(".zs").unwrap_or(&name);
+    name.split_once('.')
+        .map(|(_, ext)| ext.to_owned())
+        .unwrap_or_default()
+}
+
+/// Marks the text formats a dump wrote as text with Unix line endings, and everything
+/// else it wrote as binary, so git neither changes nor diffs the raw files.
+fn gitattributes(extensions: &BTreeSet<String>) -> String {
+    let mut attributes = String::from("# Written by totk-dump for the files in this dump\n");
+    // The manifest is always JSON
+    let text = std::iter::once("json")
+        .chain(extensions.iter().map(String::as_str))
+        .filter(|ext| TEXT_EXTENSIONS.contains(ext))
+        .collect::<BTreeSet<_>>();
+    for ext in &text {
+        attributes.push_str(&format!("*.{ext} text eol=lf\n"));
+    }
+    for ext in extensions.iter().filter(|ext| !text.contains(ext.as_str())) {
+        attributes.push_str(&format!("*.{ext} binary\n"));
+    }
+    attributes
+}
+
+/// Joins the folders of a path past `depth` levels into its file name with `__`, like
+/// `a/b/c__d__File.byml` for a depth of 2.
+fn flatten(path: &Path, depth: usize) -> PathBuf {
+    let parts = path
+        .iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>();
+    if parts.len() <= depth + 1 {
+        return path.to_path_buf();
+    }
+    let mut flat = path.iter().take(depth).collect::<PathBuf>();
+    flat.push(parts[depth..].join("__"));
+    flat
+}
+
+/// Quotes a CSV field if it needs it.
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Writes an output to disk, creating its folder first.
+fn write_to_disk(out: &Path, data: &[u8]) -> Result<()> {
+    let long = long_path(out);
+    if let Some(parent) = long.parent() {
+        retry::retry(|| fs::create_dir_all(parent))?;
+    }
+    retry::retry(|| write_atomic(&long, data))
+        .with_context(|| format!("Failed to write {}", out.display()))?;
+    Ok(())
+}
+
+/// The extended-length form of an absolute output path on Windows, like `\\?\C:\dump`,
+/// which can be written past the 260 character limit whatever the system's settings.
+#[cfg(windows)]
+fn long_path(path: &Path) -> Cow<'_, Path> {
+    use std::path::{Component, Prefix};
+    let Ok(absolute) = std::path::absolute(path) else {
+        return Cow::Borrowed(path);
+    };
+    let mut components = absolute.components();
+    let mut long = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(_) => {
+                let mut long = std::ffi::OsString::from(r"\\?\");
+                long.push(prefix.as_os_str());
+                PathBuf::from(long)
+            }
+            Prefix::UNC(server, share) => {
+                let mut long = std::ffi::OsString::from(r"\\?\UNC\");
+                long.push(server);
+                long.push(r"\");
+                long.push(share);
+                PathBuf::from(long)
+            }
+            // Already extended-length, or a device path
+            _ => return Cow::Owned(absolute),
+        },
+        _ => return Cow::Owned(absolute),
+    };
+    // Extended-length paths are taken literally, so forward slashes from archive member
+    // names and any `.` or `..` have to be resolved here instead
+    for component in components {
+        match component {
+            Component::RootDir => long.push(r"\"),
+            Component::ParentDir => {
+                long.pop();
+            }
+            Component::Normal(part) => long.push(part),
+            Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    Cow::Owned(long)
+}
+
+#[cfg(not(windows))]
+fn long_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// A name next to `out` to write it under until it is complete, unique to each write.
+fn temp_path(out: &Path) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let mut temp = out.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", NEXT.fetch_add(1, Ordering::Relaxed)));
+    PathBuf::from(temp)
+}
+
+/// The bytes of a ROM file, either read into memory or mapped
+enum SourceData {
+    Read(Vec<u8>),
+    Mapped(memmap2::Mmap),
+}
+
+impl std::ops::Deref for SourceData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Read(data) => data,
+            Self::Mapped(map) => map,
+        }
+    }
+}
+
+/// Where the members of an archive being unpacked came from
+#[derive(Debug, Clone, Copy)]
+struct ArchiveContext {
+    /// Whether the archive itself is matched by the include filter
+    included: bool,
+    /// How many archives this one is nested inside
+    depth: usize,
+    /// Modification time of the ROM file the archive was read from
+    modified: Option<SystemTime>,
+}
+
+/// Unpacks a TOTK romfs into YAML and loose files under an output folder.
+pub struct Unpacker {
+    source: PathBuf,
+    /// Update romfs folders overlaid on the source, each overriding those before it
+    layers: Vec<PathBuf>,
+    output: PathBuf,
+    dicts: ZsDic,
+    options: Options,
+    /// The actor info tables, when needed to annotate maps
+    actor_db: map::ActorDb,
+    /// Actor names by hash, built the first time a view resolves one
+    actor_names: OnceLock<Arc<names::ActorNames>>,
+    manifest: Mutex<Vec<manifest::Entry>>,
+    errors: Mutex<Vec<manifest::Failure>>,
+    stats: stats::Stats,
+    dedupe: dedupe::Dedupe,
+    since: since::Since,
+    /// Buffers for decompressing archives into, shared between the workers
+    buffers: pool::BufferPool,
+    /// Every output written, or left as it was because it was unchanged, for cleaning and
+    /// the paths file
+    outputs: Mutex<HashSet<PathBuf>>,
+    /// Extensions of the outputs written, for `.gitattributes`
+    extensions: Mutex<BTreeSet<String>>,
+    /// SHA-256 of each output, keyed by its path in the output folder
+    hashes: Mutex<BTreeMap<PathBuf, String>>,
+    /// Errors and output sizes for files whose results haven't been recorded yet
+    pending_errors: Mutex<HashMap<PathBuf, String>>,
+    written: Mutex<HashMap<PathBuf, u64>>,
+    results: Mutex<Vec<manifest::FileResult>>,
+    on_file: Option<Box<dyn Fn(&manifest::FileResult) + Send + Sync>>,
+    /// Hashes of the ROM files being unpacked, to record in the manifest
+    source_hashes: Mutex<HashMap<PathBuf, String>>,
+    /// Offsets and sizes of archive members in their decompressed archives, to record in
+    /// the manifest
+    member_ranges: Mutex<HashMap<PathBuf, (u64, u64)>>,
+    /// Warnings about each ROM file being unpacked, logged together once it is done
+    warnings: Mutex<HashMap<PathBuf, Vec<String>>>,
+    /// Which file each output was written for, to catch members overwriting each other
+    /// in the single root layout
+    claimed: Mutex<HashMap<PathBuf, PathBuf>>,
+    /// Modification times of the ROM files being unpacked, to give their outputs
+    source_times: Mutex<HashMap<PathBuf, SystemTime>>,
+    /// Manifest entries from the last dump, grouped by the ROM file they came from
+    previous: Mutex<HashMap<PathBuf, Vec<manifest::Entry>>>,
+    /// Files no converter handles, counted by extension and magic
+    unhandled: Mutex<HashMap<(String, String), usize>>,
+    stop: Arc<AtomicBool>,
+    /// The archive everything is written into instead of the output folder, while open
+    zip: Mutex<Option<ZipWriter<fs::File>>>,
+    /// The checkpoint file that finished ROM files are added to, while unpacking with one
+    checkpoint: Mutex<Option<fs::File>>,
+    /// The CSV that each file's sizes and timings are added to, while tracing
+    trace: Mutex<Option<BufWriter<fs::File>>>,
+    /// How the output folder differs from this dump, while comparing against it
+    comparison: Mutex<verify::Report>,
+    /// The threads outputs are handed to for writing, while unpacking with them
+    writer: RwLock<Option<writer::WritePool>>,
+    progress: MultiProgress,
+}
+
+impl Unpacker {
+    pub fn new(source: PathBuf, output: PathBuf) -> Self {
+        Self {
+            source,
+            layers: vec![],
+            output,
+            dicts: Default::default(),
+            actor_db: Default::default(),
+            actor_names: Default::default(),
+            options: Default::default(),
+            manifest: Default::default(),
+            errors: Default::default(),
+            stats: Default::default(),
+            dedupe: Default::default(),
+            since: Default::default(),
+            buffers: Default::default(),
+            outputs: Default::default(),
+            extensions: Default::default(),
+            hashes: Default::default(),
+            pending_errors: Default::default(),
+            written: Default::default(),
+            results: Default::default(),
+            on_file: None,
+            source_hashes: Default::default(),
+            member_ranges: Default::default(),
+            warnings: Default::default(),
+            claimed: Default::default(),
+            source_times: Default::default(),
+            previous: Default::default(),
+            unhandled: Default::default(),
+            stop: Default::default(),
+            zip: Default::default(),
+            checkpoint: Default::default(),
+            trace: Default::default(),
+            comparison: Default::default(),
+            writer: Default::default(),
+            progress: MultiProgress::new(),
+        }
+    }
+
+    /// Draws progress through the given bars, e.g. so a logger can suspend them. If they are
+    /// hidden, progress is logged every few seconds instead.
+    pub fn with_progress(mut self, progress: MultiProgress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Overlays update romfs folders on the source, later ones replacing files in earlier
+    /// ones by relative path.
+    pub fn with_layers(mut self, layers: Vec<PathBuf>) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Calls back with the result of each file as soon as it has been processed, from
+    /// whichever thread processed it.
+    pub fn with_on_file(
+        mut self,
+        on_file: impl Fn(&manifest::FileResult) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_file = Some(Box::new(on_file));
+        self
+    }
+
+    /// The source folder followed by each layer, from lowest to highest priority.
+    fn roots(&self) -> impl DoubleEndedIterator<Item = &PathBuf> {
+        std::iter::once(&self.source).chain(&self.layers)
+    }
+
+    pub fn with_options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Stops the unpack when `stop` is set, e.g. from a Ctrl-C handler. Files already being
+    /// unpacked are finished, and the manifest covers everything done so far.
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Whether the unpack was asked to stop before it finished.
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Whether more was written than `max_output_size` allows, which stops the unpack.
+    pub fn is_over_output_limit(&self) -> bool {
+        self.options
+            .max_output_size
+            .map(|max| self.stats.bytes_written.load(Ordering::Relaxed) > max)
+            .unwrap_or(false)
+    }
+
+    /// The number of files that have failed to convert so far.
+    pub fn failures(&self) -> usize {
+        self.stats.failures.load(Ordering::Relaxed)
+    }
+
+    pub fn stats(&self) -> &stats::Stats {
+        &self.stats
+    }
+
+    /// The counters so far, with how many files were processed, given how long it took.
+    pub fn summary(&self, elapsed: Duration) -> stats::Summary {
+        self.stats.summary(&self.results.lock(), elapsed)
+    }
+
+    /// Every failure so far, sorted by path.
+    pub fn errors(&self) -> Vec<manifest::Failure> {
+        let mut errors = self.errors.lock().clone();
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        errors
+    }
+
+    /// The result of every file processed so far, in the order they finished.
+    pub fn results(&self) -> Vec<manifest::FileResult> {
+        self.results.lock().clone()
+    }
+
+    /// Whether nothing should be written to the output at all.
+    fn is_dry_run(&self) -> bool {
+        self.options.dry_run || self.options.validate
+    }
+
+    fn write_output(&self, out: &Path, data: impl AsRef<[u8]>) -> Result<()> {
+        self.write_counted(out, data.as_ref(), true)
+    }
+
+    /// Writes an output that no result is recorded for, such as a raw copy, a part of a
+    /// split document or a texture's surface, so its size only goes towards the totals.
+    fn write_extra(&self, out: &Path, data: impl AsRef<[u8]>) -> Result<()> {
+        self.write_counted(out, data.as_ref(), false)
+    }
+
+    /// Writes an output, keeping its size for its file's result if one will be `recorded`.
+    fn write_counted(&self, out: &Path, data: &[u8], recorded: bool) -> Result<()> {
+        if self.is_dry_run() {
+            return Ok(());
+        }
+        let (stored, compressed);
+        let (out, data) = match self.options.compress_output {
+            OutputCompression::None => (out, data),
+            compression => {
+                stored = compression.apply(out);
+                compressed = compression.encode(data)?;
+                (stored.as_path(), compressed.as_slice())
+            }
+        };
+        self.note_output(out);
+        if let Some(previous) = &self.options.since {
+            let relative = out.strip_prefix(&self.output)?;
+            if self.since.is_unchanged(previous, relative, data) {
+                return Ok(());
+            }
+        }
+        if self.options.hashes {
+            let relative = out.strip_prefix(&self.output)?;
+            self.hashes.lock().insert(
+                relative.to_path_buf(),
+                format!("{:x}", Sha256::digest(data)),
+            );
+        }
+        if self.options.dedupe {
+            let relative = out.strip_prefix(&self.output)?;
+            if let Some(canonical) = self.dedupe.check(relative, data) {
+                return self.write_link(out, &dedupe::relative_link(relative, &canonical));
+            }
+        }
+        if self.options.skip_identical && self.is_identical(out, data) {
+            log::trace!("Leaving {} as it is, unchanged", out.display());
+            return Ok(());
+        }
+        self.write_file(out, data)?;
+        self.count_written(data.len() as u64);
+        if recorded {
+            self.written
+                .lock()
+                .insert(out.to_path_buf(), data.len() as u64);
+        }
+        Ok(())
+    }
+
+    /// Whether an output already exists on disk with exactly this content.
+    fn is_identical(&self, out: &Path, data: &[u8]) -> bool {
+        if self.options.zip.is_some() {
+            return false;
+        }
+        // Checking the size first saves reading most changed files
+        fs::metadata(out)
+            .map(|m| m.is_file() && m.len() == data.len() as u64)
+            .unwrap_or(false)
+            && fs::read(out).map(|old| old == data).unwrap_or(false)
+    }
+
+    /// Keeps an output's path to spare it when cleaning or to tell extra files when
+    /// comparing, and its extension for the `.gitattributes` written at the end.
+    fn note_output(&self, out: &Path) {
+        if self.options.clean || self.options.compare || self.options.paths_file.is_some() {
+            self.outputs.lock().insert(out.to_path_buf());
+        }
+        if !self.options.gitattributes {
+            return;
+        }
+        if let Some(ext) = out.extension() {
+            self.extensions
+                .lock()
+                .insert(ext.to_string_lossy().into_owned());
+        }
+    }
+
+    /// Adds a file just written to the totals, stopping if that goes over the output limit.
+    fn count_written(&self, len: u64) {
+        let total = self.stats.bytes_written.fetch_add(len, Ordering::Relaxed) + len;
+        if let Some(max) = self.options.max_output_size {
+            if total > max && !self.stop.swap(true, Ordering::Relaxed) {
+                log::warn!(
+                    "Wrote {}, over the limit of {}, stopping after the files in progress",
+                    HumanBytes(total),
+                    HumanBytes(max)
+                );
+            }
+        }
+    }
+
+    /// Whether a BYML file's YAML can be written straight to its file as it is serialized,
+    /// which only works when nothing needs the whole text first.
+    fn can_stream(&self, name: &str) -> bool {
+        let options = &self.options;
+        options.output_format == OutputFormat::Yaml
+            && options.indent.is_none()
+            && !options.compact
+            && options.split_large_yaml.is_none()
+            && !options.hashes
+            && !options.dedupe
+            && options.since.is_none()
+            && !options.skip_identical
+            && options.io_threads.is_none()
+            && !options.expand_nested
+            && options.binary_nodes == BinaryNodes::Base64
+            && options.zip.is_none()
+            && !options.compare
+            && options.compress_output == OutputCompression::None
+            && !self.is_dry_run()
+            && !(options.expand_gamedata && gamedata::is_gamedata_list(name))
+    }
+
+    /// Serializes a BYML file as YAML straight into its output file, without holding the
+    /// whole text in memory, which for the biggest maps is as much again as the parsed tree.
+    fn stream_yaml(&self, out: &Path, byml: &Byml) -> Result<()> {
+        let long = long_path(out);
+        if let Some(parent) = long.parent() {
+            retry::retry(|| fs::create_dir_all(parent))?;
+        }
+        let temp = temp_path(&long);
+        let written = self
+            .stats
+            .timings
+            .time(Stage::Serialize, || -> Result<u64> {
+                let mut writer = BufWriter::new(fs::File::create(&temp)?);
+                if self.options.sort_keys {
+                    serde_yaml::to_writer(&mut writer, &sort::SortedByml(byml))?;
+                } else {
+                    serde_yaml::to_writer(&mut writer, byml)?;
+                }
+                let file = writer.into_inner().map_err(|e| e.into_error())?;
+                let len = file.metadata()?.len();
+                drop(file);
+                fs::rename(&temp, &long)?;
+                Ok(len)
+            });
+        match written {
+            Ok(len) => {
+                self.note_output(out);
+                self.count_written(len);
+                self.written.lock().insert(out.to_path_buf(), len);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&temp);
+                Err(e)
+            }
+        }
+    }
+
+    /// Writes a BYML file's text, in parts if it is too big, returning the path of the text
+    /// or of the index of its parts.
+    fn write_byml_text(
+        &self,
+        name: &str,
+        byml: &Byml,
+        out: &Path,
+        text: String,
+    ) -> Result<PathBuf> {
+        if let Some(max) = self.options.split_large_yaml {
+            if text.len() as u64 > max {
+                let parts = (text.len() as u64).div_ceil(max.max(1)) as usize;
+                if let Some(index) = self.write_split(name, byml, out, parts)? {
+                    return Ok(index);
+                }
+            }
+        }
+        self.write_output(out, text)?;
+        Ok(out.to_path_buf())
+    }
+
+    /// Writes a file under the output folder, or into the zip archive if there is one.
+    fn write_file(&self, out: &Path, data: impl AsRef<[u8]>) -> Result<()> {
+        let data = data.as_ref();
+        self.note_output(out);
+        if let Some(writer) = &mut *self.zip.lock() {
+            let name = display_path(out.strip_prefix(&self.output)?);
+            let options = zip::write::FileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .large_file(data.len() as u64 >= u32::MAX as u64);
+            writer.start_file(name, options)?;
+            writer.write_all(data)?;
+            return Ok(());
+        }
+        if self.options.compare {
+            return self.compare_file(out, data);
+        }
+        if let Some(pool) = &*self.writer.read() {
+            let (out, data) = (out.to_path_buf(), data.to_vec());
+            pool.queue(&out.clone(), move || write_to_disk(&out, &data));
+            return Ok(());
+        }
+        write_to_disk(out, data)
+    }
+
+    /// Checks an output against the file already at its path, instead of writing it.
+    fn compare_file(&self, out: &Path, data: &[u8]) -> Result<()> {
+        let relative = out.strip_prefix(&self.output)?.to_path_buf();
+        match fs::read(long_path(out)) {
+            Ok(existing) if existing == data => {}
+            Ok(_) => {
+                log::debug!("{} doesn't match", relative.display());
+                self.comparison.lock().mismatched.push(relative);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::debug!("{} is missing", relative.display());
+                self.comparison.lock().missing.push(relative);
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", out.display())),
+        }
+        Ok(())
+    }
+
+    /// How the output folder differs from what was unpacked, when comparing against it. Only
+    /// complete once unpacking is done.
+    pub fn comparison(&self) -> verify::Report {
+        self.comparison.lock().clone()
+    }
+
+    /// Points `out` at an identical file, as a symlink where supported or else a text file.
+    fn write_link(&self, out: &Path, target: &Path) -> Result<()> {
+        if self.zip.lock().is_some() {
+            return self.write_file(out, format!("duplicate of {}", target.display()));
+        }
+        let out = long_path(out);
+        if let Some(parent) = out.parent() {
+            retry::retry(|| fs::create_dir_all(parent))?;
+        }
+        if out.symlink_metadata().is_ok() {
+            retry::retry(|| fs::remove_file(&out))?;
+        }
+        #[cfg(unix)]
+        retry::retry(|| std::os::unix::fs::symlink(target, &out))?;
+        #[cfg(not(unix))]
+        retry::retry(|| write_atomic(&out, format!("duplicate of {}", target.display())))?;
+        Ok(())
+    }
+
+    /// Whether any output for `relative` exists and is newer than its source.
+    fn is_up_to_date(&self, relative: &Path, modified: Option<SystemTime>) -> bool {
+        let Some(modified) = modified else {
+            return false;
+        };
+        let format = relative
+            .file_name()
+            .and_then(|n| Format::from_name(&n.to_string_lossy()))
+            .unwrap_or(Format::Raw);
+        [
+            self.text_path(relative, format),
+            self.raw_path(relative, format),
+        ]
+        .iter()
+        .any(|out| {
+            fs::metadata(self.options.compress_output.apply(out))
+                .and_then(|m| m.modified())
+                .map(|t| t >= modified)
+                .unwrap_or(false)
+        })
+    }
+
+    fn fail(
+        &self,
+        relative: &Path,
+        kind: manifest::FailureKind,
+        error: eyre::Report,
+    ) -> Result<()> {
+        self.stats.failures.fetch_add(1, Ordering::Relaxed);
+        self.pending_errors
+            .lock()
+            .insert(relative.to_path_buf(), format!("{error:#}"));
+        self.errors.lock().push(manifest::Failure {
+            path: relative.to_path_buf(),
+            archive: manifest::archive_of(relative).map(Path::to_path_buf),
+            kind,
+            error: error.to_string(),
+            causes: error.chain().skip(1).map(|e| e.to_string()).collect(),
+        });
+        if self.options.strict {
+            return Err(error);
+        }
+        self.warn(relative, format!("{error:#}"));
+        Ok(())
+    }
+
+    /// Holds a warning about a file until the ROM file it came from is done, so warnings
+    /// from files unpacked at the same time don't interleave.
+    fn warn(&self, relative: &Path, message: String) {
+        self.warnings
+            .lock()
+            .entry(manifest::source_of(relative).to_path_buf())
+            .or_default()
+            .push(message);
+    }
+
+    /// Logs the warnings held for a ROM file as one block under its path.
+    fn flush_warnings(&self, source: &Path) {
+        let Some(warnings) = self.warnings.lock().remove(source) else {
+            return;
+        };
+        log::warn!(
+            "{}:\n{}",
+            source.display(),
+            warnings
+                .iter()
+                .map(|w| format!("  {}", w.replace('\n', "\n  ")))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    /// Runs `op` for one file, turning a panic from a parser into a failure so the rest of
+    /// the dump can carry on.
+    fn guard(&self, relative: &Path, op: impl FnOnce() -> Result<()>) -> Result<()> {
+        match panic::catch_unwind(AssertUnwindSafe(op)) {
+            Ok(result) => result,
+            Err(payload) => {
+                self.stats.panics.fetch_add(1, Ordering::Relaxed);
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown cause".into());
+                self.fail(
+                    relative,
+                    manifest::FailureKind::Panic,
+                    eyre::eyre!("Panicked while unpacking {}: {message}", relative.display()),
+                )?;
+                self.record(relative, None, Format::Raw, false);
+                Ok(())
+            }
+        }
+    }
+
+    /// Gives an output the modification time of the ROM file it came from, if asked to.
+    fn preserve_time(&self, relative: &Path, out: &Path) {
+        if !self.options.preserve_times
+            || self.is_dry_run()
+            || self.options.zip.is_some()
+            || self.options.compare
+        {
+            return;
+        }
+        let Some(modified) = self
+            .source_times
+            .lock()
+            .get(manifest::source_of(relative))
+            .copied()
+        else {
+            return;
+        };
+        // Duplicates linked to another output would change that output's time instead
+        let is_link = out
+            .symlink_metadata()
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if !is_link {
+            let time = filetime::FileTime::from_system_time(modified);
+            if let Some(pool) = &*self.writer.read() {
+                // After the output's own write, which is queued on the same thread
+                let out = out.to_path_buf();
+                pool.queue(&out.clone(), move || {
+                    if let Err(e) = filetime::set_file_mtime(&out, time) {
+                        log::warn!("Failed to set the modified time of {}: {e}", out.display());
+                    }
+                    Ok(())
+                });
+                return;
+            }
+            if let Err(e) = filetime::set_file_mtime(out, time) {
+                self.warn(
+                    relative,
+                    format!("Failed to set the modified time of {}: {e}", out.display()),
+                );
+            }
+        }
+    }
+
+    /// Adds a file to the trace, with the time spent on it on this thread.
+    fn trace_file(&self, result: &manifest::FileResult) {
+        let mut trace = self.trace.lock();
+        let Some(trace) = trace.as_mut() else {
+            return;
+        };
+        let times = FileTimes::current();
+        let [decompress, parse, serialize] = times.nanos.map(|nanos| nanos as f64 / 1e6);
+        if let Err(e) = writeln!(
+            trace,
+            "{},{},{},{decompress:.3},{parse:.3},{serialize:.3}",
+            csv_field(&result.path.to_string_lossy()),
+            times.input,
+            result.bytes_written,
+        ) {
+            log::warn!("Failed to write to the trace: {e}");
+        }
+    }
+
+    fn record(&self, relative: &Path, output: Option<&Path>, format: Format, converted: bool) {
+        self.record_with_header(relative, output, format, converted, None)
+    }
+
+    fn record_with_header(
+        &self,
+        relative: &Path,
+        output: Option<&Path>,
+        format: Format,
+        converted: bool,
+        header: Option<BymlHeader>,
+    ) {
+        let stored = output.map(|out| self.options.compress_output.apply(out));
+        let output = stored.as_deref();
+        if self.options.dry_run {
+            let action = if converted { "convert" } else { "write raw" };
+            match output {
+                Some(out) => log::info!(
+                    "Would {action} {} ({format:?}) to {}",
+                    relative.display(),
+                    out.display()
+                ),
+                None => log::info!("Would skip {} ({format:?})", relative.display()),
+            }
+        }
+        if converted || output.is_some() {
+            self.stats.count(format, converted);
+        }
+        if let Some(out) = output {
+            self.preserve_time(relative, out);
+            if self.options.sarc_layout == SarcLayout::SingleRoot {
+                let previous = self
+                    .claimed
+                    .lock()
+                    .insert(out.to_path_buf(), relative.to_path_buf());
+                if let Some(previous) = previous.filter(|p| p != relative) {
+                    self.warn(
+                        relative,
+                        format!(
+                            "{} overwrote {} at {}",
+                            relative.display(),
+                            previous.display(),
+                            out.display()
+                        ),
+                    );
+                }
+            }
+        }
+        let error = self.pending_errors.lock().remove(relative);
+        let result = manifest::FileResult {
+            path: relative.to_path_buf(),
+            format,
+            outcome: match (converted, output, &error) {
+                (true, ..) => manifest::Outcome::Converted,
+                (false, Some(_), _) => manifest::Outcome::RawFallback,
+                (false, None, Some(_)) => manifest::Outcome::Failed,
+                (false, None, None) => manifest::Outcome::Skipped,
+            },
+            bytes_written: output
+                .and_then(|out| self.written.lock().remove(out))
+                .unwrap_or(0),
+            error,
+        };
+        self.trace_file(&result);
+        if let Some(on_file) = &self.on_file {
+            on_file(&result);
+        }
+        self.results.lock().push(result);
+        let range = self.member_ranges.lock().remove(relative);
+        self.manifest.lock().push(manifest::Entry {
+            path: relative.to_path_buf(),
+            archive: manifest::archive_of(relative).map(Path::to_path_buf),
+            output: output.and_then(|o| o.strip_prefix(&self.output).ok().map(Path::to_path_buf)),
+            format,
+            converted,
+            version: header.map(|h| h.version),
+            endian: header.map(|h| h.endian),
+            source_hash: self
+                .source_hashes
+                .lock()
+                .get(manifest::source_of(relative))
+                .cloned(),
+            offset: range.map(|(offset, _)| offset),
+            size: range.map(|(_, size)| size),
+        });
+    }
+
+    pub fn init_dicts(mut self) -> Result<Self> {
+        let mut dicts = match &self.options.zsdic_dir {
+            Some(dir) => ZsDic::open_dir(dir)?,
+            None => ZsDic::open(&self.zsdic_path())?,
+        };
+        if let Some(dict_map) = &self.options.dict_map {
+            dicts = dicts.with_dict_map(dict_map)?;
+        }
+        self.dicts = dicts;
+        if self.options.expand_map {
+            self.actor_db = self.load_actor_db()?;
+        }
+        Ok(self)
+    }
+
+    /// The ZsDic pack to load, from the options or else the newest layer that has one.
+    fn zsdic_path(&self) -> PathBuf {
+        self.options.zsdic.clone().unwrap_or_else(|| {
+            self.roots()
+                .rev()
+                .map(|root| root.join("Pack/ZsDic.pack.zs"))
+                .find(|path| path.exists())
+                .unwrap_or_else(|| self.source.join("Pack/ZsDic.pack.zs"))
+        })
+    }
+
+    /// Actor names by hash for the views to resolve hashed IDs with, if `resolve_names` is
+    /// set. They are built once, from the actor info loaded for maps or else from the ROM.
+    pub fn actor_names(&self) -> Option<Arc<names::ActorNames>> {
+        if !self.options.resolve_names {
+            return None;
+        }
+        let names = self.actor_names.get_or_init(|| {
+            let names = if self.actor_db.is_empty() {
+                match self.load_actor_db() {
+                    Ok(db) => names::ActorNames::new(&db),
+                    Err(e) => {
+                        log::warn!("Failed to load actor info, names won't be resolved: {e:#}");
+                        Default::default()
+                    }
+                }
+            } else {
+                names::ActorNames::new(&self.actor_db)
+            };
+            Arc::new(names)
+        });
+        Some(names.clone())
+    }
+
+    /// Reads the actor info tables from the newest layer that has them.
+    fn load_actor_db(&self) -> Result<map::ActorDb> {
+        let mut db = map::ActorDb::default();
+        let Some(dir) = self
+            .roots()
+            .rev()
+            .map(|root| root.join("RSDB"))
+            .find(|dir| dir.is_dir())
+        else {
+            log::warn!("No RSDB folder to load actor info from, maps won't name their actors");
+            return Ok(db);
+        };
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if !actor::is_actor_info(&name) {
+                continue;
+            }
+            let mut data = fs::read(&path)?;
+            if is_zstd(&data) {
+                data = self.decompress(&name, &data)?;
+            }
+            let table = parse_byml(&data)
+                .with_context(|| format!("Failed to parse actor info {}", path.display()))?;
+            db.add_table(&table);
+        }
+        log::info!("Loaded actor info for {} actors", db.len());
+        Ok(db)
+    }
+
+    /// Decompresses a file with the dictionary its name calls for. Misnamed or already
+    /// decompressed files are returned as they are.
+    pub fn decompress(&self, name: &str, data: &[u8]) -> Result<Vec<u8>> {
+        if !is_zstd(data) {
+            return Ok(data.to_vec());
+        }
+        self.stats
+            .timings
+            .time(Stage::Decompress, || self.dicts.decompress(name, data))
+    }
+
+    /// Decompresses a file while reading it, instead of reading it whole first. Returns
+    /// whether it was compressed, as files named `.zs` that aren't are read as they are.
+    fn decompress_stream(
+        &self,
+        file: &Path,
+        relative: &Path,
+        name: &str,
+    ) -> Result<(Vec<u8>, bool)> {
+        let mut reader = BufReader::new(fs::File::open(file)?);
+        if !is_zstd(reader.fill_buf()?) {
+            self.warn(
+                relative,
+                format!("{name} isn't zstd compressed, reading it as it is"),
+            );
+            let mut data = vec![];
+            reader.read_to_end(&mut data)?;
+            return Ok((data, false));
+        }
+        let mut data = Vec::with_capacity(frame_size(reader.fill_buf()?));
+        // This includes reading the file, which happens as it is decompressed
+        self.stats.timings.time(Stage::Decompress, || {
+            self.dicts
+                .decoder(reader, name)?
+                .read_to_end(&mut data)
+                .with_context(|| format!("Failed to decompress {}", file.display()))
+        })?;
+        Ok((data, true))
+    }
+
+    /// Converts a BYML file to YAML, decompressing it first if needed. Some files named as
+    /// BYML are really AAMP, so those are converted as AAMP instead.
+    pub fn write_byml(&self, mut data: Vec<u8>, relative: &Path) -> Result<()> {
+        let name = relative.file_name().map(|n| n.to_string_lossy()).unwrap();
+        let compressed = is_zstd(&data);
+        if compressed {
+            data = self.decompress(&name, &data)?;
+        }
+        let result = if self.detect_format(&data) == Format::Aamp {
+            self.convert_aamp(&data, relative)
+        } else {
+            self.convert_byml(data, relative, compressed)
+        };
+        self.flush_warnings(relative);
+        result
+    }
+
+    /// Converts a resource size table to YAML, decompressing it first.
+    pub fn write_rstb(&self, data: Vec<u8>, relative: &Path) -> Result<()> {
+        let name = relative.file_name().map(|n| n.to_string_lossy()).unwrap();
+        // Misnamed or already decompressed tables are read as they are
+        if !is_zstd(&data) {
+            self.warn(
+                relative,
+                format!("{name} isn't zstd compressed, reading it as it is"),
+            );
+        }
+        let result = self
+            .decompress(&name, &data)
+            .and_then(|data| self.convert_rstb(&data, relative));
+        self.flush_warnings(relative);
+        result
+    }
+
+    /// Where the output for a ROM path goes, following the SARC layout, with any leading
+    /// folders stripped and the prefix added.
+    fn output_path(&self, relative: &Path) -> PathBuf {
+        let path = self.options.sarc_layout.apply(relative);
+        let path = path
+            .components()
+            .skip(self.options.prefix_strip)
+            .collect::<PathBuf>();
+        let path = match self.options.flatten_depth {
+            Some(depth) => flatten(&path, depth),
+            None => path,
+        };
+        match &self.options.prefix_add {
+            Some(prefix) => self.output.join(prefix).join(path),
+            None => self.output.join(path),
+        }
+    }
+
+    /// Where the output for a file of the given format goes, under the folder it is routed
+    /// to if there is one.
+    fn routed_path(&self, relative: &Path, format: Format) -> PathBuf {
+        let out = self.output_path(relative);
+        match self.options.routes.get(&format) {
+            Some(folder) => self
+                .output
+                .join(folder)
+                .join(out.strip_prefix(&self.output).unwrap_or(&out)),
+            None => out,
+        }
+    }
+
+    /// Where the output for a ROM path goes once decompressed, without its `.zs` extension.
+    fn raw_path(&self, relative: &Path, format: Format) -> PathBuf {
+        let out = self.routed_path(relative, format);
+        match out.extension() {
+            Some(ext) if ext == "zs" => out.with_extension(""),
+            _ => out,
+        }
+    }
+
+    /// Where the text form of a file in the given format goes. Summaries always keep the
+    /// full name of the file they sit next to, and other files follow the extension style.
+    fn text_path(&self, relative: &Path, format: Format) -> PathBuf {
+        let style = if format.is_summarized() {
+            ExtensionStyle::Append
+        } else {
+            self.options.extension_style
+        };
+        style.apply(
+            &self.routed_path(relative, format),
+            self.options.output_format.extension(),
+        )
+    }
+
+    /// Writes data as-is, dropping the `.zs` extension since it has been decompressed.
+    fn write_raw(&self, relative: &Path, data: &[u8], format: Format) -> Result<()> {
+        if format == Format::Raw {
+            self.note_unhandled(relative, data);
+        }
+        let out = self.raw_path(relative, format);
+        self.write_output(&out, data)?;
+        self.record(relative, Some(&out), format, false);
+        Ok(())
+    }
+
+    /// Serializes to the output format, with the indentation and compactness chosen.
+    fn to_text<T: Serialize>(&self, value: &T) -> Result<String> {
+        self.options
+            .output_format
+            .serialize_with(value, self.options.indent, self.options.compact)
+    }
+
+    /// Serializes a value to be written at `out`, falling back to YAML if TOML can't
+    /// express it. Returns the path with the extension of the format used and the text.
+    fn to_text_at<T: Serialize>(
+        &self,
+        relative: &Path,
+        out: &Path,
+        value: &T,
+    ) -> Result<(PathBuf, String)> {
+        self.stats
+            .timings
+            .time(Stage::Serialize, || match self.to_text(value) {
+                Err(e) if self.options.output_format == OutputFormat::Toml => {
+                    self.yaml_fallback(relative, out, value, e)
+                }
+                text => Ok((out.to_path_buf(), text?)),
+            })
+    }
+
+    /// For values TOML can't express, like empty values or arrays of tables among scalars.
+    fn yaml_fallback<T: Serialize>(
+        &self,
+        relative: &Path,
+        out: &Path,
+        value: &T,
+        error: eyre::Report,
+    ) -> Result<(PathBuf, String)> {
+        self.warn(
+            relative,
+            format!(
+                "Writing {} as YAML, since TOML can't express it: {error}",
+                out.display()
+            ),
+        );
+        let text =
+            OutputFormat::Yaml.serialize_with(value, self.options.indent, self.options.compact)?;
+        Ok((out.with_extension(OutputFormat::Yaml.extension()), text))
+    }
+
+    /// Serializes a BYML file, given its path in the ROM as `name`.
+    fn serialize_byml(&self, name: &str, byml: &Byml, out: &Path) -> Result<(PathBuf, String)> {
+        let relative = Path::new(name);
+        if self.options.expand_gamedata && gamedata::is_gamedata_list(name) {
+            let names = self.actor_names();
+            match gamedata::GameDataList::new(byml, names.as_deref()) {
+                Some(list) => return self.to_text_at(relative, out, &list),
+                None => self.warn(
+                    relative,
+                    format!("{name} does not look like a GameDataList, dumping it as BYML"),
+                ),
+            }
+        }
+        if self.options.expand_nested || self.options.binary_nodes != BinaryNodes::Base64 {
+            // Binary nodes written to files of their own are named after the text's file
+            let stem = out.with_extension("");
+            let prefix = stem.file_name().unwrap_or_default().to_string_lossy();
+            let external = RefCell::new(vec![]);
+            let nested = nested::Nested {
+                byml,
+                sort: self.options.sort_keys,
+                expand: self.options.expand_nested,
+                binary: self.options.binary_nodes,
+                external: &external,
+                prefix: &prefix,
+            };
+            let text = self.to_text_at(relative, out, &nested)?;
+            for (index, data) in external.into_inner().into_iter().enumerate() {
+                self.write_extra(&out.with_file_name(format!("{prefix}.{index}.bin")), data)?;
+            }
+            return Ok(text);
+        }
+        if self.options.output_format == OutputFormat::Toml {
+            // TOML has nowhere to put the type tags, so nodes are written as plain values
+            return match self.to_text(&gamedata::Plain(byml)) {
+                Ok(text) => Ok((out.to_path_buf(), text)),
+                Err(e) if self.options.sort_keys => {
+                    self.yaml_fallback(relative, out, &sort::SortedByml(byml), e)
+                }
+                Err(e) => self.yaml_fallback(relative, out, byml, e),
+            };
+        }
+        if self.options.sort_keys {
+            self.to_text_at(relative, out, &sort::SortedByml(byml))
+        } else {
+            self.to_text_at(relative, out, byml)
+        }
+    }
+
+    /// Writes how a BYML file was stored next to its text output.
+    fn write_meta(
+        &self,
+        relative: &Path,
+        out: &Path,
+        header: BymlHeader,
+        compressed: bool,
+    ) -> Result<()> {
+        let name = relative.file_name().map(|n| n.to_string_lossy()).unwrap();
+        let meta = manifest::BymlMeta {
+            version: header.version,
+            endian: header.endian,
+            compressed,
+            dictionary: if compressed {
+                self.dicts.dictionary_name(&name)
+            } else {
+                None
+            },
+        };
+        self.write_extra(
+            &out.with_extension("meta.json"),
+            serde_json::to_string_pretty(&meta)?,
+        )
+    }
+
+    /// Writes a document as numbered parts of its top-level entries next to `out`, with an
+    /// index listing them, returning the index's path. Returns None without writing anything
+    /// if the document isn't an array or map.
+    fn write_split(
+        &self,
+        name: &str,
+        byml: &Byml,
+        out: &Path,
+        parts: usize,
+    ) -> Result<Option<PathBuf>> {
+        let Some(split) = split::split(byml, parts) else {
+            return Ok(None);
+        };
+        let ext = out
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stem = out.with_extension("");
+        let mut names = vec![];
+        for (i, part) in split.iter().enumerate() {
+            let mut path = stem.clone().into_os_string();
+            path.push(format!(".part{i:03}.{ext}"));
+            let (path, text) = self.serialize_byml(name, part, Path::new(&path))?;
+            self.write_extra(&path, text)?;
+            names.push(
+                path.file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            );
+        }
+        let index = out.with_extension("parts.json");
+        self.write_output(
+            &index,
+            serde_json::to_string_pretty(&serde_json::json!({
+                "kind": split::kind(byml),
+                "parts": names,
+            }))?,
+        )?;
+        Ok(Some(index))
+    }
+
+    fn convert_byml(&self, data: Vec<u8>, relative: &Path, compressed: bool) -> Result<()> {
+        let Some(header) = byml_header(&data) else {
+            return self.write_raw(relative, &data, Format::Raw);
+        };
+        let mut parsed = self.stats.timings.time(Stage::Parse, || parse_byml(&data));
+        if let (true, Ok(byml)) = (self.options.verify_roundtrip, &parsed) {
+            // Rebuilt in the file's own byte order, so the version bytes can be copied over
+            // as they are. roead writes its own version, which doesn't change the layout.
+            let mut rebuilt = byml.to_binary(header.endian.into());
+            rebuilt[2..4].copy_from_slice(&data[2..4]);
+            self.verify_roundtrip(relative, &data, &rebuilt);
+        }
+        if let Some(style) = self.options.canonicalize_floats {
+            parsed = parsed.map(|byml| floats::canonicalize(byml, style));
+        }
+        if let Some(actor) = &self.options.actor {
+            if actor::is_actor_info(&relative.to_string_lossy()) {
+                parsed = parsed.map(|table| actor::rows_for(table, actor));
+            }
+        }
+        if self.options.expand_map && map::is_map(&relative.to_string_lossy()) {
+            parsed = parsed.map(|byml| map::annotate(byml, &self.actor_db));
+        }
+        match parsed {
+            Ok(_) if self.options.validate => {
+                self.record_with_header(relative, None, Format::Byml, true, Some(header));
+            }
+            Ok(byml) => {
+                let out = self.text_path(relative, Format::Byml);
+                let name = relative.to_string_lossy();
+                let serialized = if self.can_stream(&name) {
+                    self.stream_yaml(&out, &byml).map(|()| (out.clone(), None))
+                } else {
+                    self.serialize_byml(&name, &byml, &out)
+                        .map(|(out, text)| (out, Some(text)))
+                };
+                match serialized {
+                    Ok((out, text)) => {
+                        let written = match text {
+                            Some(text) => self.write_byml_text(&name, &byml, &out, text)?,
+                            // Already written as it was serialized
+                            None => out.clone(),
+                        };
+                        if self.options.sidecar_meta {
+                            self.write_meta(relative, &out, header, compressed)?;
+                        }
+                        self.record_with_header(
+                            relative,
+                            Some(&written),
+                            Format::Byml,
+                            true,
+                            Some(header),
+                        );
+                    }
+                    Err(e) => {
+                        self.fail(
+                            relative,
+                            manifest::FailureKind::Serialize,
+                            e.wrap_err(format!(
+                                "Could not dump {} to {}",
+                                relative.display(),
+                                self.options.output_format,
+                            )),
+                        )?;
+                        self.record(relative, None, Format::Byml, false);
+                    }
+                }
+            }
+            Err(e) => {
+                self.fail(
+                    relative,
+                    manifest::FailureKind::Parse,
+                    e.wrap_err(format!("Failed to parse {}", relative.display())),
+                )?;
+                self.write_raw(relative, &data, Format::Byml)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the text form of whatever `parse` reads from the data to `out`, or the data
+    /// raw if parsing fails. Returns whether it parsed.
+    fn convert_parsed<T: Serialize>(
+        &self,
+        data: &[u8],
+        relative: &Path,
+        out: PathBuf,
+        format: Format,
+        parse: impl FnOnce(&[u8]) -> Result<T>,
+    ) -> Result<bool> {
+        match parse(data) {
+            Ok(_) if self.options.validate => self.record(relative, None, format, true),
+            Ok(parsed) => {
+                let (out, text) = self.to_text_at(relative, &out, &parsed)?;
+                self.write_output(&out, text)?;
+                self.record(relative, Some(&out), format, true);
+            }
+            Err(e) => {
+                self.fail(
+                    relative,
+                    manifest::FailureKind::Parse,
+                    e.wrap_err(format!("Failed to parse {}", relative.display())),
+                )?;
+                self.write_raw(relative, data, format)?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn convert_rstb(&self, data: &[u8], relative: &Path) -> Result<()> {
+        self.convert_parsed(
+            data,
+            relative,
+            self.text_path(relative, Format::Rstb),
+            Format::Rstb,
+            rstb::ResourceSizeTable::from_binary,
+        )?;
+        Ok(())
+    }
+
+    /// Writes a read-only summary of an audio, AI, event, texture, terrain, physics, or font
+    /// file next to the file itself, which is written raw since the summary can't be repacked.
+    fn convert_summary(&self, data: &[u8], relative: &Path, format: Format) -> Result<()> {
+        let out = self.raw_path(relative, format);
+        let summary = self.text_path(relative, format);
+        let parsed = match format {
+            Format::Bars => {
+                self.convert_parsed(data, relative, summary, format, audio::Bars::from_binary)?
+            }
+            Format::Bwav => {
+                self.convert_parsed(data, relative, summary, format, audio::Bwav::from_binary)?
+            }
+            Format::Ainb => {
+                self.convert_parsed(data, relative, summary, format, ainb::Ainb::from_binary)?
+            }
+            Format::Bntx => {
+                let parsed = self.convert_parsed(
+                    data,
+                    relative,
+                    summary,
+                    format,
+                    texture::Bntx::from_binary,
+                )?;
+                if self.options.extract_textures && !self.options.validate {
+                    self.write_surfaces(data, &out)?;
+                }
+                parsed
+            }
+            Format::Tscb => {
+                self.convert_parsed(data, relative, summary, format, terrain::Tscb::from_binary)?
+            }
+            Format::Phive => self.convert_parsed(
+                data,
+                relative,
+                summary,
+                format,
+                physics::Physics::from_binary,
+            )?,
+            Format::Bffnt => {
+                self.convert_parsed(data, relative, summary, format, font::Bffnt::from_binary)?
+            }
+            _ => self.convert_parsed(
+                data,
+                relative,
+                summary,
+                format,
+                bfevfl::EventFlow::from_binary,
+            )?,
+        };
+        // Files that don't parse were already written raw in place of their summary
+        if parsed {
+            self.write_extra(&out, data)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the data of each texture's mip levels to `<out>.surfaces/<texture>/mip<n>.bin`,
+    /// still swizzled. Files that don't parse were already reported by their summary.
+    fn write_surfaces(&self, data: &[u8], out: &Path) -> Result<()> {
+        let Ok(bntx) = texture::Bntx::from_binary(data) else {
+            return Ok(());
+        };
+        let mut surfaces = out.to_path_buf().into_os_string();
+        surfaces.push(".surfaces");
+        let surfaces = PathBuf::from(surfaces);
+        for texture in &bntx.textures {
+            for (i, mip) in texture.mips(data)?.into_iter().enumerate() {
+                let path = surfaces.join(&texture.name).join(format!("mip{i}.bin"));
+                self.write_extra(&path, mip)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Warns if a parsed file serialized back to binary differs from the original, saying
+    /// where the first difference is.
+    fn verify_roundtrip(&self, relative: &Path, original: &[u8], rebuilt: &[u8]) {
+        if original == rebuilt {
+            return;
+        }
+        let first = original
+            .iter()
+            .zip(rebuilt)
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| original.len().min(rebuilt.len()));
+        self.warn(
+            relative,
+            format!(
+                "{} does not round-trip: {} bytes rebuilt from {}, first differing at 0x{first:x}",
+                relative.display(),
+                rebuilt.len(),
+                original.len(),
+            ),
+        );
+        self.stats
+            .roundtrip_mismatches
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn convert_aamp(&self, data: &[u8], relative: &Path) -> Result<()> {
+        let parsed = self
+            .stats
+            .timings
+            .time(Stage::Parse, || roead::aamp::ParameterIO::from_binary(data));
+        if let (true, Ok(pio)) = (self.options.verify_roundtrip, &parsed) {
+            self.verify_roundtrip(relative, data, &pio.to_binary());
+        }
+        match parsed {
+            Ok(_) if self.options.validate => self.record(relative, None, Format::Aamp, true),
+            Ok(pio) => {
+                let out = self.text_path(relative, Format::Aamp);
+                match self.to_text_at(relative, &out, &pio) {
+                    Ok((out, text)) => {
+                        self.write_output(&out, text)?;
+                        self.record(relative, Some(&out), Format::Aamp, true);
+                    }
+                    Err(e) => {
+                        self.fail(
+                            relative,
+                            manifest::FailureKind::Serialize,
+                            e.wrap_err(format!(
+                                "Could not dump {} to {}",
+                                relative.display(),
+                                self.options.output_format
+                            )),
+                        )?;
+                        self.write_raw(relative, data, Format::Aamp)?;
+                    }
+                }
+            }
+            Err(e) => {
+                self.fail(
+                    relative,
+                    manifest::FailureKind::Parse,
+                    eyre::Report::new(e)
+                        .wrap_err(format!("Failed to parse {}", relative.display())),
+                )?;
+                self.write_raw(relative, data, Format::Aamp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn convert_msbt(&self, data: &[u8], relative: &Path) -> Result<()> {
+        match self.stats.timings.time(Stage::Parse, || parse_msbt(data)) {
+            Ok(_) if self.options.validate => self.record(relative, None, Format::Msbt, true),
+            Ok(msbt) => {
+                let out = self.text_path(relative, Format::Msbt);
+                match self.to_text_at(relative, &out, &msbt) {
+                    Ok((out, text)) => {
+                        self.write_output(&out, text)?;
+                        self.record(relative, Some(&out), Format::Msbt, true);
+                    }
+                    Err(e) => {
+                        self.fail(
+                            relative,
+                            manifest::FailureKind::Serialize,
+                            e.wrap_err(format!(
+                                "Failed to dump MSBT file {} to {}",
+                                relative.display(),
+                                self.options.output_format
+                            )),
+                        )?;
+                        self.record(relative, None, Format::Msbt, false);
+                    }
+                };
+            }
+            Err(e) => {
+                self.fail(
+                    relative,
+                    manifest::FailureKind::Parse,
+                    e.wrap_err(format!("Failed to parse MSBT file {}", relative.display())),
+                )?;
+                self.record(relative, None, Format::Msbt, false);
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts one file to text in the output format, without writing anything. Archives
+    /// are converted to one document mapping their members' names to their values.
+    pub fn convert_to_text(&self, name: &str, data: Vec<u8>) -> Result<String> {
+        let data = if is_zstd(&data) {
+            self.decompress(name, &data)?
+        } else {
+            data
+        };
+        let out = Path::new(name);
+        let (_, text) = match self.detect_format(&data) {
+            Format::Byml => {
+                let mut byml = parse_byml(&data)?;
+                if let Some(style) = self.options.canonicalize_floats {
+                    byml = floats::canonicalize(byml, style);
+                }
+                self.serialize_byml(name, &byml, out)?
+            }
+            Format::Rstb => {
+                self.to_text_at(out, out, &rstb::ResourceSizeTable::from_binary(&data)?)?
+            }
+            Format::Aamp => {
+                self.to_text_at(out, out, &roead::aamp::ParameterIO::from_binary(&data)?)?
+            }
+            Format::Msbt => self.to_text_at(out, out, &parse_msbt(&data)?)?,
+            Format::Bars => self.to_text_at(out, out, &audio::Bars::from_binary(&data)?)?,
+            Format::Bwav => self.to_text_at(out, out, &audio::Bwav::from_binary(&data)?)?,
+            Format::Ainb => self.to_text_at(out, out, &ainb::Ainb::from_binary(&data)?)?,
+            Format::Bfevfl => self.to_text_at(out, out, &bfevfl::EventFlow::from_binary(&data)?)?,
+            Format::Bntx => self.to_text_at(out, out, &texture::Bntx::from_binary(&data)?)?,
+            Format::Tscb => self.to_text_at(out, out, &terrain::Tscb::from_binary(&data)?)?,
+            Format::Phive => self.to_text_at(out, out, &physics::Physics::from_binary(&data)?)?,
+            Format::Bffnt => self.to_text_at(out, out, &font::Bffnt::from_binary(&data)?)?,
+            // As one document, as when bundling archives
+            Format::Sarc => {
+                self.to_text_at(out, out, &self.bundle_members(&data, out, true, 0)?)?
+            }
+            Format::Raw => bail!("{name} cannot be converted to text (Raw)"),
+        };
+        self.flush_warnings(out);
+        Ok(text)
+    }
+
+    /// Reads the first few decompressed bytes of a loose file.
+    fn peek(&self, file: &Path, name: &str) -> Result<Vec<u8>> {
+        let mut magic = Vec::with_capacity(8);
+        fs::File::open(file)?.take(8).read_to_end(&mut magic)?;
+        if is_zstd(&magic) {
+            let decoder = self
+                .dicts
+                .decoder(BufReader::new(fs::File::open(file)?), name)?;
+            magic.clear();
+            decoder.take(8).read_to_end(&mut magic)?;
+        }
+        Ok(magic)
+    }
+
+    /// Detects the format of data from its magic, checking the user's magic table first.
+    fn detect_format(&self, data: &[u8]) -> Format {
+        self.options
+            .magic_table
+            .get(data)
+            .unwrap_or_else(|| detect_format(data))
+    }
+
+    /// Cheaply guesses a loose file's format from its first few decompressed bytes.
+    fn sniff(&self, file: &Path, name: &str) -> Result<Format> {
+        Ok(self.detect_format(&self.peek(file, name)?))
+    }
+
+    /// Counts a file that no converter handles, by its extension and magic.
+    fn note_unhandled(&self, relative: &Path, data: &[u8]) {
+        if !self.options.report_unhandled {
+            return;
+        }
+        let extension = extension_of(relative);
+        let magic = data.get(..4).unwrap_or(data);
+        let magic = if magic.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            String::from_utf8_lossy(magic).into_owned()
+        } else {
+            magic.iter().map(|b| format!("{b:02x}")).collect()
+        };
+        *self.unhandled.lock().entry((extension, magic)).or_default() += 1;
+    }
+
+    /// How many files of each extension and magic no converter handled, most common
+    /// first. Only counted with `report_unhandled`.
+    pub fn unhandled(&self) -> Vec<(String, String, usize)> {
+        let mut unhandled = self
+            .unhandled
+            .lock()
+            .iter()
+            .map(|((ext, magic), count)| (ext.clone(), magic.clone(), *count))
+            .collect::<Vec<_>>();
+        unhandled.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1))));
+        unhandled
+    }
+
+    /// Walks a folder, following symlinks if asked to. Each folder is only visited once by its
+    /// real path, so links that loop back are skipped.
+    fn walk(&self, root: &Path) -> jwalk::WalkDir {
+        let walk = jwalk::WalkDir::new(root).follow_links(self.options.follow_symlinks);
+        if !self.options.follow_symlinks {
+            return walk;
+        }
+        let visited = Mutex::new(HashSet::new());
+        visited
+            .lock()
+            .insert(root.canonicalize().unwrap_or_else(|_| root.into()));
+        walk.process_read_dir(move |_, _, _, children| {
+            children.retain(|child| {
+                let Ok(child) = child else {
+                    return true;
+                };
+                if !child.file_type().is_dir() {
+                    return true;
+                }
+                let path = child.path();
+                let real = path.canonicalize().unwrap_or_else(|_| path.clone());
+                let first = visited.lock().insert(real);
+                if !first {
+                    log::warn!(
+                        "Skipping {}, a link to a folder already walked",
+                        path.display()
+                    );
+                }
+                first
+            });
+        })
+    }
+
+    /// Every file in the source folder and layers, sorted, with layers overriding.
+    fn source_files(&self) -> Result<Vec<PathBuf>> {
+        // Walking a whole ROM takes a while, so show that something is happening
+        let spinner = self.progress.add(ProgressBar::new_spinner().with_style(
+            ProgressStyle::with_template("{spinner} Scanning files… {pos} found")?,
+        ));
+        spinner.enable_steady_tick(Duration::from_millis(100));
+        let mut files = BTreeMap::new();
+        for root in self.roots() {
+            for entry in self.walk(root).into_iter().filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if entry.file_type().is_dir() {
+                    continue;
+                }
+                if let Ok(relative) = path.strip_prefix(root) {
+                    files.insert(relative.to_path_buf(), path);
+                    spinner.set_position(files.len() as u64);
+                }
+            }
+        }
+        spinner.finish_and_clear();
+        log::info!("Found {} files", files.len());
+        Ok(files.into_values().collect())
+    }
+
+    /// A ROM file's path relative to the base outputs are placed against, which is the
+    /// source folder or layer it is in unless another base is set.
+    fn relative_path<'a>(&self, file: &'a Path) -> Result<&'a Path> {
+        match &self.options.relative_to {
+            Some(base) => file
+                .strip_prefix(base)
+                .with_context(|| format!("{} is not under {}", file.display(), base.display())),
+            None => self
+                .roots()
+                .find_map(|root| file.strip_prefix(root).ok())
+                .context("File is not in the source folder"),
+        }
+    }
+
+    /// Walks the whole source folder and any layers, and unpacks every file found.
+    pub fn unpack(&self) -> Result<()> {
+        self.unpack_files(self.source_files()?)
+    }
+
+    /// The game version of the ROM, as given in the options or else read from its file
+    /// names, taking the newest of any layer.
+    pub fn game_version(&self) -> Option<version::GameVersion> {
+        self.options
+            .game_version
+            .or_else(|| version::GameVersion::detect(self.roots().map(PathBuf::as_path)))
+    }
+
+    /// Warns if the ROM is a game version the parsers weren't written for, as their output
+    /// could then be subtly wrong without anything failing.
+    fn check_game_version(&self) {
+        let (first, last) = (version::SUPPORTED.start(), version::SUPPORTED.end());
+        match self.game_version() {
+            Some(version) if !version.is_supported() => log::warn!(
+                "The ROM is game version {version}, but this release was written for {first} \
+                 to {last}. Check the output carefully, or use a newer release."
+            ),
+            Some(version) => log::info!("Unpacking game version {version}"),
+            None => log::debug!("Could not tell which game version the ROM is"),
+        }
+    }
+
+    /// Unpacks the members of a single archive, given by its path relative to the source
+    /// folder or just its filename, without touching any other file.
+    pub fn unpack_archive(&self, name: &str) -> Result<()> {
+        let by_path = self
+            .roots()
+            .rev()
+            .map(|root| root.join(name))
+            .find(|path| path.is_file());
+        let file = match by_path {
+            Some(file) => file,
+            None => {
+                let matches = self
+                    .source_files()?
+                    .into_iter()
+                    .filter(|file| file.file_name().map(|n| n == name).unwrap_or(false))
+                    .collect::<Vec<_>>();
+                match matches.as_slice() {
+                    [file] => file.clone(),
+                    [] => bail!("No archive named {name} found in the source folder"),
+                    _ => bail!(
+                        "{name} matches more than one file, use its full path instead:\n{}",
+                        matches
+                            .iter()
+                            .map(|m| format!("  {}", m.display()))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    ),
+                }
+            }
+        };
+        let file_name = file
+            .file_name()
+            .context("No filename")?
+            .to_string_lossy()
+            .into_owned();
+        ensure!(
+            self.sniff(&file, &file_name)? == Format::Sarc,
+            "{} is not a SARC archive",
+            file.display()
+        );
+        self.unpack_files(vec![file])
+    }
+
+    /// Unpacks one file, loose or inside an archive, on this thread alone, for debugging a
+    /// file that fails. Each step is logged, and the file's bytes as stored and once
+    /// decompressed are kept in `_test-one` in the output folder. Members are given under
+    /// their archive's path, like `Pack/Actor/Foo.pack.zs/Component/Foo.bgyml`.
+    pub fn test_one(&self, relative: &Path) -> Result<()> {
+        let start = Instant::now();
+        let components = relative.components().collect::<Vec<_>>();
+        let (file, rest) = (1..=components.len())
+            .find_map(|len| {
+                let prefix = components[..len].iter().collect::<PathBuf>();
+                let file = self
+                    .roots()
+                    .rev()
+                    .map(|root| root.join(&prefix))
+                    .find(|path| path.is_file())?;
+                Some((file, components[len..].iter().collect::<PathBuf>()))
+            })
+            .with_context(|| format!("{} isn't in the ROM", relative.display()))?;
+        log::info!("Reading {}", file.display());
+        let mut data = fs::read(&file)?;
+        let mut name = file
+            .file_name()
+            .context("No filename")?
+            .to_string_lossy()
+            .into_owned();
+        let mut rest = rest.as_path();
+        let mut depth = 0;
+        while !rest.as_os_str().is_empty() {
+            if is_zstd(&data) {
+                data = self.decompress(&name, &data)?;
+            }
+            let (member, member_data) = {
+                let sarc = Sarc::new(&data).with_context(|| {
+                    format!("{name} is not an archive, so can't hold {}", rest.display())
+                })?;
+                // The member named by the whole rest of the path, or else an archive it's in
+                let (member, member_data) = sarc
+                    .files()
+                    .filter_map(|file| Some((Path::new(file.name()?), file.data)))
+                    .filter(|(member, _)| rest.starts_with(member))
+                    .max_by_key(|(member, _)| member.components().count())
+                    .with_context(|| format!("{name} has no member {}", rest.display()))?;
+                (member.to_path_buf(), member_data.to_vec())
+            };
+            log::info!(
+                "Found {} in {name}, {} bytes",
+                member.display(),
+                member_data.len()
+            );
+            name = member
+                .file_name()
+                .context("No filename")?
+                .to_string_lossy()
+                .into_owned();
+            rest = rest.strip_prefix(&member)?;
+            data = member_data;
+            depth += 1;
+        }
+        let compressed = is_zstd(&data);
+        log::info!(
+            "{name} is {} bytes, {}",
+            data.len(),
+            if compressed {
+                "zstd compressed"
+            } else {
+                "not compressed"
+            }
+        );
+        let decompressed = if compressed {
+            let decompressed = self.decompress(&name, &data)?;
+            log::info!("Decompressed to {} bytes", decompressed.len());
+            Some(decompressed)
+        } else {
+            None
+        };
+        let plain = decompressed.as_deref().unwrap_or(&data);
+        log::info!(
+            "Format by name: {:?}, by magic: {:?}, magic bytes: {:02x?}",
+            Format::from_name(&name),
+            self.detect_format(plain),
+            &plain[..plain.len().min(8)]
+        );
+        if !self.is_dry_run() {
+            let dir = self.output.join("_test-one");
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join(&name), &data)?;
+            if let Some(decompressed) = &decompressed {
+                fs::write(dir.join(format!("{name}.decompressed")), decompressed)?;
+            }
+            log::info!("Kept its bytes in {}", dir.display());
+        }
+        let context = ArchiveContext {
+            included: true,
+            depth,
+            modified: None,
+        };
+        let result = self.unpack_data(&data, relative, context);
+        self.flush_warnings(relative);
+        for result in self.results() {
+            log::info!(
+                "{}: {:?} as {:?}, {} bytes written{}",
+                result.path.display(),
+                result.outcome,
+                result.format,
+                result.bytes_written,
+                result.error.map(|e| format!(", {e}")).unwrap_or_default()
+            );
+        }
+        log::info!(
+            "Done in {:.2?}, stage times:\n{}",
+            start.elapsed(),
+            self.stats.timings
+        );
+        result
+    }
+
+    /// Prints a tree of every file and archive member with its size and format, without
+    /// converting or writing anything.
+    pub fn list(&self) -> Result<()> {
+        let trees = self
+            .source_files()?
+            .par_iter()
+            .map(|file| -> Result<String> {
+                let relative = self.relative_path(file)?;
+                let mut tree = String::new();
+                self.list_data(&fs::read(file)?, &relative.to_string_lossy(), 0, &mut tree);
+                Ok(tree)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for tree in trees {
+            print!("{tree}");
+        }
+        Ok(())
+    }
+
+    fn list_data(&self, data: &[u8], name: &str, depth: usize, tree: &mut String) {
+        let indent = "  ".repeat(depth);
+        let decompressed;
+        let data = if is_zstd(data) {
+            match self.decompress(name, data) {
+                Ok(data) => {
+                    decompressed = data;
+                    &decompressed[..]
+                }
+                Err(_) => {
+                    tree.push_str(&format!("{indent}{name} (could not decompress)\n"));
+                    return;
+                }
+            }
+        } else {
+            data
+        };
+        let format = self.detect_format(data);
+        tree.push_str(&format!(
+            "{indent}{name} ({}, {format:?})\n",
+            HumanBytes(data.len() as u64)
+        ));
+        if format == Format::Sarc && depth < MAX_SARC_DEPTH {
+            if let Ok(sarc) = Sarc::new(data) {
+                for (index, file) in sarc.files().enumerate() {
+                    let name = file
+                        .name()
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| format!("__unnamed_{index:04}"));
+                    self.list_data(file.data, &name, depth + 1, tree);
+                }
+            }
+        }
+    }
+
+    /// Unpacks an explicit list of files, which must be inside the source folder or a layer.
+    pub fn unpack_files(&self, files: Vec<PathBuf>) -> Result<()> {
+        // Outputs of the files skipped aren't written again, so they would all be removed
+        ensure!(
+            !self.options.clean
+                || !(self.options.incremental
+                    || self.options.skip_unchanged
+                    || self.options.checkpoint.is_some()
+                    || self.options.zip.is_some()),
+            "Cleaning can't be combined with skipping files done before or writing a zip"
+        );
+        // Each of these would leave outputs unchecked, or write them some other way
+        ensure!(
+            !self.options.compare
+                || !(self.options.incremental
+                    || self.options.skip_unchanged
+                    || self.options.skip_identical
+                    || self.options.checkpoint.is_some()
+                    || self.options.clean
+                    || self.options.dedupe
+                    || self.options.since.is_some()
+                    || self.options.zip.is_some()
+                    || self.is_dry_run()),
+            "Comparing against a dump can't be combined with skipping files, linking \
+             duplicates, cleaning, a dry run, or writing a zip"
+        );
+        self.check_game_version();
+        let files = match &self.options.checkpoint {
+            Some(path) => self.open_checkpoint(path, files)?,
+            None => files,
+        };
+        // Progress is weighted by size, since a few huge packs take most of the time
+        let files = files
+            .into_iter()
+            .map(|file| {
+                let len = fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+                (file, len)
+            })
+            .collect::<Vec<_>>();
+        let total = files.iter().map(|(_, len)| len).sum();
+        let ticker = ticker::Ticker::new(
+            files.len(),
+            self.progress.is_hidden(),
+            self.options.progress_json,
+        );
+        if self.options.skip_unchanged {
+            self.load_previous()?;
+        }
+        if let Some(path) = &self.options.zip {
+            if !self.is_dry_run() {
+                let file = fs::File::create(path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                *self.zip.lock() = Some(ZipWriter::new(file));
+            }
+        }
+        if let Some(path) = &self.options.trace {
+            let file = fs::File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            let mut trace = BufWriter::new(file);
+            writeln!(
+                trace,
+                "path,input_bytes,output_bytes,decompress_ms,parse_ms,serialize_ms"
+            )?;
+            *self.trace.lock() = Some(trace);
+        }
+        if let (Some(threads), None) = (self.options.io_threads, &self.options.zip) {
+            *self.writer.write() = Some(writer::WritePool::new(threads, self.stop.clone())?);
+        }
+        let bar = self.progress.add(ProgressBar::new(total).with_style(
+            ProgressStyle::with_template(
+                "{wide_bar} {bytes}/{total_bytes} ({binary_bytes_per_sec}, ETA {eta})",
+            )?,
+        ));
+        let unpacked = files
+            .into_par_iter()
+            .try_for_each(|(file, len)| -> Result<()> {
+                if self.is_stopped() {
+                    return Ok(());
+                }
+                let result = self.guard(&file, || self.unpack_file(&file));
+                // Panics are recorded under the full path
+                self.flush_warnings(&file);
+                if let Ok(relative) = self.relative_path(&file) {
+                    self.flush_warnings(relative);
+                }
+                result?;
+                self.checkpoint_file(&file)?;
+                bar.inc(len);
+                ticker.tick(self.relative_path(&file).unwrap_or(&file));
+                Ok(())
+            });
+        // Everything handed to the writer threads is written even if unpacking failed
+        let written = match self.writer.write().take() {
+            Some(pool) => pool.finish(),
+            None => Ok(()),
+        };
+        unpacked?;
+        bar.finish();
+        let sources = self.warnings.lock().keys().cloned().collect::<Vec<_>>();
+        for source in sources {
+            self.flush_warnings(&source);
+        }
+        written?;
+        if let Some(mut trace) = self.trace.lock().take() {
+            trace.flush()?;
+        }
+        self.write_manifest()?;
+        if let (Some(path), false) = (&self.options.paths_file, self.is_dry_run()) {
+            self.write_paths(path)?;
+        }
+        if self.options.clean {
+            self.clean()?;
+        }
+        if self.options.compare {
+            self.find_extra();
+        }
+        if self.is_stopped() {
+            log::warn!("Stopped early. {}", self.stats);
+        } else {
+            log::info!("Done. {}", self.stats);
+        }
+        Ok(())
+    }
+
+    /// Writes the path of every output this run wrote or left unchanged, relative to the
+    /// output folder and sorted, one a line. Files describing the dump itself are left out.
+    fn write_paths(&self, path: &Path) -> Result<()> {
+        let mut paths = self
+            .outputs
+            .lock()
+            .iter()
+            .filter_map(|out| out.strip_prefix(&self.output).ok())
+            .filter(|out| {
+                !since::METADATA_FILES
+                    .iter()
+                    .any(|name| *out == Path::new(name))
+            })
+            .map(display_path)
+            .collect::<Vec<_>>();
+        paths.sort();
+        let mut text = String::new();
+        for path in paths {
+            text.push_str(&path);
+            text.push('\n');
+        }
+        fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Removes every file in the output folder that this run didn't write, other than
+    /// hidden ones, and then any folders left empty. Nothing is removed if the run stopped
+    /// early.
+    fn clean(&self) -> Result<()> {
+        if self.is_stopped() {
+            log::warn!("Not cleaning the output folder, since the dump stopped early");
+            return Ok(());
+        }
+        let (stale, mut folders) = self.unrecorded();
+        if self.is_dry_run() {
+            log::info!("Would remove {} stale files", stale.len());
+            return Ok(());
+        }
+        for path in &stale {
+            log::debug!("Removing stale {}", path.display());
+            retry::retry(|| fs::remove_file(path))
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        // Deepest first, so folders holding only empty folders go too
+        folders.sort_by_key(|folder| std::cmp::Reverse(folder.components().count()));
+        for folder in folders.iter().filter(|folder| **folder != self.output) {
+            // Fails for folders that aren't empty, which stay
+            let _ = fs::remove_dir(folder);
+        }
+        log::info!("Removed {} stale files", stale.len());
+        Ok(())
+    }
+
+    /// The files in the output folder that this run didn't write, other than hidden ones,
+    /// and every folder in it.
+    fn unrecorded(&self) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let outputs = self.outputs.lock();
+        let mut files = vec![];
+        let mut folders = vec![];
+        // Hidden files are left alone, like a `.git` folder for a dump kept in git
+        for entry in jwalk::WalkDir::new(&self.output)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            if entry.file_type().is_dir() {
+                folders.push(path);
+            } else if !outputs.contains(&path) {
+                files.push(path);
+            }
+        }
+        (files, folders)
+    }
+
+    /// Notes the files in the output folder that this run didn't write as extra, other
+    /// than those a dump writes about itself, like its manifest.
+    fn find_extra(&self) {
+        if self.is_stopped() {
+            log::warn!("Not looking for extra files, since the comparison stopped early");
+            return;
+        }
+        let (files, _) = self.unrecorded();
+        let mut comparison = self.comparison.lock();
+        for path in files {
+            let Ok(relative) = path.strip_prefix(&self.output) else {
+                continue;
+            };
+            if !verify::DUMP_FILES
+                .iter()
+                .any(|file| relative == Path::new(file))
+            {
+                comparison.extra.push(relative.to_path_buf());
+            }
+        }
+        comparison.sort();
+    }
+
+    /// Leaves out the files a checkpoint lists as finished by an earlier run, and opens it
+    /// to add the files finished by this one.
+    fn open_checkpoint(&self, path: &Path, files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        // Either way, files would be marked finished before their outputs were on disk
+        ensure!(
+            self.options.io_threads.is_none() && self.options.zip.is_none(),
+            "A checkpoint can't be kept when writing on separate threads or into a zip"
+        );
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read checkpoint {}", path.display()))
+            }
+        };
+        // A line cut off by a crash is left out, so its file is unpacked again
+        let done = match text.rfind('\n') {
+            Some(end) => text[..end]
+                .lines()
+                .map(PathBuf::from)
+                .collect::<HashSet<_>>(),
+            None => HashSet::new(),
+        };
+        let total = files.len();
+        let files = files
+            .into_iter()
+            .filter(|file| {
+                self.relative_path(file)
+                    .map(|relative| !done.contains(relative))
+                    .unwrap_or(true)
+            })
+            .collect::<Vec<_>>();
+        if files.len() < total {
+            log::info!(
+                "Skipping {} files finished in an earlier run, from {}",
+                total - files.len(),
+                path.display()
+            );
+        }
+        if !self.is_dry_run() {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open checkpoint {}", path.display()))?;
+            if !text.is_empty() && !text.ends_with('\n') {
+                // Ends the cut off line, so the next one starts on its own
+                file.write_all(b"\n")?;
+            }
+            *self.checkpoint.lock() = Some(file);
+        }
+        Ok(files)
+    }
+
+    /// Adds a ROM file to the checkpoint once everything for it is written, syncing it so
+    /// it holds up through a crash or power loss.
+    fn checkpoint_file(&self, file: &Path) -> Result<()> {
+        let mut checkpoint = self.checkpoint.lock();
+        let Some(checkpoint) = checkpoint.as_mut() else {
+            return Ok(());
+        };
+        let mut line = self.relative_path(file)?.to_string_lossy().into_owned();
+        line.push('\n');
+        checkpoint.write_all(line.as_bytes())?;
+        checkpoint.sync_data()?;
+        Ok(())
+    }
+
+    /// Reads the manifest of an earlier dump in the output folder, if there is one.
+    fn load_previous(&self) -> Result<()> {
+        let path = self.output.join("manifest.json");
+        if !path.exists() {
+            return Ok(());
+        }
+        let entries: Vec<manifest::Entry> = serde_json::from_slice(&fs::read(&path)?)
+            .with_context(|| format!("Failed to read previous manifest {}", path.display()))?;
+        let mut previous = self.previous.lock();
+        for entry in entries {
+            if entry.source_hash.is_some() {
+                previous
+                    .entry(manifest::source_of(&entry.path).to_path_buf())
+                    .or_insert_with(Vec::new)
+                    .push(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Hashes a ROM file and checks it against the last dump, keeping that dump's entries
+    /// for it if it hasn't changed.
+    fn is_unchanged_source(&self, file: &Path, relative: &Path) -> Result<bool> {
+        let mut hasher = blake3::Hasher::new();
+        std::io::copy(&mut fs::File::open(file)?, &mut hasher)?;
+        let hash = hasher.finalize().to_hex().to_string();
+        let previous = self.previous.lock().remove(relative);
+        if let Some(entries) = previous {
+            if entries
+                .iter()
+                .all(|e| e.source_hash.as_ref() == Some(&hash))
+            {
+                self.manifest.lock().extend(entries);
+                return Ok(true);
+            }
+        }
+        self.source_hashes
+            .lock()
+            .insert(relative.to_path_buf(), hash);
+        Ok(false)
+    }
+
+    fn unpack_file(&self, file: &Path) -> Result<()> {
+        let name = file
+            .file_name()
+            .context("No filename")?
+            .to_str()
+            .context("Bad filename")?;
+        let relative = self.relative_path(file)?;
+        log::debug!("Unpacking {}", relative.display());
+        let filter = &self.options.filter;
+        if is_archive(name) {
+            if filter.is_excluded(relative) {
+                return Ok(());
+            }
+        } else if !filter.matches(relative) {
+            return Ok(());
+        }
+        if !self.is_language_selected(relative) {
+            log::debug!("Skipping {}, not a selected language", relative.display());
+            return Ok(());
+        }
+        let format = match Format::from_name(name) {
+            Some(format) => format,
+            None => self.sniff(file, name).unwrap_or(Format::Raw),
+        };
+        // Loose files that can't be converted aren't copied to the output
+        if format == Format::Raw {
+            if self.options.report_unhandled {
+                if let Ok(magic) = self.peek(file, name) {
+                    self.note_unhandled(relative, &magic);
+                }
+            }
+            return Ok(());
+        }
+        if !self.is_wanted(format) {
+            return Ok(());
+        }
+        if self.options.skip_unchanged && self.is_unchanged_source(file, relative)? {
+            log::debug!(
+                "Skipping {}, unchanged since the last dump",
+                relative.display()
+            );
+            return Ok(());
+        }
+        let modified = fs::metadata(file).and_then(|m| m.modified()).ok();
+        if let (true, Some(modified)) = (self.options.preserve_times, modified) {
+            self.source_times
+                .lock()
+                .insert(relative.to_path_buf(), modified);
+        }
+        if self.options.incremental
+            && format != Format::Sarc
+            && self.is_up_to_date(relative, modified)
+        {
+            log::debug!("Skipping {}, already up to date", relative.display());
+            return Ok(());
+        }
+        let context = ArchiveContext {
+            included: filter.is_included(relative),
+            depth: 0,
+            modified,
+        };
+        let len = fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        if self.is_too_large(relative, len) {
+            if self.options.skip_large {
+                self.record(relative, None, Format::Raw, false);
+                return Ok(());
+            }
+            self.stats.bytes_read.fetch_add(len, Ordering::Relaxed);
+            return self.write_large(relative, &self.read_source(file)?);
+        }
+        FileTimes::scope(len, || {
+            // The original bytes are needed to keep them, so only stream when they aren't
+            if len >= STREAM_THRESHOLD && name.ends_with(".zs") && !self.options.keep_compressed {
+                let (data, compressed) = self.decompress_stream(file, relative, name)?;
+                self.stats.bytes_read.fetch_add(len, Ordering::Relaxed);
+                return self.unpack_decompressed(&data, relative, context, compressed);
+            }
+            let data = self.read_source(file)?;
+            self.stats
+                .bytes_read
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+            self.unpack_data(&data, relative, context)
+        })
+    }
+
+    /// Reads a whole ROM file, or maps it into memory with `mmap`.
+    fn read_source(&self, file: &Path) -> Result<SourceData> {
+        if self.options.mmap {
+            let handle = fs::File::open(file)?;
+            // SAFETY: the ROM isn't written to while it is dumped, which mapping it relies on
+            let map = unsafe { memmap2::Mmap::map(&handle) }
+                .with_context(|| format!("Failed to map {}", file.display()))?;
+            Ok(SourceData::Mapped(map))
+        } else {
+            Ok(SourceData::Read(fs::read(file)?))
+        }
+    }
+
+    /// Whether files of a format are unpacked at all, which with `only_text` is only message
+    /// files and the archives they may be in.
+    fn is_wanted(&self, format: Format) -> bool {
+        !self.options.only_text || matches!(format, Format::Sarc | Format::Msbt)
+    }
+
+    /// Whether a path is for one of the languages asked for, or isn't localized.
+    fn is_language_selected(&self, relative: &Path) -> bool {
+        self.options
+            .languages
+            .as_ref()
+            .map(|languages| languages.is_selected(relative))
+            .unwrap_or(true)
+    }
+
+    /// Whether a file is over the size limit, so it shouldn't be decompressed or parsed.
+    fn is_too_large(&self, relative: &Path, len: u64) -> bool {
+        match self.options.max_file_size {
+            Some(max) if len > max => {
+                let action = if self.options.skip_large {
+                    "Skipping"
+                } else {
+                    "Writing raw"
+                };
+                log::info!("{action} {} ({})", relative.display(), HumanBytes(len));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Writes a file over the size limit exactly as stored, still compressed if it was.
+    fn write_large(&self, relative: &Path, data: &[u8]) -> Result<()> {
+        let out = self.output_path(relative);
+        self.write_output(&out, data)?;
+        self.record(relative, Some(&out), Format::Raw, false);
+        Ok(())
+    }
+
+    fn unpack_sarc(&self, data: &[u8], relative: &Path, context: ArchiveContext) -> Result<()> {
+        let filter = &self.options.filter;
+        let sarc = match Sarc::new(data) {
+            Ok(sarc) => sarc,
+            Err(e) if self.options.continue_on_sarc_error => {
+                self.fail(
+                    relative,
+                    manifest::FailureKind::Parse,
+                    eyre::Report::new(e)
+                        .wrap_err(format!("Failed to open archive {}", relative.display())),
+                )?;
+                return self.write_raw(relative, data, Format::Sarc);
+            }
+            // Members that only start like an archive are kept as they are, rather than
+            // failing the archive they are in
+            Err(e) if context.depth > 1 => {
+                self.warn(
+                    relative,
+                    format!(
+                        "{} isn't a valid archive, writing it raw: {e}",
+                        relative.display()
+                    ),
+                );
+                return self.write_raw(relative, data, Format::Sarc);
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to open archive {}", relative.display()))
+            }
+        };
+        let files = sarc.files().enumerate().collect::<Vec<_>>();
+        let unnamed = files
+            .iter()
+            .filter(|(_, file)| file.name().is_none())
+            .count();
+        // Big packs hold thousands of members, so they are spread across the pool too. Each
+        // decompression gets its own decoder, sharing only the prepared dictionaries. To bound
+        // memory, members go a batch at a time, since blocking pool threads on a semaphore
+        // could deadlock with nested archives.
+        let workers = self
+            .options
+            .workers_per_sarc
+            .unwrap_or_else(|| rayon::current_num_threads() * 2)
+            .max(1);
+        files.chunks(workers).try_for_each(|batch| {
+            batch
+                .par_iter()
+                .try_for_each(|(index, file)| -> Result<()> {
+                    if self.is_stopped() {
+                        return Ok(());
+                    }
+                    let member = match file.name() {
+                        Some(name) => relative.join(name),
+                        None => {
+                            let ext = self.detect_format(file.data).extension();
+                            relative.join(format!("__unnamed_{index:04}.{ext}"))
+                        }
+                    };
+                    if !(context.included || filter.is_included(&member))
+                        || filter.is_excluded(&member)
+                        || !self.is_language_selected(&member)
+                    {
+                        return Ok(());
+                    }
+                    if self.options.incremental && self.is_up_to_date(&member, context.modified) {
+                        return Ok(());
+                    }
+                    if self.options.manifest_offsets {
+                        // Members borrow from the archive data, so their offset is how far
+                        // into it they start
+                        let offset = (file.data.as_ptr() as usize)
+                            .checked_sub(data.as_ptr() as usize)
+                            .filter(|offset| offset + file.data.len() <= data.len());
+                        if let Some(offset) = offset {
+                            self.member_ranges
+                                .lock()
+                                .insert(member.clone(), (offset as u64, file.data.len() as u64));
+                        }
+                    }
+                    if self.is_too_large(&member, file.data.len() as u64) {
+                        if self.options.skip_large {
+                            self.record(&member, None, Format::Raw, false);
+                            return Ok(());
+                        }
+                        return self.write_large(&member, file.data);
+                    }
+                    self.guard(&member, || {
+                        FileTimes::scope(file.data.len() as u64, || {
+                            self.unpack_data(file.data, &member, context)
+                        })
+                    })
+                })
+        })?;
+        if unnamed > 0 {
+            log::info!(
+                "{} contains {unnamed} members without names",
+                relative.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes a whole archive as one document, mapping each member's name to its converted
+    /// value.
+    fn bundle_sarc(&self, data: &[u8], relative: &Path, context: ArchiveContext) -> Result<()> {
+        let value = match self.bundle_members(data, relative, context.included, context.depth) {
+            Ok(value) => value,
+            Err(e) => {
+                self.fail(
+                    relative,
+                    manifest::FailureKind::Parse,
+                    e.wrap_err(format!("Failed to open archive {}", relative.display())),
+                )?;
+                return self.write_raw(relative, data, Format::Sarc);
+            }
+        };
+        if self.options.validate {
+            self.record(relative, None, Format::Sarc, true);
+            return Ok(());
+        }
+        let (out, text) =
+            self.to_text_at(relative, &self.text_path(relative, Format::Sarc), &value)?;
+        self.write_output(&out, text)?;
+        self.record(relative, Some(&out), Format::Sarc, true);
+        Ok(())
+    }
+
+    /// Converts the members of an archive wanted by the filter, sorted by name.
+    fn bundle_members(
+        &self,
+        data: &[u8],
+        relative: &Path,
+        included: bool,
+        depth: usize,
+    ) -> Result<serde_yaml::Value> {
+        let filter = &self.options.filter;
+        let sarc = Sarc::new(data)?;
+        let mut members = sarc
+            .files()
+            .enumerate()
+            .map(|(index, file)| {
+                let name = match file.name() {
+                    Some(name) => name.to_owned(),
+                    None => {
+                        let ext = self.detect_format(file.data).extension();
+                        format!("__unnamed_{index:04}.{ext}")
+                    }
+                };
+                (name, file.data)
+            })
+            .filter(|(name, _)| {
+                let member = relative.join(name);
+                (included || filter.is_included(&member))
+                    && !filter.is_excluded(&member)
+                    && self.is_language_selected(&member)
+            })
+            .collect::<Vec<_>>();
+        members.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let included = included || filter.is_included(relative);
+        let values = members
+            .into_par_iter()
+            .map(|(name, data)| {
+                let value = self.bundle_value(&relative.join(&name), data, included, depth);
+                (serde_yaml::Value::from(name), value)
+            })
+            .collect::<Vec<_>>();
+        Ok(serde_yaml::Value::Mapping(values.into_iter().collect()))
+    }
+
+    /// Converts one member of a bundled archive, falling back to its bytes if it can't be.
+    fn bundle_value(
+        &self,
+        relative: &Path,
+        data: &[u8],
+        included: bool,
+        depth: usize,
+    ) -> serde_yaml::Value {
+        let name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        let decompressed;
+        let data = if is_zstd(data) {
+            match self.decompress(&name, data) {
+                Ok(data) => {
+                    decompressed = data;
+                    &decompressed[..]
+                }
+                Err(_) => return binary_value(data),
+            }
+        } else {
+            data
+        };
+        let value = match self.detect_format(data) {
+            Format::Sarc if depth < MAX_SARC_DEPTH => {
+                self.bundle_members(data, relative, included, depth + 1)
+            }
+            Format::Byml => parse_byml(data).and_then(|byml| {
+                let byml = match self.options.canonicalize_floats {
+                    Some(style) => floats::canonicalize(byml, style),
+                    None => byml,
+                };
+                if self.options.sort_keys {
+                    to_value(&sort::SortedByml(&byml))
+                } else {
+                    to_value(&byml)
+                }
+            }),
+            Format::Aamp => roead::aamp::ParameterIO::from_binary(data)
+                .map_err(eyre::Report::new)
+                .and_then(|pio| to_value(&pio)),
+            Format::Msbt => parse_msbt(data).and_then(|msbt| to_value(&msbt)),
+            Format::Rstb => rstb::ResourceSizeTable::from_binary(data).and_then(|t| to_value(&t)),
+            _ => return binary_value(data),
+        };
+        value.unwrap_or_else(|e| {
+            log::debug!("Bundling {} as binary: {e:?}", relative.display());
+            binary_value(data)
+        })
+    }
+
+    /// Converts a loose file or archive member according to its decompressed magic.
+    fn unpack_data(&self, data: &[u8], relative: &Path, context: ArchiveContext) -> Result<()> {
+        let name = relative
+            .file_name()
+            .context("No filename")?
+            .to_str()
+            .context("Bad filename")?;
+        let original = data;
+        let compressed = is_zstd(data);
+        let decompressed;
+        let mut pooled;
+        let data = if compressed && is_archive(name) {
+            // Archives are the biggest files decompressed whole, and there are many of them
+            pooled = self.buffers.take();
+            self.stats.timings.time(Stage::Decompress, || {
+                self.dicts.decompress_into(name, data, &mut pooled)
+            })?;
+            &pooled[..]
+        } else if compressed {
+            decompressed = self.decompress(name, data)?;
+            &decompressed[..]
+        } else {
+            data
+        };
+        let format = self.detect_format(data);
+        if !self.is_wanted(format) {
+            return Ok(());
+        }
+        // Archives are unpacked into a folder of the same name and raw files are already
+        // written as-is, so only converted files need their original kept. Summaries are
+        // written next to their file, which is already the original if it wasn't compressed.
+        if self.options.keep_compressed
+            && !matches!(format, Format::Sarc | Format::Raw)
+            && (compressed || !format.is_summarized())
+        {
+            self.write_extra(&self.output_path(relative), original)?;
+        }
+        self.unpack_decompressed(data, relative, context, compressed)
+    }
+
+    fn unpack_decompressed(
+        &self,
+        data: &[u8],
+        relative: &Path,
+        context: ArchiveContext,
+        compressed: bool,
+    ) -> Result<()> {
+        let format = self.detect_format(data);
+        if !self.is_wanted(format) {
+            return Ok(());
+        }
+        // Summaries already have the file raw next to them, and archives are unpacked
+        if self.options.extract_raw_also
+            && matches!(
+                format,
+                Format::Byml | Format::Aamp | Format::Msbt | Format::Rstb
+            )
+        {
+            let out = self.raw_path(relative, format);
+            let out = self
+                .output
+                .join(RAW_FOLDER)
+                .join(out.strip_prefix(&self.output).unwrap_or(&out));
+            self.write_extra(&out, data)?;
+        }
+        if let Some(only) = &self.options.parse_only {
+            if format != Format::Raw && !only.contains(format) {
+                return self.write_raw(relative, data, format);
+            }
+        }
+        match format {
+            Format::Sarc if context.depth < MAX_SARC_DEPTH => {
+                let context = ArchiveContext {
+                    included: context.included || self.options.filter.is_included(relative),
+                    depth: context.depth + 1,
+                    ..context
+                };
+                if self.options.bundle_sarc {
+                    self.bundle_sarc(data, relative, context)
+                } else {
+                    self.unpack_sarc(data, relative, context)
+                }
+            }
+            Format::Sarc => {
+                self.fail(
+                    relative,
+                    manifest::FailureKind::Nesting,
+                    eyre::eyre!(
+                        "Archive {} is nested too deeply, writing it raw",
+                        relative.display()
+                    ),
+                )?;
+                self.write_raw(relative, data, Format::Sarc)
+            }
+            Format::Byml => self.convert_byml(data.to_vec(), relative, compressed),
+            Format::Rstb => self.convert_rstb(data, relative),
+            Format::Aamp => self.convert_aamp(data, relative),
+            Format::Msbt => self.convert_msbt(data, relative),
+            format @ (Format::Bars
+            | Format::Bwav
+            | Format::Ainb
+            | Format::Bfevfl
+            | Format::Bntx
+            | Format::Tscb
+            | Format::Phive
+            | Format::Bffnt) => self.convert_summary(data, relative, format),
+            Format::Raw => self.write_raw(relative, data, Format::Raw),
+        }
+    }
+
+    fn write_manifest(&self) -> Result<()> {
+        let mut manifest = self.manifest.lock();
+        if self.options.validate || self.options.compare {
+            return Ok(());
+        }
+        if self.options.dry_run {
+            log::info!("Would write {} files", manifest.len());
+            return Ok(());
+        }
+        manifest.sort_by(|a, b| a.path.cmp(&b.path));
+        self.write_file(
+            &self.output.join("manifest.json"),
+            serde_json::to_string_pretty(&*manifest)?,
+        )?;
+        if self.options.dedupe {
+            self.write_file(
+                &self.output.join("duplicates.json"),
+                serde_json::to_string_pretty(&*self.dedupe.duplicates.lock())?,
+            )?;
+        }
+        let mut errors = self.errors.lock();
+        if !errors.is_empty() {
+            errors.sort_by(|a, b| a.path.cmp(&b.path));
+            self.write_file(
+                &self.output.join("_errors.json"),
+                serde_json::to_string_pretty(&*errors)?,
+            )?;
+        }
+        if self.options.hashes {
+            let mut sums = String::new();
+            for (path, hash) in self.hashes.lock().iter() {
+                sums.push_str(&format!("{hash}  {}\n", path.display()));
+            }
+            self.write_file(&self.output.join("SHA256SUMS"), sums)?;
+        }
+        if self.options.gitattributes {
+            self.write_file(
+                &self.output.join(".gitattributes"),
+                gitattributes(&self.extensions.lock()),
+            )?;
+        }
+        if let Some(previous) = &self.options.since {
+            let removed = self.since.removed(previous);
+            log::info!(
+                "{} files unchanged and {} removed since {}",
+                self.since.unchanged(),
+                removed.len(),
+                previous.display()
+            );
+            let mut text = String::new();
+            for path in removed {
+                text.push_str(&display_path(&path));
+                text.push('\n');
+            }
+            self.write_file(&self.output.join("removed.txt"), text)?;
+        }
+        if let Some(zip) = self.zip.lock().take() {
+            zip.finish()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use crate::format::Format;
+use eyre::{bail, Context, Result};
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// User `magic: format` associations, checked before the built-in magics
+#[derive(Debug, Clone, Default)]
+pub struct MagicTable(Vec<(Vec<u8>, Format)>);
+
+impl MagicTable {
+    /// Loads a YAML map of magics to the format to handle them as, one of `byml`, `aamp`,
+    /// `msbt`, `sarc`, or `raw`. Magics are taken as text, like `XYZ`, or as hex bytes with
+    /// a `0x` prefix, like `0x58595A00`. Longer magics come first so the most specific one
+    /// matches.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read magic table at {}", path.display()))?;
+        let map: BTreeMap<String, String> = serde_yaml::from_str(&text)
+            .with_context(|| format!("Failed to parse magic table at {}", path.display()))?;
+        let mut magics = map
+            .into_iter()
+            .map(|(magic, format)| {
+                let format = match format.parse()? {
+                    format @ (Format::Byml
+                    | Format::Aamp
+                    | Format::Msbt
+                    | Format::Sarc
+                    | Format::Raw) => format,
+                    _ => bail!("Magic {magic} can't be handled as {format}, expected byml, aamp, msbt, sarc, or raw"),
+                };
+                Ok((parse_magic(&magic)?, format))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        magics.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+        Ok(Self(magics))
+    }
+
+    /// The format a user magic at the start of the data maps to, if any.
+    pub fn get(&self, data: &[u8]) -> Option<Format> {
+        self.0
+            .iter()
+            .find(|(magic, _)| data.starts_with(magic))
+            .map(|(_, format)| *format)
+    }
+}
+
+fn parse_magic(magic: &str) -> Result<Vec<u8>> {
+    let bytes = match magic.strip_prefix("0x") {
+        Some(hex) if hex.len() % 2 == 0 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Bad hex magic {magic}"))?,
+        Some(_) => bail!("Hex magic {magic} has an odd number of digits"),
+        None => magic.as_bytes().to_vec(),
+    };
+    if bytes.is_empty() {
+        bail!("Empty magic in magic table");
+    }
+    Ok(bytes)
+}
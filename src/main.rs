@@ -1,233 +1,982 @@
-#![allow(irrefutable_let_patterns)]
-#![feature(let_chains)]
 use argh::FromArgs;
-use eyre::{bail, ContextCompat, Result};
-use indicatif::ParallelProgressIterator;
-use parking_lot::Mutex;
-use rayon::prelude::*;
-use roead::{byml::Byml, sarc::Sarc};
+use eyre::{bail, Context, ContextCompat, Result};
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use log::LevelFilter;
 use std::{
+    collections::HashMap,
     fs,
-    path::{Path, PathBuf},
-    println,
+    io::{IsTerminal, Read, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
-use zstd::bulk::Decompressor;
+use totk_dump::{
+    actor, display_path,
+    filter::{read_globs, Filter},
+    find_romfs, find_zsdic,
+    format::{is_zstd, Format, Formats},
+    language::LanguageMap,
+    magic_table::MagicTable,
+    manifest,
+    version::GameVersion,
+    BinaryNodes, ExtensionStyle, FloatStyle, Options, OutputCompression, OutputFormat, Repacker,
+    SarcLayout, Unpacker,
+};
+
+/// Exit code when an unpack is stopped with Ctrl-C, as shells use for SIGINT
+const INTERRUPTED: i32 = 130;
 
-const COMPRESSION_LEVEL: usize = 15;
+/// Exit code when an unpack is stopped for writing more than `--max-output-size`
+const OUTPUT_LIMIT_REACHED: i32 = 3;
 
 #[derive(FromArgs, PartialEq, Debug)]
 /// Tool to unpack TOTK ROM to a human-readable, pseudosource format
+struct Args {
+    /// log every file as it is processed
+    #[argh(switch, short = 'v')]
+    verbose: bool,
+    /// only log errors
+    #[argh(switch, short = 'q')]
+    quiet: bool,
+    /// log progress every few seconds instead of drawing progress bars, which is the
+    /// default when not run in a terminal
+    #[argh(switch)]
+    no_progress: bool,
+    #[argh(subcommand)]
+    command: Command,
+}
+
+/// Logs through env_logger while hiding the progress bars, so they aren't clobbered
+struct Logger {
+    inner: env_logger::Logger,
+    progress: MultiProgress,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.inner.matches(record) {
+            self.progress.suspend(|| self.inner.log(record));
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+fn init_logger(args: &Args) -> Result<MultiProgress> {
+    let test_one = matches!(&args.command, Command::Unpack(unpack) if unpack.test_one.is_some());
+    let progress_json = matches!(&args.command, Command::Unpack(unpack) if unpack.progress_json);
+    let level = if test_one {
+        LevelFilter::Trace
+    } else if args.quiet {
+        LevelFilter::Error
+    } else if args.verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    };
+    // Diagnostics go to stderr, keeping stdout for listings and converted files
+    let inner = env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .target(env_logger::Target::Stderr)
+        .build();
+    // Bars would fill logs and CI output with escape codes
+    let interactive = std::io::stdout().is_terminal() && std::io::stderr().is_terminal();
+    let progress = if args.no_progress || !interactive || test_one || progress_json {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+        MultiProgress::new()
+    };
+    log::set_max_level(inner.filter());
+    log::set_boxed_logger(Box::new(Logger {
+        inner,
+        progress: progress.clone(),
+    }))?;
+    Ok(progress)
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum Command {
+    Unpack(UnpackArgs),
+    Repack(RepackArgs),
+    Convert(ConvertArgs),
+    Verify(VerifyArgs),
+    DiffManifest(DiffManifestArgs),
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Unpack a TOTK ROM to YAML and loose files
+#[argh(subcommand, name = "unpack")]
 struct UnpackArgs {
-    /// the source folder for the TOTK ROM
+    /// the source folder for the TOTK ROM, or a single archive like `Foo.pack.zs` to dump
+    /// just its members
     #[argh(positional)]
     source: PathBuf,
     /// the destination for the unpacked data (defaults to `./unpacked`)
     #[argh(positional)]
     output: Option<PathBuf>,
+    /// an update romfs to overlay on the source, overriding its files (repeatable, in
+    /// order)
+    #[argh(option)]
+    layer: Vec<PathBuf>,
+    /// number of worker threads to use (defaults to all cores)
+    #[argh(option)]
+    threads: Option<usize>,
+    /// write outputs on this many threads of their own, separate from the worker threads,
+    /// for slow storage (defaults to writing on the worker threads)
+    #[argh(option)]
+    threads_io: Option<usize>,
+    /// abort on the first file that fails to convert
+    #[argh(switch)]
+    strict: bool,
+    /// write archives that can't be opened raw with a warning, instead of aborting
+    #[argh(switch)]
+    continue_on_sarc_error: bool,
+    /// only unpack files matching this glob (repeatable)
+    #[argh(option)]
+    include: Vec<String>,
+    /// only unpack files matching the globs in this file, one per line with `#` for
+    /// comments, like those written by `diff-manifest --globs`
+    #[argh(option)]
+    include_from: Option<PathBuf>,
+    /// only unpack one actor's pack, actor info, and messages, into a folder named after it
+    /// in the output, e.g. `Enemy_Bokoblin`
+    #[argh(option)]
+    actor: Option<String>,
+    /// skip files matching this glob (repeatable)
+    #[argh(option)]
+    exclude: Vec<String>,
+    /// skip files matching the globs in this file, one per line with `#` for comments
+    #[argh(option)]
+    exclude_from: Option<PathBuf>,
+    /// skip files matching patterns in this gitignore-style file (defaults to
+    /// `.totkdumpignore` in the current folder, if there is one)
+    #[argh(option)]
+    ignore_file: Option<PathBuf>,
+    /// the ZsDic pack to load dictionaries from (defaults to `Pack/ZsDic.pack.zs` in the source)
+    #[argh(option)]
+    zsdic: Option<PathBuf>,
+    /// a folder of loose `zs.zsdic`, `pack.zsdic`, and `bcett.byml.zsdic` files to load
+    /// dictionaries from instead of a ZsDic pack
+    #[argh(option)]
+    zsdic_dir: Option<PathBuf>,
+    /// a YAML map of extra file suffixes to the dictionaries they are compressed with
+    #[argh(option)]
+    dict_map: Option<PathBuf>,
+    /// a YAML map of extra magics to the format to handle them as, one of `byml`, `aamp`,
+    /// `msbt`, `sarc`, or `raw`
+    #[argh(option)]
+    magic_table: Option<PathBuf>,
+    /// walk into symlinked folders in the source, skipping any that loop back (by default
+    /// they are skipped)
+    #[argh(switch)]
+    follow_symlinks: bool,
+    /// log what would be written without writing anything
+    #[argh(switch)]
+    dry_run: bool,
+    /// parse every file without converting or writing anything, failing if any can't be
+    #[argh(switch)]
+    validate: bool,
+    /// serialize each BYML and AAMP file back to binary after parsing it, warning about any
+    /// that don't match the original bytes
+    #[argh(switch)]
+    verify_roundtrip: bool,
+    /// print how long was spent decompressing, parsing, and serializing, summed across
+    /// threads. Combine with `--include` to time a sample and `--dry-run` to leave out
+    /// writing.
+    #[argh(switch)]
+    bench: bool,
+    /// skip files whose output already exists and is newer than the source
+    #[argh(switch)]
+    incremental: bool,
+    /// leave outputs that already exist with the same content untouched, so only real
+    /// changes show up in e.g. `git status`
+    #[argh(switch)]
+    quiet_skip_unchanged: bool,
+    /// format to convert files to, `yaml`, `json`, or `toml` (defaults to `yaml`)
+    #[argh(option, default = "OutputFormat::Yaml")]
+    output_format: OutputFormat,
+    /// how to name converted files: `append` the format's extension to the full name, e.g.
+    /// `Foo.bgyml.yml`, or `replace` the last extension with it (defaults to `append`)
+    #[argh(option, default = "ExtensionStyle::Append")]
+    extension_style: ExtensionStyle,
+    /// sort BYML map keys for stable output
+    #[argh(switch)]
+    sort_keys: bool,
+    /// write BYML floats in one canonical form: `shortest` to keep their values, or a
+    /// number of decimal places to round them to, which can't be repacked
+    #[argh(option)]
+    canonicalize_floats: Option<FloatStyle>,
+    /// spaces per indentation level in converted files (defaults to 2)
+    #[argh(option)]
+    indent: Option<usize>,
+    /// write lists of scalars on one line in YAML, or JSON without whitespace
+    #[argh(switch)]
+    compact: bool,
+    /// write BYML files whose text is bigger than this many bytes as numbered parts, which
+    /// can't be repacked
+    #[argh(option)]
+    split_large_yaml: Option<u64>,
+    /// dump GameDataList flags as readable tables by hash, which can't be repacked
+    #[argh(switch)]
+    expand_gamedata: bool,
+    /// annotate map actors with their actor info and links with the actors they point to,
+    /// which can't be repacked
+    #[argh(switch)]
+    expand_map: bool,
+    /// show actor names next to the hashes that refer to them in the expanded views
+    #[argh(switch)]
+    resolve_names: bool,
+    /// write BYML binary nodes that hold an AAMP or BYML file as that file instead of
+    /// base64, which can't be repacked
+    #[argh(switch)]
+    expand_nested: bool,
+    /// how to write BYML binary nodes: `base64`, `hex`, or `external` in `.bin` files next
+    /// to the text (defaults to `base64`). Only base64 can be repacked
+    #[argh(option, default = "BinaryNodes::Base64")]
+    binary_nodes: BinaryNodes,
+    /// write identical outputs once, linking the duplicates to it
+    #[argh(switch)]
+    dedupe: bool,
+    /// also copy the original binary of each converted file to the output
+    #[argh(switch)]
+    keep_compressed: bool,
+    /// also write the decompressed binary of each converted file, as it was parsed, under
+    /// `_raw` in the output
+    #[argh(switch)]
+    extract_raw_also: bool,
+    /// a previous dump to compare against, only writing files that changed
+    #[argh(option)]
+    since: Option<PathBuf>,
+    /// write a SHA256SUMS file listing the hash of every output
+    #[argh(switch)]
+    hashes: bool,
+    /// write a .gitattributes marking the text files written as text and the raw files as
+    /// binary, for committing the dump to git
+    #[argh(switch)]
+    gitattributes: bool,
+    /// record in the manifest where each archive member's data is in its decompressed
+    /// archive, by offset and size
+    #[argh(switch)]
+    json_manifest_with_offsets: bool,
+    /// where to write archive members: `nested` in a folder per archive, `flat` in one
+    /// folder per archive, `mirror` next to the archive, or `single-root` by their names in
+    /// the archive alone (defaults to `nested`)
+    #[argh(option, default = "SarcLayout::Nested")]
+    sarc_layout: SarcLayout,
+    /// write each archive as one `<pack>.yml` mapping its members' names to their converted
+    /// values, instead of a folder of files
+    #[argh(switch)]
+    bundle_sarc: bool,
+    /// print every file and archive member with its size and type, without unpacking
+    #[argh(switch)]
+    list: bool,
+    /// write files and archive members bigger than this many bytes without converting them
+    #[argh(option)]
+    max_file_size: Option<u64>,
+    /// leave out files bigger than `--max-file-size` instead of writing them raw
+    #[argh(switch)]
+    skip_large: bool,
+    /// stop after writing this many bytes in total, finishing the files in progress and
+    /// writing the manifest
+    #[argh(option)]
+    max_output_size: Option<u64>,
+    /// write everything into this zip archive instead of the output folder
+    #[argh(option)]
+    zip: Option<PathBuf>,
+    /// compress every output with `gzip` or `zstd`, adding `.gz` or `.zst` to its name, to
+    /// save space on archived dumps (defaults to `none`)
+    #[argh(option, default = "OutputCompression::None")]
+    compress_output: OutputCompression,
+    /// also write the raw image data of each texture's mip levels
+    #[argh(switch)]
+    extract_textures: bool,
+    /// finish by printing `FAIL <path> <kind> <reason>` to stderr for each failure, sorted
+    /// by path
+    #[argh(switch)]
+    flat_errors: bool,
+    /// skip ROM files unchanged since the last dump, going by the hashes in its manifest
+    #[argh(switch)]
+    skip_unchanged: bool,
+    /// keep a list of the ROM files finished in this file, and skip those listed in it,
+    /// to carry on after a crash
+    #[argh(option)]
+    checkpoint: Option<PathBuf>,
+    /// write the path of every output file to this file, one a line and sorted, to diff
+    /// against another dump's
+    #[argh(option)]
+    paths_file: Option<PathBuf>,
+    /// write the sizes of each file and the time spent on each step of converting it to
+    /// this CSV file
+    #[argh(option)]
+    trace: Option<PathBuf>,
+    /// write progress to stderr as lines of JSON, `{"done", "total", "current"}` every so
+    /// often and `{"summary"}` at the end, in place of the progress bar
+    #[argh(switch)]
+    progress_json: bool,
+    /// remove files in the output folder that this run didn't write, like outputs for
+    /// files no longer in the ROM
+    #[argh(switch)]
+    clean: bool,
+    /// map ROM files into memory instead of reading them, which can be faster for many
+    /// small files. The ROM must not change during the dump.
+    #[argh(switch)]
+    mmap: bool,
+    /// how many members of one archive to unpack at once (defaults to twice the threads)
+    #[argh(option)]
+    workers_per_sarc: Option<usize>,
+    /// give each output the modification time of the ROM file it came from
+    #[argh(switch)]
+    preserve_times: bool,
+    /// write the original version, byte order, and compression of each BYML file to a
+    /// `.meta.json` file next to it
+    #[argh(switch)]
+    sidecar_meta: bool,
+    /// write counts by type, failures, bytes, and time taken to this JSON file at the end
+    #[argh(option)]
+    summary_json: Option<PathBuf>,
+    /// leading folders to drop from each path relative to the romfs when writing it
+    #[argh(option, default = "0")]
+    prefix_strip: usize,
+    /// folders to put every output under, after `--prefix-strip`, e.g. `100/romfs`
+    #[argh(option)]
+    prefix_add: Option<PathBuf>,
+    /// take paths relative to this folder instead of the romfs, like the common parent of
+    /// the source and its layers
+    #[argh(option)]
+    relative_to: Option<PathBuf>,
+    /// join folders deeper than this many levels into the file name with `__`, to stay
+    /// within path length limits on Windows, which can't be repacked
+    #[argh(option)]
+    flatten_depth: Option<usize>,
+    /// finish by printing how many files of each extension and magic were written raw
+    /// because nothing converts them
+    #[argh(switch)]
+    report_unhandled: bool,
+    /// write files of a format under a folder of their own, as `<format>=<folder>`, e.g.
+    /// `msbt=text` (repeatable)
+    #[argh(option)]
+    route: Vec<String>,
+    /// only unpack MSBT message files, from loose files and archives alike, into a `text`
+    /// folder unless routed elsewhere
+    #[argh(switch)]
+    only_text: bool,
+    /// only convert files of these comma separated formats, e.g. `sarc,byml,msbt`, and
+    /// write everything else raw after decompressing it
+    #[argh(option)]
+    parse_only: Option<Formats>,
+    /// only unpack localized files, like message packs, for these comma separated
+    /// languages, e.g. `en,ja`, or the game's own codes like `EUen`. Unknown ones are taken
+    /// as game codes, with a warning
+    #[argh(option)]
+    languages: Option<String>,
+    /// map a game language code to a usual one for `--languages`, as `<code>=<locale>`,
+    /// e.g. `EUpt=pt`, adding to or replacing the built-in table (repeatable)
+    #[argh(option)]
+    language_map: Vec<String>,
+    /// the game version of the ROM, like `1.2.1`, to check against the versions this
+    /// release was written for, if it can't be told from the ROM's file names
+    #[argh(option)]
+    game_version: Option<GameVersion>,
+    /// only unpack the archive with this filename or path relative to the romfs
+    #[argh(option)]
+    only_sarc: Option<String>,
+    /// debug a single file, loose or inside an archive like
+    /// `Pack/Foo.pack.zs/Actor/Foo.bgyml`, on one thread, logging every step and keeping its
+    /// raw and decompressed bytes in `_test-one` in the output folder
+    #[argh(option)]
+    test_one: Option<PathBuf>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Rebuild ROM files from an unpacked folder
+#[argh(subcommand, name = "repack")]
+struct RepackArgs {
+    /// the folder previously written by `unpack`
+    #[argh(positional)]
+    source: PathBuf,
+    /// the destination for the rebuilt ROM files (defaults to `./repacked`)
+    #[argh(positional)]
+    output: Option<PathBuf>,
+    /// number of worker threads to use (defaults to all cores)
+    #[argh(option)]
+    threads: Option<usize>,
+    /// a YAML map of extra file suffixes to the dictionaries they are compressed with
+    #[argh(option)]
+    dict_map: Option<PathBuf>,
+    /// zstd level to compress at, from 1 to 22 (defaults to 15, as the game uses). Lower
+    /// is faster but bigger
+    #[argh(option)]
+    compression_level: Option<i32>,
 }
 
-struct Unpacker {
+#[derive(FromArgs, PartialEq, Debug)]
+/// Convert a single ROM file to YAML
+#[argh(subcommand, name = "convert")]
+struct ConvertArgs {
+    /// the file to convert, or `-` to read it from stdin, which needs `--zsdic` or
+    /// `--zsdic-dir` if it is compressed
+    #[argh(positional)]
+    input: PathBuf,
+    /// where to write the converted file, or `-` for stdout (defaults to stdout)
+    #[argh(positional)]
+    output: Option<PathBuf>,
+    /// the ZsDic pack to load dictionaries from, needed for compressed files
+    #[argh(option)]
+    zsdic: Option<PathBuf>,
+    /// a folder of loose dictionary files to load instead of a ZsDic pack
+    #[argh(option)]
+    zsdic_dir: Option<PathBuf>,
+    /// a YAML map of extra file suffixes to the dictionaries they are compressed with
+    #[argh(option)]
+    dict_map: Option<PathBuf>,
+    /// a YAML map of extra magics to the format to handle them as, one of `byml`, `aamp`,
+    /// `msbt`, `sarc`, or `raw`
+    #[argh(option)]
+    magic_table: Option<PathBuf>,
+    /// format to convert the file to, `yaml`, `json`, or `toml` (defaults to `yaml`)
+    #[argh(option, default = "OutputFormat::Yaml")]
+    output_format: OutputFormat,
+    /// spaces per indentation level in the converted file (defaults to 2)
+    #[argh(option)]
+    indent: Option<usize>,
+    /// write lists of scalars on one line in YAML, or JSON without whitespace
+    #[argh(switch)]
+    compact: bool,
+    /// dump a GameDataList's flags as a readable table by hash
+    #[argh(switch)]
+    expand_gamedata: bool,
+    /// the name of the file read from stdin, which picks its format and dictionary
+    /// (defaults to `stdin.pack.zs`)
+    #[argh(option)]
+    name: Option<String>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Check that a folder written by `unpack` matches a fresh dump of a ROM, without changing
+/// it. Pass the same conversion options the dump was made with.
+#[argh(subcommand, name = "verify")]
+struct VerifyArgs {
+    /// the source folder for the TOTK ROM
+    #[argh(positional)]
     source: PathBuf,
-    output: PathBuf,
-    default_decomp: Mutex<Decompressor<'static>>,
-    common_decomp: Mutex<Decompressor<'static>>,
-    pack_decomp: Mutex<Decompressor<'static>>,
-    map_decomp: Mutex<Decompressor<'static>>,
-}
-
-impl Unpacker {
-    fn new(source: PathBuf, output: PathBuf) -> Self {
-        Self {
-            source,
-            output,
-            common_decomp: Default::default(),
-            default_decomp: Default::default(),
-            map_decomp: Default::default(),
-            pack_decomp: Default::default(),
+    /// the folder previously written by `unpack`
+    #[argh(positional)]
+    dump: PathBuf,
+    /// an update romfs the dump was overlaid with (repeatable, in order)
+    #[argh(option)]
+    layer: Vec<PathBuf>,
+    /// number of worker threads to use (defaults to all cores)
+    #[argh(option)]
+    threads: Option<usize>,
+    /// the ZsDic pack to load dictionaries from (defaults to `Pack/ZsDic.pack.zs` in the source)
+    #[argh(option)]
+    zsdic: Option<PathBuf>,
+    /// a folder of loose dictionary files to load instead of a ZsDic pack
+    #[argh(option)]
+    zsdic_dir: Option<PathBuf>,
+    /// a YAML map of extra file suffixes to the dictionaries they are compressed with
+    #[argh(option)]
+    dict_map: Option<PathBuf>,
+    /// a YAML map of extra magics to the format to handle them as, one of `byml`, `aamp`,
+    /// `msbt`, `sarc`, or `raw`
+    #[argh(option)]
+    magic_table: Option<PathBuf>,
+    /// format files were converted to, `yaml`, `json`, or `toml` (defaults to `yaml`)
+    #[argh(option, default = "OutputFormat::Yaml")]
+    output_format: OutputFormat,
+    /// how converted files were named, `append` or `replace` (defaults to `append`)
+    #[argh(option, default = "ExtensionStyle::Append")]
+    extension_style: ExtensionStyle,
+    /// BYML map keys were sorted
+    #[argh(switch)]
+    sort_keys: bool,
+    /// how BYML floats were made canonical, `shortest` or a number of decimal places
+    #[argh(option)]
+    canonicalize_floats: Option<FloatStyle>,
+    /// spaces per indentation level in converted files (defaults to 2)
+    #[argh(option)]
+    indent: Option<usize>,
+    /// lists of scalars were written on one line in YAML, or JSON without whitespace
+    #[argh(switch)]
+    compact: bool,
+    /// where archive members were written: `nested`, `flat`, `mirror`, or `single-root`
+    /// (defaults to `nested`)
+    #[argh(option, default = "SarcLayout::Nested")]
+    sarc_layout: SarcLayout,
+    /// what outputs were compressed with, `none`, `gzip`, or `zstd` (defaults to `none`)
+    #[argh(option, default = "OutputCompression::None")]
+    compress_output: OutputCompression,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// List the files added, removed, and changed between two dumps, from their manifests
+#[argh(subcommand, name = "diff-manifest")]
+struct DiffManifestArgs {
+    /// the `manifest.json` of the older dump
+    #[argh(positional)]
+    old: PathBuf,
+    /// the `manifest.json` of the newer dump
+    #[argh(positional)]
+    new: PathBuf,
+    /// also write globs for the added and changed files to this file, for `unpack
+    /// --include-from`
+    #[argh(option)]
+    globs: Option<PathBuf>,
+}
+
+fn with_threads(threads: Option<usize>, op: impl FnOnce() -> Result<()> + Send) -> Result<()> {
+    match threads {
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .build()?
+            .install(op),
+        None => op(),
+    }
+}
+
+fn unpack(args: UnpackArgs, progress: MultiProgress) -> Result<()> {
+    // A single archive is unpacked on its own, from the folder it's in
+    let single = if args.source.is_file() {
+        Some(args.source.canonicalize()?)
+    } else {
+        None
+    };
+    let source = match &single {
+        Some(file) => file
+            .parent()
+            .context("Source archive is not in a folder")?
+            .to_path_buf(),
+        None => find_romfs(&args.source)?,
+    };
+    let zsdic = match (args.zsdic, &single) {
+        (Some(zsdic), _) => Some(zsdic),
+        (None, Some(_)) if args.zsdic_dir.is_some() => None,
+        (None, Some(file)) => Some(find_zsdic(file).with_context(|| {
+            format!(
+                "No ZsDic pack found for {}, pass one with --zsdic",
+                file.display()
+            )
+        })?),
+        (None, None) => None,
+    };
+    let layers = args
+        .layer
+        .iter()
+        .map(|layer| find_romfs(layer))
+        .collect::<Result<Vec<_>>>()?;
+    let magic_table = match &args.magic_table {
+        Some(path) => MagicTable::load(path)?,
+        None => MagicTable::default(),
+    };
+    // Compared against the canonical paths of the source and layers
+    let relative_to = args
+        .relative_to
+        .as_ref()
+        .map(|base| {
+            base.canonicalize()
+                .with_context(|| format!("Failed to find {}", base.display()))
+        })
+        .transpose()?;
+    let mut output = args
+        .output
+        .unwrap_or_else(|| std::env::current_dir().unwrap().join("unpacked"));
+    if let Some(actor) = &args.actor {
+        output.push(actor);
+    }
+    if args.validate {
+        log::info!("Validating ROM…");
+    } else if !args.list {
+        let destination = args.zip.as_deref().unwrap_or(&output);
+        log::info!("Unpacking ROM to {}…", destination.display());
+    }
+    let mut include = args.include.clone();
+    if let Some(path) = &args.include_from {
+        include.extend(read_globs(path)?);
+    }
+    if let Some(actor) = &args.actor {
+        include.extend(actor::globs(actor));
+    }
+    let mut exclude = args.exclude.clone();
+    if let Some(path) = &args.exclude_from {
+        exclude.extend(read_globs(path)?);
+    }
+    let mut filter = Filter::new(&include, &exclude)?;
+    let ignore_file = args
+        .ignore_file
+        .or_else(|| Some(PathBuf::from(".totkdumpignore")).filter(|p| p.exists()));
+    if let Some(ignore_file) = ignore_file {
+        filter = filter.with_ignore_file(&ignore_file)?;
+    }
+    let mut routes = args
+        .route
+        .iter()
+        .map(|route| {
+            let (format, folder) = route
+                .split_once('=')
+                .with_context(|| format!("Route {route} should be <format>=<folder>"))?;
+            Ok((format.parse()?, PathBuf::from(folder)))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+    if args.only_text {
+        routes.entry(Format::Msbt).or_insert_with(|| "text".into());
+    }
+    let language_map = args
+        .language_map
+        .iter()
+        .try_fold(LanguageMap::default(), |map, entries| {
+            map.with_entries(entries)
+        })?;
+    let options = Options {
+        strict: args.strict,
+        continue_on_sarc_error: args.continue_on_sarc_error,
+        filter,
+        zsdic,
+        zsdic_dir: args.zsdic_dir.clone(),
+        dict_map: args.dict_map,
+        magic_table,
+        follow_symlinks: args.follow_symlinks,
+        dry_run: args.dry_run,
+        validate: args.validate,
+        compare: false,
+        verify_roundtrip: args.verify_roundtrip,
+        incremental: args.incremental,
+        skip_identical: args.quiet_skip_unchanged,
+        output_format: args.output_format,
+        extension_style: args.extension_style,
+        sort_keys: args.sort_keys,
+        canonicalize_floats: args.canonicalize_floats,
+        indent: args.indent,
+        compact: args.compact,
+        split_large_yaml: args.split_large_yaml,
+        expand_gamedata: args.expand_gamedata,
+        expand_map: args.expand_map,
+        resolve_names: args.resolve_names,
+        expand_nested: args.expand_nested,
+        binary_nodes: args.binary_nodes,
+        dedupe: args.dedupe,
+        keep_compressed: args.keep_compressed,
+        extract_raw_also: args.extract_raw_also,
+        since: args.since,
+        hashes: args.hashes,
+        gitattributes: args.gitattributes,
+        manifest_offsets: args.json_manifest_with_offsets,
+        sarc_layout: args.sarc_layout,
+        bundle_sarc: args.bundle_sarc,
+        max_file_size: args.max_file_size,
+        skip_large: args.skip_large,
+        max_output_size: args.max_output_size,
+        zip: args.zip,
+        compress_output: args.compress_output,
+        io_threads: args.threads_io,
+        extract_textures: args.extract_textures,
+        skip_unchanged: args.skip_unchanged,
+        checkpoint: args.checkpoint,
+        paths_file: args.paths_file,
+        trace: args.trace,
+        progress_json: args.progress_json,
+        clean: args.clean,
+        mmap: args.mmap,
+        workers_per_sarc: args.workers_per_sarc,
+        preserve_times: args.preserve_times,
+        sidecar_meta: args.sidecar_meta,
+        prefix_strip: args.prefix_strip,
+        prefix_add: args.prefix_add,
+        relative_to,
+        flatten_depth: args.flatten_depth,
+        report_unhandled: args.report_unhandled,
+        only_text: args.only_text,
+        parse_only: args.parse_only,
+        game_version: args.game_version,
+        languages: args
+            .languages
+            .as_deref()
+            .map(|languages| language_map.languages(languages)),
+        actor: args.actor.clone(),
+        routes,
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || {
+        // A second Ctrl-C doesn't wait for files in progress
+        if handler_stop.swap(true, Ordering::Relaxed) {
+            std::process::exit(INTERRUPTED);
+        }
+        eprintln!("Stopping after the files in progress, press Ctrl-C again to quit now");
+    })?;
+    let unpacker = Unpacker::new(source, output)
+        .with_layers(layers)
+        .with_options(options)
+        .with_progress(progress)
+        .with_stop(stop)
+        .init_dicts()?;
+    let start = Instant::now();
+    if let Some(path) = &args.test_one {
+        with_threads(Some(1), || unpacker.test_one(path))?;
+        let failures = unpacker.failures();
+        if failures > 0 {
+            bail!("{} failed to convert", path.display());
         }
+        return Ok(());
+    }
+    if args.list {
+        return with_threads(args.threads, || unpacker.list());
+    }
+    let only_sarc = match &single {
+        Some(file) => file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned()),
+        None => args.only_sarc.clone(),
+    };
+    match &only_sarc {
+        Some(name) => with_threads(args.threads, || unpacker.unpack_archive(name))?,
+        None => with_threads(args.threads, || unpacker.unpack())?,
+    }
+    if args.progress_json {
+        let summary = serde_json::json!({ "summary": unpacker.summary(start.elapsed()) });
+        eprintln!("{summary}");
+    }
+    if let Some(path) = &args.summary_json {
+        fs::write(
+            path,
+            serde_json::to_string_pretty(&unpacker.summary(start.elapsed()))?,
+        )
+        .with_context(|| format!("Failed to write {}", path.display()))?;
     }
+    if args.report_unhandled {
+        println!("{:>8}  {:<24}  magic", "count", "extension");
+        for (extension, magic, count) in unpacker.unhandled() {
+            println!("{count:>8}  {extension:<24}  {magic}");
+        }
+    }
+    if args.flat_errors {
+        for failure in unpacker.errors() {
+            eprintln!(
+                "FAIL {} {} {}",
+                display_path(&failure.path),
+                failure.kind,
+                failure.reason()
+            );
+        }
+    }
+    if args.verify_roundtrip {
+        let mismatches = unpacker
+            .stats()
+            .roundtrip_mismatches
+            .load(Ordering::Relaxed);
+        log::info!("{mismatches} files did not round-trip");
+    }
+    if args.bench {
+        println!("Stage times, {:.2?} in all:", start.elapsed());
+        print!("{}", unpacker.stats().timings);
+    }
+    if unpacker.is_over_output_limit() {
+        std::process::exit(OUTPUT_LIMIT_REACHED);
+    }
+    if unpacker.is_stopped() {
+        std::process::exit(INTERRUPTED);
+    }
+    let failures = unpacker.failures();
+    if failures > 0 && args.validate {
+        bail!("{failures} files failed to parse");
+    } else if failures > 0 {
+        bail!("{failures} files failed to convert");
+    }
+    Ok(())
+}
 
-    fn init_dicts(self) -> Result<Self> {
-        let data = fs::read(self.source.join("Pack/ZsDic.pack.zs"))?;
-        let sarc = Sarc::new(
-            self.default_decomp
-                .lock()
-                .decompress(&data, data.len() * 15)?,
-        )?;
-        let zs = sarc
-            .get_data("zs.zsdic")
-            .context("ZsDic pack missing general dictionary")?;
-        self.common_decomp.lock().set_dictionary(zs)?;
-        let pack = sarc
-            .get_data("pack.zsdic")
-            .context("ZsDic pack missing pack dictionary")?;
-        self.pack_decomp.lock().set_dictionary(pack)?;
-        let map = sarc
-            .get_data("bcett.byml.zsdic")
-            .context("ZsDic pack missing map dictionary")?;
-        self.map_decomp.lock().set_dictionary(map)?;
-        Ok(self)
-    }
-
-    fn decompress(&self, name: &str, data: &[u8]) -> Result<Vec<u8>> {
-        let mut decompressor = if name.ends_with(".bcett.byml.zs") {
-            self.map_decomp.lock()
-        } else if name.ends_with(".pack.zs") {
-            self.pack_decomp.lock()
-        } else if name.ends_with(".rsizetable.zs") {
-            self.default_decomp.lock()
-        } else {
-            self.common_decomp.lock()
-        };
-        let mut last_error = None;
-        for i in 2..(COMPRESSION_LEVEL * 2) {
-            match decompressor.decompress(data, data.len() * i) {
-                Ok(data) => return Ok(data),
-                Err(e) => {
-                    last_error = Some(e);
-                    continue;
-                }
-            }
+fn repack(args: RepackArgs, progress: MultiProgress) -> Result<()> {
+    let output = args
+        .output
+        .unwrap_or_else(|| std::env::current_dir().unwrap().join("repacked"));
+    log::info!("Repacking ROM to {}…", output.display());
+    let mut repacker = Repacker::new(args.source, output)?.with_progress(progress);
+    if let Some(level) = args.compression_level {
+        if !(1..=22).contains(&level) {
+            bail!("Compression level {level} is out of range, expected 1 to 22");
         }
-        eyre::bail!("Failed to decompress. {last_error:?}")
+        repacker = repacker.with_compression_level(level)?;
+    }
+    if let Some(dict_map) = &args.dict_map {
+        repacker = repacker.with_dict_map(dict_map)?;
     }
+    with_threads(args.threads, || repacker.repack())
+}
 
-    fn write_byml(&self, mut data: Vec<u8>, relative: &Path) -> Result<()> {
-        let name = relative.file_name().map(|n| n.to_string_lossy()).unwrap();
-        if name.ends_with(".zs") {
-            data = self.decompress(&name, &data)?;
+fn convert(args: ConvertArgs) -> Result<()> {
+    let stdin = args.input.as_os_str() == "-";
+    let (name, data, source) = if stdin {
+        let mut data = vec![];
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut data)
+            .context("Failed to read stdin")?;
+        if is_zstd(&data) && args.zsdic.is_none() && args.zsdic_dir.is_none() {
+            // There's no ROM folder to find the dictionaries in
+            bail!("Compressed input from stdin needs --zsdic or --zsdic-dir");
         }
-        match &data[..2] {
-            b"BY" => data[3] = 4,
-            b"YB" => data[2] = 2,
-            _ => return Ok(()),
-        };
-        match Byml::from_binary(&data) {
-            Ok(byml) => {
-                let out = self.output.join(relative).with_extension("yml");
-                out.parent().map(fs::create_dir_all).transpose()?;
-                match serde_yaml::to_string(&byml) {
-                    Ok(text) => fs::write(out, text)?,
-                    Err(_) => println!(
-                        "WARNING: Could not dump {} to YAML.",
-                        relative.display(),
-                        // byml
-                    ),
-                }
-            }
-            Err(e) => {
-                println!(
-                    "WARNING: Failed to parse {}. Reason: {}",
-                    relative.display(),
-                    e
-                );
-                let mut out = self.output.join(relative);
-                if name.ends_with(".zs") {
-                    out.set_extension("");
-                }
-                out.parent().map(fs::create_dir_all).transpose()?;
-                fs::write(out, data)?;
-            }
+        let name = args.name.unwrap_or_else(|| "stdin.pack.zs".into());
+        (name, data, PathBuf::new())
+    } else {
+        let name = args
+            .input
+            .file_name()
+            .context("No filename")?
+            .to_string_lossy()
+            .into_owned();
+        let data = fs::read(&args.input)
+            .with_context(|| format!("Failed to read {}", args.input.display()))?;
+        let source = args
+            .input
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        (name, data, source)
+    };
+    let magic_table = match &args.magic_table {
+        Some(path) => MagicTable::load(path)?,
+        None => MagicTable::default(),
+    };
+    let mut unpacker = Unpacker::new(source, PathBuf::new()).with_options(Options {
+        zsdic: args.zsdic,
+        zsdic_dir: args.zsdic_dir,
+        dict_map: args.dict_map,
+        magic_table,
+        output_format: args.output_format,
+        indent: args.indent,
+        compact: args.compact,
+        expand_gamedata: args.expand_gamedata,
+        ..Default::default()
+    });
+    if name.ends_with(".zs") || (stdin && is_zstd(&data)) {
+        unpacker = unpacker.init_dicts()?;
+    }
+    let text = unpacker.convert_to_text(&name, data)?;
+    match args.output {
+        Some(output) if output.as_os_str() != "-" => fs::write(&output, text)
+            .with_context(|| format!("Failed to write {}", output.display()))?,
+        // A pager or `head` closing the pipe early isn't an error
+        _ => match std::io::stdout().lock().write_all(text.as_bytes()) {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => result.context("Failed to write to stdout")?,
+        },
+    }
+    Ok(())
+}
+
+fn verify(args: VerifyArgs, progress: MultiProgress) -> Result<()> {
+    let source = find_romfs(&args.source)?;
+    let layers = args
+        .layer
+        .iter()
+        .map(|layer| find_romfs(layer))
+        .collect::<Result<Vec<_>>>()?;
+    let magic_table = match &args.magic_table {
+        Some(path) => MagicTable::load(path)?,
+        None => MagicTable::default(),
+    };
+    if !args.dump.is_dir() {
+        bail!("{} is not a folder", args.dump.display());
+    }
+    log::info!("Comparing ROM against {}…", args.dump.display());
+    let unpacker = Unpacker::new(source, args.dump)
+        .with_layers(layers)
+        .with_options(Options {
+            compare: true,
+            zsdic: args.zsdic,
+            zsdic_dir: args.zsdic_dir,
+            dict_map: args.dict_map,
+            magic_table,
+            output_format: args.output_format,
+            extension_style: args.extension_style,
+            sort_keys: args.sort_keys,
+            canonicalize_floats: args.canonicalize_floats,
+            indent: args.indent,
+            compact: args.compact,
+            sarc_layout: args.sarc_layout,
+            compress_output: args.compress_output,
+            ..Default::default()
+        })
+        .with_progress(progress)
+        .init_dicts()?;
+    with_threads(args.threads, || unpacker.unpack())?;
+    let report = unpacker.comparison();
+    for (label, paths) in [
+        ("MISMATCH", &report.mismatched),
+        ("MISSING", &report.missing),
+        ("EXTRA", &report.extra),
+    ] {
+        for path in paths {
+            println!("{label} {}", display_path(path));
         }
-        Ok(())
-    }
-
-    fn unpack(&self) -> Result<()> {
-        let files = jwalk::WalkDir::new(&self.source)
-            .into_iter()
-            .filter_map(|e| e.ok().map(|e| e.path()))
-            .collect::<Vec<_>>();
-        let len = files.len();
-        files
-            .into_par_iter()
-            .progress_count(len as u64)
-            .try_for_each(|file| -> Result<()> {
-                let name = file
-                    .file_name()
-                    .context("No filename")?
-                    .to_str()
-                    .context("Bad filename")?;
-                let relative = file.strip_prefix(&self.source).unwrap();
-                if name.ends_with(".byml.zs") || name.ends_with(".bgyml") {
-                    let data = fs::read(&file)?;
-                    self.write_byml(data, relative)?;
-                } else if name.ends_with(".pack.zs") || name.ends_with(".sarc.zs") {
-                    let data = self.decompress(name, &fs::read(&file)?)?;
-                    let sarc = Sarc::new(data)?;
-                    for file in sarc.files().filter(|f| f.name().is_some()) {
-                        let name = file.unwrap_name();
-                        if name.ends_with(".byml.zs") || name.ends_with(".bgyml") {
-                            let data = file.data().to_vec();
-                            self.write_byml(data, &relative.join(name))?;
-                        } else if file.is_aamp() {
-                            let pio = roead::aamp::ParameterIO::from_binary(file.data)?;
-                            let out = self.output.join(relative).join(name).with_extension("yml");
-                            out.parent().map(fs::create_dir_all).transpose()?;
-                            fs::write(out, serde_yaml::to_string(&pio)?)?;
-                        } else if file.data.starts_with(b"MsgStdBn") {
-                            match msyt::Msyt::from_msbt_bytes(file.data)
-                                .map_err(|e| e.chain().rev().fold(eyre::eyre!("Failed to parse MSBT"), |acc, e| acc.wrap_err(eyre::eyre!("{e}"))))
-                            {
-                                Ok(msbt) => {
-                                    let out =
-                                        self.output.join(relative).join(name).with_extension("yml");
-                                    out.parent().map(fs::create_dir_all).transpose()?;
-                                    match serde_yaml::to_string(&msbt) {
-                                        Ok(text) => fs::write(out, text)?,
-                                        Err(e) => {
-                                            println!("WARNING: Failed to dump MSBT file to YAML. Error: {e:?}.")
-                                        }
-                                    };
-                                }
-                                Err(e) => println!(
-                                    "WARNING: Failed to parse MSBT file {name}. Error: {e:?}."
-                                ),
-                            }
-                        } else {
-                            let out = self.output.join(relative).join(name);
-                            out.parent().map(fs::create_dir_all).transpose()?;
-                            fs::write(out, file.data())?;
-                        }
-                    }
-                }
-                Ok(())
-            })?;
-        println!("Done");
-        Ok(())
     }
+    let failures = unpacker.failures();
+    if failures > 0 {
+        bail!("{failures} files failed to convert");
+    }
+    if !report.is_match() {
+        bail!(
+            "The dump doesn't match: {} mismatched, {} missing, {} extra",
+            report.mismatched.len(),
+            report.missing.len(),
+            report.extra.len()
+        );
+    }
+    log::info!("The dump matches");
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    let args: UnpackArgs = argh::from_env();
-    let mut source = args.source.canonicalize()?;
-    if !source.exists() {
-        bail!("Source directory does not exist");
-    }
-    if !source.ends_with("romfs") {
-        if let subsource = source.join("romfs") && subsource.exists() {
-            source = subsource
-        } else if let upsource = source
-            .parent()
-            .context("No source folder parent")?
-            .join("romfs")
-            && upsource.exists()
-        {
-            source = upsource
-        } else if let upsource = source
-            .parent()
-            .context("No source folder parnet")?
-            .with_file_name("romfs")
-            && upsource.exists()
-        {
-            source = upsource
-        } else {
-            bail!("No romfs folder found");
+fn diff_manifest(args: DiffManifestArgs) -> Result<()> {
+    let read = |path: &PathBuf| -> Result<Vec<manifest::Entry>> {
+        let text = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&text)
+            .with_context(|| format!("Failed to parse manifest {}", path.display()))
+    };
+    let diff = manifest::Diff::new(&read(&args.old)?, &read(&args.new)?);
+    for (label, paths) in [
+        ("ADDED", &diff.added),
+        ("REMOVED", &diff.removed),
+        ("CHANGED", &diff.changed),
+    ] {
+        for path in paths {
+            println!("{label} {}", display_path(path));
         }
     }
-    let output = args
-        .output
-        .unwrap_or_else(|| std::env::current_dir().unwrap().join("unpacked"));
-    println!("Unpacking ROM to {}…", output.display());
-    Unpacker::new(source, output).init_dicts()?.unpack()?;
+    log::info!(
+        "{} added, {} removed, {} changed",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+    if let Some(path) = &args.globs {
+        fs::write(path, diff.globs())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let args: Args = argh::from_env();
+    if matches!(&args.command, Command::Unpack(unpack) if unpack.test_one.is_some()) {
+        // Errors are reported with where they came from
+        std::env::set_var("RUST_BACKTRACE", "1");
+    }
+    let progress = init_logger(&args)?;
+    match args.command {
+        Command::Unpack(args) => unpack(args, progress),
+        Command::Repack(args) => repack(args, progress),
+        Command::Convert(args) => convert(args),
+        Command::Verify(args) => verify(args, progress),
+        Command::DiffManifest(args) => diff_manifest(args),
+    }
+}
@@ -0,0 +1,212 @@
+use crate::format::{Endian, Format};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// One file processed during an unpack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Path in the ROM, relative to the romfs, with SARC members nested under their archive
+    pub path: PathBuf,
+    /// The SARC this file was extracted from, if any
+    pub archive: Option<PathBuf>,
+    /// Path written, relative to the output folder, if anything was written
+    pub output: Option<PathBuf>,
+    pub format: Format,
+    /// Whether the file was converted to YAML rather than written raw
+    pub converted: bool,
+    /// The BYML version from the file's header, before any patching to parse it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u16>,
+    /// The BYML byte order from the file's header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endian: Option<Endian>,
+    /// BLAKE3 hash of the ROM file this came from, the outermost archive for members
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+    /// Where an archive member's data starts in its decompressed archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+    /// The length of an archive member's data as stored in its archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// How a BYML file was stored in the ROM, written next to its text with `--sidecar-meta`
+/// so it can be rebuilt with the same layout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BymlMeta {
+    /// The version from the file's header, before any patching to parse it
+    pub version: u16,
+    pub endian: Endian,
+    /// Whether the file was zstd compressed
+    pub compressed: bool,
+    /// The dictionary it was compressed with: a file in the ZsDic pack, or a suffix from
+    /// the dictionary map. None if it was compressed without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<String>,
+}
+
+/// The step a file failed at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureKind {
+    Parse,
+    /// Converting the parsed file to text
+    Serialize,
+    Panic,
+    /// An archive nested too many levels deep
+    Nesting,
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Parse => "parse",
+            Self::Serialize => "serialize",
+            Self::Panic => "panic",
+            Self::Nesting => "nesting",
+        })
+    }
+}
+
+/// A file that failed to convert, with the full chain of errors
+#[derive(Debug, Clone, Serialize)]
+pub struct Failure {
+    pub path: PathBuf,
+    /// The SARC the file was extracted from, if any
+    pub archive: Option<PathBuf>,
+    pub kind: FailureKind,
+    pub error: String,
+    /// What caused the error, from the outermost cause inward
+    pub causes: Vec<String>,
+}
+
+impl Failure {
+    /// The innermost cause of the failure, on one line.
+    pub fn reason(&self) -> String {
+        self.causes
+            .last()
+            .unwrap_or(&self.error)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// How processing a file ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Outcome {
+    Converted,
+    /// Written as-is, either because it can't be converted or because converting failed
+    RawFallback,
+    /// Nothing written, like members left out by the filter or oversized files
+    Skipped,
+    Failed,
+}
+
+/// The result of processing one file, for programs using the unpacker as a library
+#[derive(Debug, Clone, Serialize)]
+pub struct FileResult {
+    pub path: PathBuf,
+    pub format: Format,
+    pub outcome: Outcome,
+    /// Bytes written for the file, which is zero if it was unchanged or linked to a
+    /// duplicate
+    pub bytes_written: u64,
+    /// Why the file failed to convert, if it did, even if it was then written raw
+    pub error: Option<String>,
+}
+
+/// Finds the ROM file a path came from, which is its outermost archive if it is a member.
+pub(crate) fn source_of(path: &Path) -> &Path {
+    path.ancestors()
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(crate::is_archive)
+                .unwrap_or(false)
+        })
+        .last()
+        .unwrap_or(path)
+}
+
+/// Finds the innermost archive containing a SARC member path.
+pub(crate) fn archive_of(path: &Path) -> Option<&Path> {
+    path.ancestors().skip(1).find(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .map(crate::is_archive)
+            .unwrap_or(false)
+    })
+}
+
+/// How the files listed in one dump's manifest differ from another's, by ROM path
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub changed: Vec<PathBuf>,
+}
+
+impl Diff {
+    /// Compares two manifests. Only what they record can be compared: the source hashes
+    /// kept when dumping with `--skip-unchanged`, archive members' sizes, and each file's
+    /// format and header. A member counts as changed whenever its ROM file's hash did, as
+    /// members have no hashes of their own.
+    pub fn new(old: &[Entry], new: &[Entry]) -> Self {
+        let old = old.iter().map(|e| (&e.path, e)).collect::<BTreeMap<_, _>>();
+        let new = new.iter().map(|e| (&e.path, e)).collect::<BTreeMap<_, _>>();
+        let mut diff = Self::default();
+        for (path, entry) in &new {
+            match old.get(path) {
+                None => diff.added.push(path.to_path_buf()),
+                Some(previous) if !is_same(previous, entry) => {
+                    diff.changed.push(path.to_path_buf())
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|path| !new.contains_key(*path))
+            .map(|path| path.to_path_buf())
+            .collect();
+        diff
+    }
+
+    /// Globs matching each added and changed file, one per line, for `--include-from`.
+    pub fn globs(&self) -> String {
+        let mut globs = String::new();
+        for path in self.added.iter().chain(&self.changed) {
+            // Brackets match glob characters literally
+            for c in crate::display_path(path).chars() {
+                match c {
+                    '*' | '?' | '[' | ']' | '{' | '}' => {
+                        globs.push('[');
+                        globs.push(c);
+                        globs.push(']');
+                    }
+                    c => globs.push(c),
+                }
+            }
+            globs.push('\n');
+        }
+        globs
+    }
+}
+
+fn is_same(old: &Entry, new: &Entry) -> bool {
+    old.format == new.format
+        && old.converted == new.converted
+        && old.version == new.version
+        && old.endian == new.endian
+        && old.size == new.size
+        && match (&old.source_hash, &new.source_hash) {
+            (Some(old), Some(new)) => old == new,
+            _ => true,
+        }
+}
@@ -0,0 +1,121 @@
+use roead::byml::Byml;
+use std::collections::{BTreeMap, HashMap};
+
+/// Whether a file is map placement data, e.g. `Banc/MainField/A-1_Static.bcett.byml.zs`.
+pub fn is_map(name: &str) -> bool {
+    name.contains(".bcett.byml")
+}
+
+/// Readable details of every actor, from the actor info tables, for annotating maps
+#[derive(Debug, Default)]
+pub struct ActorDb(HashMap<String, BTreeMap<String, String>>);
+
+impl ActorDb {
+    /// Adds the rows of an actor info table, keeping each row's text fields, like its
+    /// category, but none of its numbers or nested values.
+    pub fn add_table(&mut self, table: &Byml) {
+        let Byml::Array(rows) = table else {
+            return;
+        };
+        for row in rows {
+            let Byml::Map(fields) = row else {
+                continue;
+            };
+            let Some(Byml::String(id)) = fields.get("__RowId") else {
+                continue;
+            };
+            let info = fields
+                .iter()
+                .filter(|(key, _)| key.as_str() != "__RowId")
+                .filter_map(|(key, value)| match value {
+                    Byml::String(value) => Some((key.to_string(), value.to_string())),
+                    _ => None,
+                })
+                .collect();
+            self.0.insert(id.to_string(), info);
+        }
+    }
+
+    /// The names of every actor.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Annotates a map's placed actors, which the game refers to by instance hash. Each link
+/// gets a `DstActor` naming the actor its `Dst` hash is placed as in the same file, and
+/// each actor in the database gets its text fields as `ActorInfo`. Anything else is left
+/// as it is, so maps that don't look like the usual `Actors` list come out unchanged.
+pub fn annotate(mut map: Byml, db: &ActorDb) -> Byml {
+    let Byml::Map(root) = &mut map else {
+        return map;
+    };
+    let Some(Byml::Array(actors)) = root.get_mut("Actors") else {
+        return map;
+    };
+    let names: HashMap<u64, String> = actors
+        .iter()
+        .filter_map(|actor| match actor {
+            Byml::Map(fields) => Some((
+                hash(fields.get("Hash")?)?,
+                text(fields.get("Gyaml"))?.to_owned(),
+            )),
+            _ => None,
+        })
+        .collect();
+    for actor in actors.iter_mut() {
+        let Byml::Map(fields) = actor else {
+            continue;
+        };
+        if let Some(info) = text(fields.get("Gyaml")).and_then(|name| db.0.get(name)) {
+            fields.insert(
+                "ActorInfo".into(),
+                Byml::Map(
+                    info.iter()
+                        .map(|(key, value)| {
+                            (key.as_str().into(), Byml::String(value.as_str().into()))
+                        })
+                        .collect(),
+                ),
+            );
+        }
+        let Some(Byml::Array(links)) = fields.get_mut("Links") else {
+            continue;
+        };
+        for link in links {
+            let Byml::Map(link) = link else {
+                continue;
+            };
+            let target = link.get("Dst").and_then(hash).and_then(|h| names.get(&h));
+            if let Some(target) = target {
+                link.insert("DstActor".into(), Byml::String(target.as_str().into()));
+            }
+        }
+    }
+    map
+}
+
+fn text(value: Option<&Byml>) -> Option<&str> {
+    match value {
+        Some(Byml::String(name)) => Some(name.as_str()),
+        _ => None,
+    }
+}
+
+fn hash(value: &Byml) -> Option<u64> {
+    match value {
+        Byml::U64(hash) => Some(*hash),
+        Byml::I64(hash) => Some(*hash as u64),
+        Byml::U32(hash) => Some(*hash as u64),
+        Byml::I32(hash) => Some(*hash as u32 as u64),
+        _ => None,
+    }
+}
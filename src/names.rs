@@ -0,0 +1,79 @@
+use crate::map::ActorDb;
+use std::collections::HashMap;
+
+/// Actor names by their hashes, for views that show readable names in place of the hashed
+/// IDs files refer to actors by. Each name is under both its CRC32 and its 32-bit Murmur3
+/// hash, the two the game hashes names with.
+#[derive(Debug, Default)]
+pub struct ActorNames(HashMap<u32, String>);
+
+impl ActorNames {
+    /// Hashes the name of every actor in the database.
+    pub fn new(db: &ActorDb) -> Self {
+        let mut names = HashMap::new();
+        for name in db.names() {
+            names.insert(crc32(name.as_bytes()), name.to_owned());
+            names.insert(murmur3(name.as_bytes()), name.to_owned());
+        }
+        Self(names)
+    }
+
+    /// The actor whose name has this hash, if there is one.
+    pub fn get(&self, hash: u32) -> Option<&str> {
+        self.0.get(&hash).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// CRC32 with the usual reflected polynomial, as zlib computes it
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// 32-bit Murmur3 with a seed of 0
+pub fn murmur3(data: &[u8]) -> u32 {
+    const C1: u32 = 0xCC9E_2D51;
+    const C2: u32 = 0x1B87_3593;
+    let mut hash = 0u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        hash = hash
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xE654_6B64);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .rev()
+            .fold(0u32, |k, &byte| (k << 8) | byte as u32);
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xC2B2_AE35);
+    hash ^ (hash >> 16)
+}
@@ -0,0 +1,123 @@
+use crate::options::BinaryNodes;
+use roead::{aamp::ParameterIO, byml::Byml};
+use serde::{ser::SerializeSeq, Serialize, Serializer};
+use std::cell::RefCell;
+
+/// Serializes a BYML document like its own `Serialize` impl, but with its binary nodes
+/// written another way. With `expand`, those that hold an AAMP or BYML file are written as
+/// that file, under a `!BinaryAamp` or `!BinaryByml` tag. The rest are written as chosen by
+/// `binary`. Views other than plain base64 can't be repacked.
+pub(crate) struct Nested<'a> {
+    pub byml: &'a Byml,
+    /// Sort the entries of every map by key, as with `sort_keys`
+    pub sort: bool,
+    pub expand: bool,
+    pub binary: BinaryNodes,
+    /// The binary nodes to write to files of their own, in order, for
+    /// [`BinaryNodes::External`]. Each is referred to as `<prefix>.<index>.bin`.
+    pub external: &'a RefCell<Vec<Vec<u8>>>,
+    pub prefix: &'a str,
+}
+
+impl<'a> Nested<'a> {
+    fn child<'b>(&'b self, byml: &'b Byml) -> Nested<'b> {
+        Nested {
+            byml,
+            sort: self.sort,
+            expand: self.expand,
+            binary: self.binary,
+            external: self.external,
+            prefix: self.prefix,
+        }
+    }
+}
+
+impl Serialize for Nested<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Variant names and indices follow the declaration order of `Byml`
+        match self.byml {
+            Byml::BinaryData(data) => {
+                if self.expand && data.starts_with(b"AAMP") {
+                    if let Ok(pio) = ParameterIO::from_binary(data) {
+                        return serializer.serialize_newtype_variant("Byml", 1, "BinaryAamp", &pio);
+                    }
+                } else if self.expand && (data.starts_with(b"BY") || data.starts_with(b"YB")) {
+                    if let Ok(byml) = crate::parse_byml(data) {
+                        return serializer.serialize_newtype_variant(
+                            "Byml",
+                            1,
+                            "BinaryByml",
+                            &self.child(&byml),
+                        );
+                    }
+                }
+                match self.binary {
+                    BinaryNodes::Base64 => self.byml.serialize(serializer),
+                    BinaryNodes::Hex => {
+                        let hex = data.iter().map(|b| format!("{b:02x}")).collect::<String>();
+                        serializer.serialize_newtype_variant("Byml", 1, "BinaryHex", &hex)
+                    }
+                    BinaryNodes::External => {
+                        let mut external = self.external.borrow_mut();
+                        let path = format!("{}.{}.bin", self.prefix, external.len());
+                        external.push(data.to_vec());
+                        serializer.serialize_newtype_variant("Byml", 1, "BinaryFile", &path)
+                    }
+                }
+            }
+            Byml::Array(array) => serializer.serialize_newtype_variant(
+                "Byml",
+                3,
+                "Array",
+                &Items(array.iter().map(|item| self.child(item)).collect()),
+            ),
+            Byml::Map(map) => {
+                let mut entries = map.iter().collect::<Vec<_>>();
+                if self.sort {
+                    entries.sort_by(|a, b| a.0.cmp(b.0));
+                }
+                let entries = Entries(
+                    entries
+                        .into_iter()
+                        .map(|(k, v)| (k, self.child(v)))
+                        .collect(),
+                );
+                serializer.serialize_newtype_variant("Byml", 4, "Map", &entries)
+            }
+            Byml::HashMap(map) => {
+                let mut entries = map.iter().collect::<Vec<_>>();
+                if self.sort {
+                    entries.sort_by(|a, b| a.0.cmp(b.0));
+                }
+                let entries = Entries(
+                    entries
+                        .into_iter()
+                        .map(|(k, v)| (k, self.child(v)))
+                        .collect(),
+                );
+                serializer.serialize_newtype_variant("Byml", 5, "HashMap", &entries)
+            }
+            other => other.serialize(serializer),
+        }
+    }
+}
+
+struct Items<'a>(Vec<Nested<'a>>);
+
+impl Serialize for Items<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for item in &self.0 {
+            seq.serialize_element(item)?;
+        }
+        seq.end()
+    }
+}
+
+struct Entries<'a, K>(Vec<(K, Nested<'a>)>);
+
+impl<K: Serialize> Serialize for Entries<'_, K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+    }
+}
@@ -0,0 +1,498 @@
+use crate::{
+    filter::Filter,
+    format::{Format, Formats},
+    language::Languages,
+    magic_table::MagicTable,
+    style,
+    version::GameVersion,
+};
+use eyre::{bail, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The text format converted files are written in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Yaml,
+    Json,
+    /// Only suits simple config files, and can't be repacked for BYML as types are lost
+    Toml,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Yaml => "yml",
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<String> {
+        self.serialize_with(value, None, false)
+    }
+
+    /// Serializes with `indent` spaces per level instead of two, and if `compact`, with
+    /// lists of scalars on one line in YAML or no whitespace at all in JSON.
+    pub fn serialize_with<T: Serialize>(
+        self,
+        value: &T,
+        indent: Option<usize>,
+        compact: bool,
+    ) -> Result<String> {
+        match self {
+            Self::Yaml => {
+                let mut text = serde_yaml::to_string(value)?;
+                if compact {
+                    text = style::compact_yaml(&text);
+                }
+                if let Some(indent) = indent {
+                    text = style::reindent_yaml(&text, indent);
+                }
+                Ok(text)
+            }
+            Self::Json if compact => Ok(serde_json::to_string(value)?),
+            Self::Json => {
+                let indent = " ".repeat(indent.unwrap_or(2));
+                let mut data = vec![];
+                let mut serializer = serde_json::Serializer::with_formatter(
+                    &mut data,
+                    serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes()),
+                );
+                value.serialize(&mut serializer)?;
+                Ok(String::from_utf8(data)?)
+            }
+            Self::Toml => Ok(toml::to_string_pretty(value)?),
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(self, text: &str) -> Result<T> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::from_str(text)?,
+            Self::Json => serde_json::from_str(text)?,
+            Self::Toml => toml::from_str(text)?,
+        })
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            _ => bail!("Unknown output format {s}, expected yaml, json, or toml"),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Yaml => "YAML",
+            Self::Json => "JSON",
+            Self::Toml => "TOML",
+        })
+    }
+}
+
+/// Where SARC members are written relative to their archive
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SarcLayout {
+    /// In a folder named after the archive, with folders in member names kept
+    #[default]
+    Nested,
+    /// In a folder named after the archive, with folders in member names joined by `_`
+    Flat,
+    /// Next to the archive, as if its members were part of the romfs
+    Mirror,
+    /// At the top of the output by their names in the archive alone, so members with the
+    /// same name overwrite each other
+    SingleRoot,
+}
+
+impl SarcLayout {
+    /// Maps a ROM path, with members nested under their archives, to its output path.
+    pub fn apply(self, relative: &Path) -> PathBuf {
+        let Some(archive) = crate::manifest::archive_of(relative) else {
+            return relative.to_path_buf();
+        };
+        let member = relative.strip_prefix(archive).unwrap_or(relative);
+        let archive = self.apply(archive);
+        match self {
+            Self::Nested => archive.join(member),
+            Self::Flat => archive.join(
+                member
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("_"),
+            ),
+            Self::Mirror => archive.parent().unwrap_or(Path::new("")).join(member),
+            Self::SingleRoot => member.to_path_buf(),
+        }
+    }
+}
+
+impl FromStr for SarcLayout {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nested" => Ok(Self::Nested),
+            "flat" => Ok(Self::Flat),
+            "mirror" => Ok(Self::Mirror),
+            "single-root" => Ok(Self::SingleRoot),
+            _ => bail!("Unknown SARC layout {s}, expected nested, flat, mirror, or single-root"),
+        }
+    }
+}
+
+/// What every output is compressed with before it is written, to save space on dumps kept
+/// for archival. This is separate from the game's own zstd compression.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl OutputCompression {
+    /// Adds the suffix of the compression to an output path.
+    pub fn apply(self, out: &Path) -> PathBuf {
+        let suffix = match self {
+            Self::None => return out.to_path_buf(),
+            Self::Gzip => ".gz",
+            // Not `.zs`, which would be taken for one of the game's files
+            Self::Zstd => ".zst",
+        };
+        let mut out = out.as_os_str().to_owned();
+        out.push(suffix);
+        PathBuf::from(out)
+    }
+
+    /// Compresses an output's contents.
+    pub fn encode(self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(match self {
+            Self::None => data.to_vec(),
+            Self::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            Self::Zstd => zstd::encode_all(data, 0)?,
+        })
+    }
+}
+
+impl FromStr for OutputCompression {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => bail!("Unknown output compression {s}, expected none, gzip, or zstd"),
+        }
+    }
+}
+
+/// How binary nodes in BYML files are written in their text
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryNodes {
+    /// As base64, the way they are read back when repacking
+    #[default]
+    Base64,
+    /// As hex under a `!BinaryHex` tag, which diffs byte by byte
+    Hex,
+    /// In a `.bin` file of their own next to the text, referred to by its name under a
+    /// `!BinaryFile` tag
+    External,
+}
+
+impl FromStr for BinaryNodes {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "base64" => Ok(Self::Base64),
+            "hex" => Ok(Self::Hex),
+            "external" => Ok(Self::External),
+            _ => bail!("Unknown binary node style {s}, expected base64, hex, or external"),
+        }
+    }
+}
+
+/// How floats in BYML files are made canonical before they are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatStyle {
+    /// Keep every value, written in the shortest form that reads back the same, but write
+    /// negative zero as zero and every NaN as the same NaN
+    Shortest,
+    /// Round to this many decimal places, which can't be repacked to the same file
+    Fixed(u8),
+}
+
+impl FloatStyle {
+    pub(crate) fn apply(self, value: f64) -> f64 {
+        let value = match self {
+            _ if value.is_nan() => return f64::NAN,
+            Self::Fixed(places) if value.is_finite() => {
+                let scale = 10f64.powi(places as i32);
+                (value * scale).round() / scale
+            }
+            _ => value,
+        };
+        // Also catches values that round to negative zero
+        if value == 0.0 {
+            0.0
+        } else {
+            value
+        }
+    }
+}
+
+impl FromStr for FloatStyle {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "shortest" => Ok(Self::Shortest),
+            // Past this, doubles have no more decimal places to round
+            _ => match s.parse() {
+                Ok(places) if places <= 17 => Ok(Self::Fixed(places)),
+                _ => bail!("Unknown float style {s}, expected shortest or up to 17 decimal places"),
+            },
+        }
+    }
+}
+
+/// How the names of converted files are made from the names of the files in the ROM
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionStyle {
+    /// Replace the last extension with the output format's, e.g. `Foo.bgyml` to `Foo.yml`.
+    /// Files differing only by extension, like `Foo.byml` and `Foo.bgyml`, then overwrite
+    /// each other.
+    Replace,
+    /// Keep the full name and add the output format's extension, e.g. `Foo.bgyml` to
+    /// `Foo.bgyml.yml`. Only `.zs` is dropped, as the output is decompressed.
+    #[default]
+    Append,
+}
+
+impl ExtensionStyle {
+    /// Gives an output path the extension `ext` in this style.
+    pub fn apply(self, out: &Path, ext: &str) -> PathBuf {
+        match self {
+            Self::Replace => out.with_extension(ext),
+            Self::Append => {
+                let mut out = match out.extension() {
+                    Some(zs) if zs == "zs" => out.with_extension(""),
+                    _ => out.to_path_buf(),
+                }
+                .into_os_string();
+                out.push(".");
+                out.push(ext);
+                PathBuf::from(out)
+            }
+        }
+    }
+}
+
+impl FromStr for ExtensionStyle {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "replace" => Ok(Self::Replace),
+            "append" => Ok(Self::Append),
+            _ => bail!("Unknown extension style {s}, expected replace or append"),
+        }
+    }
+}
+
+/// Settings that change how an [`Unpacker`](crate::Unpacker) handles files
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Abort on the first file that fails to convert instead of warning and continuing
+    pub strict: bool,
+    /// Which files to unpack. Archives are opened unless excluded, and their members
+    /// are kept if either the member or its archive is included.
+    pub filter: Filter,
+    /// The ZsDic pack to load dictionaries from, instead of the one in the source folder.
+    /// It may be compressed or already decompressed.
+    pub zsdic: Option<PathBuf>,
+    /// A folder of loose dictionary files to load instead of any ZsDic pack, for when only
+    /// the dictionaries were extracted
+    pub zsdic_dir: Option<PathBuf>,
+    /// A YAML map of extra file suffixes to the dictionary files they are compressed with
+    pub dict_map: Option<PathBuf>,
+    /// Extra magics to handle as one of the existing formats, checked before the built-in
+    /// ones
+    pub magic_table: MagicTable,
+    /// Decompress and parse everything, but only log what would be written
+    pub dry_run: bool,
+    /// Decompress and parse everything to check that it can be, skipping serialization and
+    /// writing nothing at all
+    pub validate: bool,
+    /// Compare each output against the file already at its path in the output folder
+    /// instead of writing it, for [`Unpacker::comparison`](crate::Unpacker::comparison).
+    /// Nothing in the output folder is changed.
+    pub compare: bool,
+    /// Serialize each BYML and AAMP file back to binary after parsing it, and warn about
+    /// any that don't come out the same as the original
+    pub verify_roundtrip: bool,
+    /// Warn about archives that can't be opened and write them raw, instead of aborting
+    pub continue_on_sarc_error: bool,
+    /// Walk into symlinked folders in the source, which are otherwise skipped
+    pub follow_symlinks: bool,
+    /// Skip files whose output already exists and is newer than the source
+    pub incremental: bool,
+    /// Leave outputs that already exist with the same content untouched, so their
+    /// modification times don't change
+    pub skip_identical: bool,
+    pub output_format: OutputFormat,
+    /// Whether converted files keep their original extensions before the output format's
+    pub extension_style: ExtensionStyle,
+    /// Sort BYML map keys so identical documents always dump identically
+    pub sort_keys: bool,
+    /// Rewrite the floats in BYML files in one canonical form, so dumps don't differ by how
+    /// floats are written. With `sort_keys`, this makes the text of a document fully
+    /// deterministic.
+    pub canonicalize_floats: Option<FloatStyle>,
+    /// Spaces per indentation level in text output, instead of two
+    pub indent: Option<usize>,
+    /// Keep text output small, with lists of scalars on one line in YAML
+    pub compact: bool,
+    /// BYML files whose text would be bigger than this many bytes are written as numbered
+    /// parts of their top-level entries, with an index of the parts. These can't be
+    /// repacked.
+    pub split_large_yaml: Option<u64>,
+    /// Dump GameDataList files as tables of flags by hash instead of raw BYML. These can't
+    /// be repacked.
+    pub expand_gamedata: bool,
+    /// Annotate map files' actors with their actor info and links with the actors they
+    /// point to, which needs the actor info tables loaded first. These can't be repacked.
+    pub expand_map: bool,
+    /// Give the expanded views readable actor names for the hashes they refer to actors
+    /// by, from the actor info tables
+    pub resolve_names: bool,
+    /// Write binary nodes in BYML files that hold an AAMP or BYML file as that file, tagged
+    /// with its type. These can't be repacked.
+    pub expand_nested: bool,
+    /// How binary nodes in BYML files are written. Anything but base64 can't be repacked.
+    pub binary_nodes: BinaryNodes,
+    /// Write files with the same content as an earlier one as links to it
+    pub dedupe: bool,
+    /// Also write the original bytes of each converted file next to its text version
+    pub keep_compressed: bool,
+    /// Also write the decompressed bytes of each converted file, as they were parsed, to
+    /// the same path under `_raw` in the output folder
+    pub extract_raw_also: bool,
+    /// An earlier dump to compare against, so only new or changed files are written
+    pub since: Option<PathBuf>,
+    /// Write a `SHA256SUMS` file with the hash of every output
+    pub hashes: bool,
+    /// Write the path of every output, relative to the output folder, to this file, one a
+    /// line and sorted
+    pub paths_file: Option<PathBuf>,
+    /// Write a `.gitattributes` marking the kinds of text file written as text and the
+    /// rest as binary
+    pub gitattributes: bool,
+    /// Record where each archive member's data is in its decompressed archive in the
+    /// manifest
+    pub manifest_offsets: bool,
+    /// Files and archive members bigger than this many bytes are written as they are
+    /// stored, without decompressing or parsing them
+    pub max_file_size: Option<u64>,
+    /// Leave out files over `max_file_size` entirely instead of writing them raw
+    pub skip_large: bool,
+    /// Stop once this many bytes have been written in total, finishing the files in
+    /// progress and writing the manifest as when stopped by hand
+    pub max_output_size: Option<u64>,
+    /// A zip archive to write everything into, instead of the output folder
+    pub zip: Option<PathBuf>,
+    /// Compress every output with this, adding its suffix, like `Foo.bgyml.yml.gz`
+    pub compress_output: OutputCompression,
+    /// Hand outputs to this many threads of their own to write, so parsing doesn't wait on
+    /// slow storage. Ignored when writing a zip archive.
+    pub io_threads: Option<usize>,
+    /// Also write the image data of each BNTX texture's mip levels, next to its summary
+    pub extract_textures: bool,
+    /// Skip ROM files whose hash matches the one in the output folder's manifest from an
+    /// earlier dump
+    pub skip_unchanged: bool,
+    /// A file listing the ROM files finished so far, which are skipped when it is used again,
+    /// so a dump that crashed can carry on where it was. Each file is added once all its
+    /// outputs are written.
+    pub checkpoint: Option<PathBuf>,
+    /// A CSV file to write each file's path, input and output sizes, and the milliseconds
+    /// spent decompressing, parsing, and serializing it to, as each one finishes
+    pub trace: Option<PathBuf>,
+    /// Write a line of JSON to stderr every so often with how many ROM files are done, out
+    /// of how many, and the last one finished, for front-ends to show progress with
+    pub progress_json: bool,
+    /// Remove every file in the output folder that wasn't written by this run, like the
+    /// outputs of files gone from a newer version of the game
+    pub clean: bool,
+    /// Map ROM files into memory instead of reading them into buffers. The ROM must not
+    /// change while it is dumped.
+    pub mmap: bool,
+    /// How many members of one archive are unpacked at once, instead of twice the number
+    /// of threads
+    pub workers_per_sarc: Option<usize>,
+    /// Give each output the modification time of its ROM file, or the archive it's in
+    pub preserve_times: bool,
+    /// Write each BYML file's version, byte order, and compression to `<name>.meta.json`
+    /// next to its text, so it can be repacked exactly as it was
+    pub sidecar_meta: bool,
+    /// How many leading folders to drop from each path relative to the romfs before it is
+    /// joined to the output folder
+    pub prefix_strip: usize,
+    /// Folders to put every output path under, like `100/romfs` for the game's mount point
+    pub prefix_add: Option<PathBuf>,
+    /// The folder paths in the ROM are taken relative to, instead of the source folder or
+    /// layer each file is in, so several sources can be dumped into one tree. Every file
+    /// must be under it.
+    pub relative_to: Option<PathBuf>,
+    /// How many folders deep outputs can go, with any deeper folders joined into the file
+    /// name with `__`, for platforms with short path limits. These can't be repacked.
+    pub flatten_depth: Option<usize>,
+    /// Count the files no converter handles by extension and magic, for
+    /// [`Unpacker::unhandled`](crate::Unpacker::unhandled)
+    pub report_unhandled: bool,
+    /// Only unpack MSBT message files, looking for them in every archive
+    pub only_text: bool,
+    /// Only convert files of these formats, writing everything else raw once
+    /// decompressed. Archives are only opened if `sarc` is one of them.
+    pub parse_only: Option<Formats>,
+    /// Only unpack localized files, like the message packs, for these languages. Packs for
+    /// other languages aren't read at all.
+    pub languages: Option<Languages>,
+    /// Which game version the ROM is, instead of reading it from the ROM's file names, to
+    /// check it against the versions the parsers were written for
+    pub game_version: Option<GameVersion>,
+    /// Only keep this actor's rows of the actor info table. Picking its files is left to
+    /// the filter, with [`actor::globs`](crate::actor::globs).
+    pub actor: Option<String>,
+    /// Folders to write files of some formats under, wherever they are in the ROM
+    pub routes: HashMap<Format, PathBuf>,
+    /// Where SARC members are written. Only the nested layout can be repacked.
+    pub sarc_layout: SarcLayout,
+    /// Write each archive as one document mapping its members' names to their converted
+    /// values, with members that can't be converted as base64 tagged `!binary`. These can't
+    /// be repacked.
+    pub bundle_sarc: bool,
+}
@@ -0,0 +1,199 @@
+use eyre::{ensure, ContextCompat, Result};
+use serde::Serialize;
+
+const PHIVE_MAGIC: &[u8] = b"Phive\0";
+const PHIVE_HEADER_SIZE: usize = 0x28;
+const MATERIAL_SIZE: usize = 0x10;
+/// Havok sections holding further sections rather than data
+const CONTAINERS: &[&[u8; 4]] = &[b"TAG0", b"TYPE", b"INDX"];
+
+/// Reads little endian values from a Phive container
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        Ok(self
+            .0
+            .get(offset..offset + N)
+            .context("Unexpected end of physics data")?
+            .try_into()?)
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn range(&self, offset: usize, size: usize) -> Result<Range> {
+        let range = Range {
+            offset: self.u32(offset)?,
+            size: self.u32(size)?,
+        };
+        ensure!(
+            (range.offset as usize).saturating_add(range.size as usize) <= self.0.len(),
+            "Physics section out of bounds"
+        );
+        Ok(range)
+    }
+}
+
+/// Read-only view of a Phive physics file, or a bare Havok tagfile like cloth, leaving
+/// the Havok objects themselves as opaque sections
+#[derive(Debug, Serialize)]
+pub struct Physics {
+    /// The Phive container's version, if the Havok data is in one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u8>,
+    /// Where the Havok tagfile is in a Phive container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tagfile: Option<Range>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub materials: Vec<Material>,
+    /// Where the collision interaction table is in a Phive container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactions: Option<Range>,
+    pub havok: Tagfile,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Range {
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Material {
+    pub index: u32,
+    pub unknown: u32,
+    /// Which collision layers the material collides with
+    pub flags: u64,
+}
+
+/// The structure of a Havok tagfile
+#[derive(Debug, Serialize)]
+pub struct Tagfile {
+    /// The Havok SDK version it was written by, like `20220100`
+    pub sdk_version: Option<String>,
+    /// Names of the types the file's objects use, like `hknpConvexPolytopeShape`
+    pub types: Vec<String>,
+    pub sections: Vec<Section>,
+}
+
+/// A section of a Havok tagfile, with offsets from the start of the tagfile
+#[derive(Debug, Serialize)]
+pub struct Section {
+    pub tag: String,
+    pub offset: usize,
+    pub size: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Section>,
+}
+
+/// Whether data is a Phive container or a bare Havok tagfile.
+pub(crate) fn is_physics(data: &[u8]) -> bool {
+    data.starts_with(PHIVE_MAGIC) || data.get(4..8) == Some(&b"TAG0"[..])
+}
+
+impl Physics {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(PHIVE_MAGIC) {
+            return Ok(Self {
+                version: None,
+                tagfile: None,
+                materials: vec![],
+                interactions: None,
+                havok: Tagfile::from_binary(data)?,
+            });
+        }
+        ensure!(data.len() >= PHIVE_HEADER_SIZE, "Phive header is truncated");
+        ensure!(
+            data[0x6..0x8] == [0xFF, 0xFE],
+            "Only little endian Phive files are supported"
+        );
+        let reader = Reader(data);
+        let tagfile = reader.range(0xC, 0x1C)?;
+        let materials = reader.range(0x10, 0x20)?;
+        let interactions = reader.range(0x14, 0x24)?;
+        let base = materials.offset as usize;
+        let materials = (0..materials.size as usize / MATERIAL_SIZE)
+            .map(|i| {
+                let offset = base + i * MATERIAL_SIZE;
+                Ok(Material {
+                    index: reader.u32(offset)?,
+                    unknown: reader.u32(offset + 0x4)?,
+                    flags: u64::from_le_bytes(reader.bytes(offset + 0x8)?),
+                })
+            })
+            .collect::<Result<_>>()?;
+        let start = tagfile.offset as usize;
+        Ok(Self {
+            version: Some(data[0x8]),
+            tagfile: Some(tagfile),
+            materials,
+            interactions: Some(interactions),
+            havok: Tagfile::from_binary(&data[start..start + tagfile.size as usize])?,
+        })
+    }
+}
+
+impl Tagfile {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.get(4..8) == Some(&b"TAG0"[..]), "Not a Havok tagfile");
+        let sections = sections(data, 0, data.len())?;
+        let body = |tag: &str| find(&sections, tag).map(|s| &data[s.offset + 8..s.offset + s.size]);
+        Ok(Self {
+            sdk_version: body("SDKV").map(|v| String::from_utf8_lossy(v).into_owned()),
+            // Newer files name their type string table TST1, older ones TSTR
+            types: body("TST1")
+                .or_else(|| body("TSTR"))
+                .map(|table| {
+                    table
+                        .split(|&b| b == 0)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| String::from_utf8_lossy(name).into_owned())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            sections,
+        })
+    }
+}
+
+/// Reads the sections between two offsets. Each starts with its size, including the
+/// header, as a big endian u32 whose top two bits are flags, and then its tag.
+fn sections(data: &[u8], start: usize, end: usize) -> Result<Vec<Section>> {
+    let mut sections = vec![];
+    let mut offset = start;
+    while offset + 8 <= end {
+        let header: [u8; 4] = data[offset..offset + 4].try_into()?;
+        let size = (u32::from_be_bytes(header) & 0x3FFF_FFFF) as usize;
+        ensure!(
+            size >= 8 && offset + size <= end,
+            "Bad Havok section size at 0x{offset:x}"
+        );
+        let tag: [u8; 4] = data[offset + 4..offset + 8].try_into()?;
+        let children = if CONTAINERS.contains(&&tag) {
+            sections(data, offset + 8, offset + size)?
+        } else {
+            vec![]
+        };
+        sections.push(Section {
+            tag: String::from_utf8_lossy(&tag).into_owned(),
+            offset,
+            size,
+            children,
+        });
+        offset += size;
+    }
+    Ok(sections)
+}
+
+/// Finds the first section with a tag, searching depth first.
+fn find<'a>(sections: &'a [Section], tag: &str) -> Option<&'a Section> {
+    sections.iter().find_map(|section| {
+        if section.tag == tag {
+            Some(section)
+        } else {
+            find(&section.children, tag)
+        }
+    })
+}
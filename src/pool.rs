@@ -0,0 +1,66 @@
+use parking_lot::Mutex;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The most memory the pool keeps hold of between archives, so idle buffers don't undo the
+/// limits on how much is unpacked at once
+const MAX_POOLED_BYTES: usize = 256 * 1024 * 1024;
+
+/// Buffers that decompressed archives are read into, handed back once the archive is
+/// unpacked so the next one can reuse the memory instead of allocating its own
+#[derive(Debug, Default)]
+pub(crate) struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    /// The capacity of every buffer in the pool together
+    pooled: AtomicUsize,
+}
+
+impl BufferPool {
+    /// Borrows an empty buffer, returned to the pool when it is dropped. New buffers start
+    /// with no capacity, and grow to fit what is decompressed into them.
+    pub fn take(&self) -> Pooled<'_> {
+        let buffer = self.buffers.lock().pop().map_or_else(Vec::new, |buffer| {
+            self.pooled.fetch_sub(buffer.capacity(), Ordering::Relaxed);
+            buffer
+        });
+        Pooled { pool: self, buffer }
+    }
+
+    fn give(&self, mut buffer: Vec<u8>) {
+        let mut buffers = self.buffers.lock();
+        let pooled = self.pooled.load(Ordering::Relaxed);
+        if pooled + buffer.capacity() <= MAX_POOLED_BYTES {
+            buffer.clear();
+            self.pooled.fetch_add(buffer.capacity(), Ordering::Relaxed);
+            buffers.push(buffer);
+        }
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`]
+pub(crate) struct Pooled<'a> {
+    pool: &'a BufferPool,
+    buffer: Vec<u8>,
+}
+
+impl Deref for Pooled<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for Pooled<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for Pooled<'_> {
+    fn drop(&mut self) {
+        self.pool.give(std::mem::take(&mut self.buffer));
+    }
+}
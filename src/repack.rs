@@ -0,0 +1,389 @@
+use crate::{
+    dict_map,
+    format::{Endian as HeaderEndian, PHIVE_EXTS, RSDB_EXTS},
+    is_archive,
+    manifest::BymlMeta,
+    rstb::ResourceSizeTable,
+    ticker::Ticker,
+    Dictionary, OutputFormat, BYML_VERSION, COMPRESSION_LEVEL, RAW_FOLDER,
+};
+use eyre::{eyre, Context, ContextCompat, Result};
+use indicatif::{MultiProgress, ParallelProgressIterator, ProgressBar};
+use parking_lot::Mutex;
+use rayon::prelude::*;
+use roead::{aamp::ParameterIO, byml::Byml, sarc::SarcWriter, Endian};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use zstd::bulk::Compressor;
+
+/// Files and folders written about the dump itself at the top of the unpacked folder,
+/// which aren't part of the ROM
+const DUMP_FILES: &[&str] = &[
+    "manifest.json",
+    "duplicates.json",
+    "_errors.json",
+    "SHA256SUMS",
+    "removed.txt",
+    RAW_FOLDER,
+];
+
+/// Compressors for one dictionary, each used by one thread at a time. Only taking one and
+/// putting it back is locked, so threads don't wait on each other to compress.
+struct Pool {
+    /// Empty for compressing without a dictionary
+    dict: Vec<u8>,
+    level: i32,
+    idle: Mutex<Vec<Compressor<'static>>>,
+}
+
+impl Pool {
+    /// Starts the pool with one compressor, so a bad dictionary is caught straight away.
+    fn new(dict: Vec<u8>, level: i32) -> Result<Self> {
+        let pool = Self {
+            dict,
+            level,
+            idle: Mutex::default(),
+        };
+        let first = pool.create()?;
+        pool.idle.lock().push(first);
+        Ok(pool)
+    }
+
+    fn create(&self) -> Result<Compressor<'static>> {
+        Ok(if self.dict.is_empty() {
+            Compressor::new(self.level)?
+        } else {
+            Compressor::with_dictionary(self.level, &self.dict)?
+        })
+    }
+
+    fn set_level(&mut self, level: i32) -> Result<()> {
+        self.level = level;
+        for comp in self.idle.get_mut() {
+            comp.set_compression_level(level)?;
+        }
+        Ok(())
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let idle = self.idle.lock().pop();
+        let mut comp = match idle {
+            Some(comp) => comp,
+            None => self.create()?,
+        };
+        let compressed = comp.compress(data);
+        self.idle.lock().push(comp);
+        Ok(compressed?)
+    }
+}
+
+/// Rebuilds ROM files from a folder written by [`Unpacker`](crate::Unpacker).
+pub struct Repacker {
+    source: PathBuf,
+    output: PathBuf,
+    default_comp: Pool,
+    common_comp: Pool,
+    pack_comp: Pool,
+    map_comp: Pool,
+    custom_comp: Vec<(String, Pool)>,
+    level: i32,
+    progress: MultiProgress,
+}
+
+impl Repacker {
+    /// Loads the compression dictionaries from the unpacked ZsDic pack in `source`.
+    pub fn new(source: PathBuf, output: PathBuf) -> Result<Self> {
+        let dicts = source.join("Pack/ZsDic.pack.zs");
+        let read_dict = |name: &str| {
+            fs::read(dicts.join(name))
+                .with_context(|| format!("Unpacked ZsDic pack missing {name}"))
+        };
+        let level = COMPRESSION_LEVEL as i32;
+        Ok(Self {
+            default_comp: Pool::new(vec![], level)?,
+            common_comp: Pool::new(read_dict("zs.zsdic")?, level)?,
+            pack_comp: Pool::new(read_dict("pack.zsdic")?, level)?,
+            map_comp: Pool::new(read_dict("bcett.byml.zsdic")?, level)?,
+            custom_comp: vec![],
+            level,
+            source,
+            output,
+            progress: MultiProgress::new(),
+        })
+    }
+
+    /// Draws progress through the given bars, e.g. so a logger can suspend them. If they are
+    /// hidden, progress is logged every few seconds instead.
+    pub fn with_progress(mut self, progress: MultiProgress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Compresses at a zstd level other than the game's own 15. Lower levels are much
+    /// faster but make bigger files, and higher ones the other way around.
+    pub fn with_compression_level(mut self, level: i32) -> Result<Self> {
+        self.level = level;
+        for comp in [
+            &mut self.default_comp,
+            &mut self.common_comp,
+            &mut self.pack_comp,
+            &mut self.map_comp,
+        ]
+        .into_iter()
+        .chain(self.custom_comp.iter_mut().map(|(_, comp)| comp))
+        {
+            comp.set_level(level)?;
+        }
+        Ok(self)
+    }
+
+    /// Compresses files with the suffixes in a dictionary map using their own dictionaries.
+    pub fn with_dict_map(mut self, path: &Path) -> Result<Self> {
+        for (suffix, dict) in dict_map::load(path)? {
+            self.custom_comp
+                .push((suffix, Pool::new(dict, self.level)?));
+        }
+        Ok(self)
+    }
+
+    fn compress(&self, name: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let pool = match Dictionary::for_name_with(name, &self.custom_comp) {
+            Dictionary::Map => &self.map_comp,
+            Dictionary::Pack => &self.pack_comp,
+            Dictionary::None => &self.default_comp,
+            Dictionary::Common => &self.common_comp,
+            Dictionary::Custom(i) => &self.custom_comp[i].1,
+        };
+        pool.compress(data)
+    }
+
+    /// Converts one unpacked file back to binary, returning its ROM filename and data.
+    fn convert(&self, file: &Path) -> Result<(String, Vec<u8>)> {
+        let name = file
+            .file_name()
+            .context("No filename")?
+            .to_str()
+            .context("Bad filename")?;
+        let text_stem = name
+            .strip_suffix(".yml")
+            .map(|stem| (stem, OutputFormat::Yaml))
+            .or_else(|| {
+                name.strip_suffix(".json")
+                    .map(|stem| (stem, OutputFormat::Json))
+            })
+            .or_else(|| {
+                name.strip_suffix(".toml")
+                    .map(|stem| (stem, OutputFormat::Toml))
+            });
+        // Whether to compress, when a BYML file's sidecar says how it was stored
+        let mut compressed = None;
+        let (mut name, data) = match text_stem {
+            Some((stem, format)) => {
+                let text = fs::read_to_string(file)?;
+                // The unpacked name doesn't always keep the original extension, so fall
+                // back to the most common one for whichever format the YAML parses as
+                let with_ext = |ext: &str| {
+                    if stem.contains('.') {
+                        stem.to_owned()
+                    } else {
+                        format!("{stem}.{ext}")
+                    }
+                };
+                if stem.ends_with(".rsizetable") {
+                    let table: ResourceSizeTable = format.deserialize(&text)?;
+                    (stem.to_owned(), table.to_binary())
+                } else if let Ok(msyt) = format.deserialize::<msyt::Msyt>(&text) {
+                    let data = msyt
+                        .into_msbt_bytes(msyt::Endianness::Little)
+                        .map_err(|e| eyre!("Failed to write MSBT: {e:?}"))?;
+                    (with_ext("msbt"), data)
+                } else if let Ok(pio) = format.deserialize::<ParameterIO>(&text) {
+                    (with_ext("bxml"), pio.to_binary())
+                } else {
+                    let byml: Byml = format.deserialize(&text).with_context(|| {
+                        format!("Could not read {} as BYML, AAMP, or MSBT", file.display())
+                    })?;
+                    let meta = read_meta(file)?;
+                    compressed = meta.as_ref().map(|meta| meta.compressed);
+                    (with_ext("bgyml"), byml_binary(&byml, meta.as_ref()))
+                }
+            }
+            None => (name.to_owned(), fs::read(file)?),
+        };
+        let compressed = compressed.unwrap_or_else(|| {
+            name.ends_with(".byml")
+                || name.ends_with(".rsizetable")
+                || RSDB_EXTS.iter().any(|ext| name.ends_with(ext))
+        });
+        if compressed && !name.ends_with(".zs") {
+            name.push_str(".zs");
+        }
+        let data = if name.ends_with(".zs") {
+            self.compress(&name, &data)?
+        } else {
+            data
+        };
+        Ok((name, data))
+    }
+
+    fn repack_archive(&self, dir: &Path) -> Result<Vec<u8>> {
+        let name = dir
+            .file_name()
+            .context("No filename")?
+            .to_str()
+            .context("Bad filename")?;
+        let mut sarc = SarcWriter::new(Endian::Little);
+        self.add_members(&mut sarc, dir, dir)?;
+        let data = sarc.to_binary();
+        if name.ends_with(".zs") {
+            self.compress(name, &data)
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Adds the files in `dir` to `sarc`, rebuilding nested archive folders as members.
+    fn add_members(&self, sarc: &mut SarcWriter, root: &Path, dir: &Path) -> Result<()> {
+        let mut entries = fs::read_dir(dir)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for path in entries {
+            let name = path
+                .file_name()
+                .context("No filename")?
+                .to_str()
+                .context("Bad filename")?;
+            if is_summary(name) {
+                continue;
+            }
+            let (member, data) = if path.is_dir() {
+                if !is_archive(name) {
+                    self.add_members(sarc, root, &path)?;
+                    continue;
+                }
+                (name.to_owned(), self.repack_archive(&path)?)
+            } else {
+                self.convert(&path)?
+            };
+            let folder = dir
+                .strip_prefix(root)
+                .context("Member outside of archive folder")?;
+            let member = folder
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .chain(std::iter::once(member))
+                .collect::<Vec<_>>()
+                .join("/");
+            sarc.add_file(member, data);
+        }
+        Ok(())
+    }
+
+    /// Repacks every converted file and archive folder in the source folder.
+    pub fn repack(&self) -> Result<()> {
+        let mut units = vec![];
+        collect_units(&self.source, &mut units)?;
+        units.retain(|unit| {
+            !DUMP_FILES
+                .iter()
+                .any(|name| unit.starts_with(self.source.join(name)))
+        });
+        let len = units.len();
+        let ticker = Ticker::new(len, self.progress.is_hidden(), false);
+        units
+            .into_par_iter()
+            .progress_with(self.progress.add(ProgressBar::new(len as u64)))
+            .try_for_each(|unit| -> Result<()> {
+                let relative = unit.strip_prefix(&self.source)?;
+                log::debug!("Repacking {}", relative.display());
+                let (out, data) = if unit.is_dir() {
+                    (self.output.join(relative), self.repack_archive(&unit)?)
+                } else {
+                    let (name, data) = self.convert(&unit)?;
+                    (self.output.join(relative).with_file_name(name), data)
+                };
+                out.parent().map(fs::create_dir_all).transpose()?;
+                fs::write(out, data)?;
+                ticker.tick(relative);
+                Ok(())
+            })?;
+        log::info!("Done");
+        Ok(())
+    }
+}
+
+/// How a BYML file was stored in the ROM, from the `.meta.json` written next to its text
+/// with `--sidecar-meta`, if there is one.
+fn read_meta(file: &Path) -> Result<Option<BymlMeta>> {
+    let path = file.with_extension("meta.json");
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(&path)?;
+    serde_json::from_str(&text)
+        .with_context(|| format!("Bad BYML meta file {}", path.display()))
+        .map(Some)
+}
+
+/// Writes a BYML document in the byte order and with the version it had in the ROM, or
+/// as TOTK's usual little endian version 7 without a sidecar to say.
+fn byml_binary(byml: &Byml, meta: Option<&BymlMeta>) -> Vec<u8> {
+    let (endian, version) = match meta {
+        Some(meta) => (meta.endian, meta.version),
+        None => (HeaderEndian::Little, BYML_VERSION),
+    };
+    let mut data = byml.to_binary(endian.into());
+    // roead writes its own version, which doesn't change the layout
+    let version = match endian {
+        HeaderEndian::Big => version.to_be_bytes(),
+        HeaderEndian::Little => version.to_le_bytes(),
+    };
+    data[2..4].copy_from_slice(&version);
+    data
+}
+
+/// Whether a file is an audio, AI, event, texture, terrain, physics, or font summary, a folder of extracted
+/// texture data, a BYML sidecar, or a bundled archive, which are only written for reference.
+fn is_summary(name: &str) -> bool {
+    if name.ends_with(".bntx.surfaces") || name.ends_with(".meta.json") {
+        return true;
+    }
+    let stem = name
+        .strip_suffix(".yml")
+        .or_else(|| name.strip_suffix(".json"))
+        .or_else(|| name.strip_suffix(".toml"));
+    stem.map(|stem| {
+        [
+            ".bars", ".bwav", ".ainb", ".bfevfl", ".bntx", ".tscb", ".bffnt",
+        ]
+        .iter()
+        .chain(PHIVE_EXTS)
+        .any(|ext| stem.ends_with(ext))
+            || is_archive(stem)
+    })
+    .unwrap_or(false)
+}
+
+/// Collects loose files and archive folders, without descending into the archives.
+fn collect_units(dir: &Path, units: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if is_summary(name) {
+            continue;
+        }
+        let archive = is_archive(name);
+        if path.is_dir() && !archive {
+            collect_units(&path, units)?;
+        } else {
+            units.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::{io, thread, time::Duration};
+
+/// How many times an output write is tried before its error is returned
+const ATTEMPTS: u32 = 4;
+/// How long to wait before the first retry, doubling after each one
+const BACKOFF: Duration = Duration::from_millis(50);
+
+/// Whether an error could go away by itself, like a network share dropping out, rather
+/// than one that will happen again every time.
+fn is_transient(error: &io::Error) -> bool {
+    !matches!(
+        error.kind(),
+        io::ErrorKind::NotFound
+            | io::ErrorKind::PermissionDenied
+            | io::ErrorKind::AlreadyExists
+            | io::ErrorKind::InvalidInput
+            | io::ErrorKind::InvalidData
+            | io::ErrorKind::Unsupported
+    )
+}
+
+/// Runs a filesystem operation, trying it again with a short backoff if it fails with an
+/// error that may be transient.
+pub(crate) fn retry<T>(mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut delay = BACKOFF;
+    for _ in 1..ATTEMPTS {
+        match op() {
+            Err(e) if is_transient(&e) => {
+                log::debug!("Retrying after {e}");
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    op()
+}
@@ -0,0 +1,79 @@
+use eyre::{ensure, ContextCompat, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const HEADER_SIZE: usize = 0x16;
+const NAME_SIZE: usize = 0xA0;
+
+/// TOTK resource size table, with both lookup tables sorted for a stable dump
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResourceSizeTable {
+    pub version: u32,
+    /// Sizes keyed by the CRC32 of the resource path
+    pub hashes: BTreeMap<u32, u32>,
+    /// Sizes keyed by resource path, for paths whose hashes collide
+    pub names: BTreeMap<String, u32>,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .context("Unexpected end of RSTB data")?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+impl ResourceSizeTable {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"RESTBL"), "Not a RESTBL file");
+        let version = read_u32(data, 0x6)?;
+        let string_size = read_u32(data, 0xA)? as usize;
+        let hash_count = read_u32(data, 0xE)? as usize;
+        let name_count = read_u32(data, 0x12)? as usize;
+        let mut table = Self {
+            version,
+            ..Default::default()
+        };
+        let mut offset = HEADER_SIZE;
+        for _ in 0..hash_count {
+            table
+                .hashes
+                .insert(read_u32(data, offset)?, read_u32(data, offset + 4)?);
+            offset += 8;
+        }
+        for _ in 0..name_count {
+            let name = data
+                .get(offset..offset + string_size)
+                .context("Unexpected end of RSTB data")?;
+            let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+            let name = String::from_utf8_lossy(&name[..len]).into_owned();
+            table
+                .names
+                .insert(name, read_u32(data, offset + string_size)?);
+            offset += string_size + 4;
+        }
+        Ok(table)
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(
+            HEADER_SIZE + self.hashes.len() * 8 + self.names.len() * (NAME_SIZE + 4),
+        );
+        data.extend_from_slice(b"RESTBL");
+        data.extend_from_slice(&self.version.to_le_bytes());
+        data.extend_from_slice(&(NAME_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&(self.hashes.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(self.names.len() as u32).to_le_bytes());
+        for (hash, size) in &self.hashes {
+            data.extend_from_slice(&hash.to_le_bytes());
+            data.extend_from_slice(&size.to_le_bytes());
+        }
+        for (name, size) in &self.names {
+            let mut buf = [0u8; NAME_SIZE];
+            let len = name.len().min(NAME_SIZE - 1);
+            buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+            data.extend_from_slice(&buf);
+            data.extend_from_slice(&size.to_le_bytes());
+        }
+        data
+    }
+}
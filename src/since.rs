@@ -0,0 +1,59 @@
+use parking_lot::Mutex;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Files written at the top of an output folder that describe the dump itself
+pub(crate) const METADATA_FILES: &[&str] = &[
+    "manifest.json",
+    "duplicates.json",
+    "removed.txt",
+    "SHA256SUMS",
+    "_errors.json",
+];
+
+/// Compares outputs against an earlier dump, so only what changed is written
+#[derive(Debug, Default)]
+pub(crate) struct Since {
+    produced: Mutex<HashSet<PathBuf>>,
+    unchanged: AtomicUsize,
+}
+
+impl Since {
+    /// Marks `relative` as produced, returning whether the dump in `previous` already has it
+    /// with the same content.
+    pub fn is_unchanged(&self, previous: &Path, relative: &Path, data: &[u8]) -> bool {
+        self.produced.lock().insert(relative.to_path_buf());
+        let unchanged = fs::read(previous.join(relative))
+            .map(|old| old == data)
+            .unwrap_or(false);
+        if unchanged {
+            self.unchanged.fetch_add(1, Ordering::Relaxed);
+        }
+        unchanged
+    }
+
+    pub fn unchanged(&self) -> usize {
+        self.unchanged.load(Ordering::Relaxed)
+    }
+
+    /// Files in the dump in `previous` that weren't produced this time, sorted.
+    pub fn removed(&self, previous: &Path) -> Vec<PathBuf> {
+        let produced = self.produced.lock();
+        let mut removed = jwalk::WalkDir::new(previous)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| !e.file_type().is_dir())
+            .filter_map(|e| e.path().strip_prefix(previous).ok().map(Path::to_path_buf))
+            .filter(|path| {
+                !produced.contains(path)
+                    && !METADATA_FILES.iter().any(|name| path == Path::new(name))
+            })
+            .collect::<Vec<_>>();
+        removed.sort();
+        removed
+    }
+}
@@ -0,0 +1,49 @@
+use roead::byml::Byml;
+use serde::{ser::SerializeSeq, Serialize, Serializer};
+
+/// Serializes a BYML document like its own `Serialize` impl, but with the entries of every
+/// map sorted by key so the same document always produces the same text. Arrays keep
+/// their order.
+pub(crate) struct SortedByml<'a>(pub &'a Byml);
+
+impl Serialize for SortedByml<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Variant names and indices follow the declaration order of `Byml`
+        match self.0 {
+            Byml::Array(array) => {
+                serializer.serialize_newtype_variant("Byml", 3, "Array", &SortedArray(array))
+            }
+            Byml::Map(map) => {
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                serializer.serialize_newtype_variant("Byml", 4, "Map", &SortedEntries(entries))
+            }
+            Byml::HashMap(map) => {
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                serializer.serialize_newtype_variant("Byml", 5, "HashMap", &SortedEntries(entries))
+            }
+            other => other.serialize(serializer),
+        }
+    }
+}
+
+struct SortedArray<'a>(&'a [Byml]);
+
+impl Serialize for SortedArray<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for item in self.0 {
+            seq.serialize_element(&SortedByml(item))?;
+        }
+        seq.end()
+    }
+}
+
+struct SortedEntries<'a, K>(Vec<(K, &'a Byml)>);
+
+impl<K: Serialize> Serialize for SortedEntries<'_, K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(k, v)| (k, SortedByml(v))))
+    }
+}
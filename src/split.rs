@@ -0,0 +1,54 @@
+use roead::byml::Byml;
+
+/// Splits the top-level entries of an array or map into about `parts` documents of the same
+/// kind, each with about the same number of entries. Map entries are sorted by key first so
+/// the parts are the same every time. Other nodes can't be split.
+pub(crate) fn split(byml: &Byml, parts: usize) -> Option<Vec<Byml>> {
+    let size = |len: usize| len.div_ceil(parts.max(1)).max(1);
+    Some(match byml {
+        Byml::Array(array) => array
+            .chunks(size(array.len()))
+            .map(|chunk| Byml::Array(chunk.to_vec()))
+            .collect(),
+        Byml::Map(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            entries
+                .chunks(size(entries.len()))
+                .map(|chunk| {
+                    Byml::Map(
+                        chunk
+                            .iter()
+                            .map(|(k, v)| ((*k).clone(), (*v).clone()))
+                            .collect(),
+                    )
+                })
+                .collect()
+        }
+        Byml::HashMap(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            entries
+                .chunks(size(entries.len()))
+                .map(|chunk| {
+                    Byml::HashMap(
+                        chunk
+                            .iter()
+                            .map(|(k, v)| ((*k).clone(), (*v).clone()))
+                            .collect(),
+                    )
+                })
+                .collect()
+        }
+        _ => return None,
+    })
+}
+
+/// What kind of node was split, for the index of parts.
+pub(crate) fn kind(byml: &Byml) -> &'static str {
+    match byml {
+        Byml::Array(_) => "array",
+        Byml::HashMap(_) => "hash_map",
+        _ => "map",
+    }
+}
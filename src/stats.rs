@@ -0,0 +1,274 @@
+use crate::{format::Format, manifest::FileResult};
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    fmt,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Counters accumulated over an unpack
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub byml: AtomicUsize,
+    pub aamp: AtomicUsize,
+    pub msbt: AtomicUsize,
+    pub rstb: AtomicUsize,
+    /// Sound banks and waveforms summarized
+    pub audio: AtomicUsize,
+    pub ainb: AtomicUsize,
+    pub bfevfl: AtomicUsize,
+    pub textures: AtomicUsize,
+    /// Terrain scenes summarized
+    pub terrain: AtomicUsize,
+    /// Physics files summarized
+    pub physics: AtomicUsize,
+    /// Fonts summarized
+    pub fonts: AtomicUsize,
+    /// Files written as-is, whether unconvertible or after a failed conversion
+    pub raw: AtomicUsize,
+    pub failures: AtomicUsize,
+    /// Failures where parsing panicked, which are also counted as failures
+    pub panics: AtomicUsize,
+    /// Converted files that didn't serialize back to their original bytes, when checked
+    pub roundtrip_mismatches: AtomicUsize,
+    pub bytes_read: AtomicU64,
+    pub bytes_written: AtomicU64,
+    pub timings: Timings,
+}
+
+/// A snapshot of the counters for a run, small enough to track across dumps
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    /// Files and archive members processed, whatever their outcome
+    pub files: usize,
+    /// Files converted, by kind
+    pub converted: BTreeMap<&'static str, usize>,
+    pub raw: usize,
+    pub failures: usize,
+    pub panics: usize,
+    pub roundtrip_mismatches: usize,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub elapsed_secs: f64,
+    /// Output sizes of the files of each format
+    pub by_format: BTreeMap<Format, Sizes>,
+    /// Output sizes of the files with each extension, like `bgyml` or `bcett.byml`
+    pub by_extension: BTreeMap<String, Sizes>,
+}
+
+/// How big the outputs of a group of files were, in bytes. Files that were unchanged or
+/// linked to a duplicate count as writing nothing.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Sizes {
+    pub count: usize,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub total: u64,
+}
+
+impl Sizes {
+    fn add(&mut self, size: u64) {
+        self.min = if self.count == 0 {
+            size
+        } else {
+            self.min.min(size)
+        };
+        self.max = self.max.max(size);
+        self.count += 1;
+        self.total += size;
+        self.mean = self.total as f64 / self.count as f64;
+    }
+}
+
+impl Stats {
+    /// Takes a snapshot of the counters, given the files processed and how long it took.
+    pub fn summary(&self, results: &[FileResult], elapsed: Duration) -> Summary {
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+        let mut by_format = BTreeMap::<_, Sizes>::new();
+        let mut by_extension = BTreeMap::<_, Sizes>::new();
+        for result in results {
+            by_format
+                .entry(result.format)
+                .or_default()
+                .add(result.bytes_written);
+            by_extension
+                .entry(crate::extension_of(&result.path))
+                .or_default()
+                .add(result.bytes_written);
+        }
+        Summary {
+            files: results.len(),
+            converted: [
+                ("byml", &self.byml),
+                ("aamp", &self.aamp),
+                ("msbt", &self.msbt),
+                ("rstb", &self.rstb),
+                ("audio", &self.audio),
+                ("ainb", &self.ainb),
+                ("bfevfl", &self.bfevfl),
+                ("textures", &self.textures),
+                ("terrain", &self.terrain),
+                ("physics", &self.physics),
+                ("fonts", &self.fonts),
+            ]
+            .into_iter()
+            .map(|(kind, counter)| (kind, load(counter)))
+            .collect(),
+            raw: load(&self.raw),
+            failures: load(&self.failures),
+            panics: load(&self.panics),
+            roundtrip_mismatches: load(&self.roundtrip_mismatches),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            elapsed_secs: elapsed.as_secs_f64(),
+            by_format,
+            by_extension,
+        }
+    }
+
+    pub(crate) fn count(&self, format: Format, converted: bool) {
+        let counter = match format {
+            _ if !converted => &self.raw,
+            Format::Byml => &self.byml,
+            Format::Aamp => &self.aamp,
+            Format::Msbt => &self.msbt,
+            Format::Rstb => &self.rstb,
+            Format::Bars | Format::Bwav => &self.audio,
+            Format::Ainb => &self.ainb,
+            Format::Bfevfl => &self.bfevfl,
+            Format::Bntx => &self.textures,
+            Format::Tscb => &self.terrain,
+            Format::Phive => &self.physics,
+            Format::Bffnt => &self.fonts,
+            Format::Sarc | Format::Raw => &self.raw,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+        write!(
+            f,
+            "Converted {} BYML, {} AAMP, {} MSBT, {} RSTB, {} audio, {} AINB, {} BFEVFL, {} BNTX, {} TSCB, {} physics, {} fonts, {} raw fallbacks, {} failures \
+             ({} panics). Read {}, wrote {}.",
+            load(&self.byml),
+            load(&self.aamp),
+            load(&self.msbt),
+            load(&self.rstb),
+            load(&self.audio),
+            load(&self.ainb),
+            load(&self.bfevfl),
+            load(&self.textures),
+            load(&self.terrain),
+            load(&self.physics),
+            load(&self.fonts),
+            load(&self.raw),
+            load(&self.failures),
+            load(&self.panics),
+            HumanBytes(self.bytes_read.load(Ordering::Relaxed)),
+            HumanBytes(self.bytes_written.load(Ordering::Relaxed)),
+        )
+    }
+}
+
+/// A step in converting a file, timed for [`Timings`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Stage {
+    Decompress,
+    /// Reading BYML, AAMP, and MSBT files
+    Parse,
+    /// Converting parsed files to text
+    Serialize,
+}
+
+/// Time spent in each stage of converting files, in nanoseconds summed across threads, so
+/// together they can add up to more than the time taken
+#[derive(Debug, Default)]
+pub struct Timings {
+    pub decompress: AtomicU64,
+    pub parse: AtomicU64,
+    pub serialize: AtomicU64,
+}
+
+impl Timings {
+    /// Runs `f`, adding the time it takes to `stage`.
+    pub(crate) fn time<T>(&self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let counter = match stage {
+            Stage::Decompress => &self.decompress,
+            Stage::Parse => &self.parse,
+            Stage::Serialize => &self.serialize,
+        };
+        let nanos = start.elapsed().as_nanos() as u64;
+        counter.fetch_add(nanos, Ordering::Relaxed);
+        TRACED.with(|traced| {
+            let mut times = traced.get();
+            times.nanos[stage as usize] += nanos;
+            traced.set(times);
+        });
+        result
+    }
+}
+
+thread_local! {
+    /// The size of the file being unpacked on this thread and the time spent on it so far
+    static TRACED: Cell<FileTimes> = Cell::new(FileTimes::default());
+}
+
+/// The size of one file and the time spent in each stage on it, for `--trace`
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct FileTimes {
+    pub input: u64,
+    /// Nanoseconds, in the order of [`Stage`]
+    pub nanos: [u64; 3],
+}
+
+impl FileTimes {
+    /// Runs `f` for a file of `input` bytes, counting the stages timed meanwhile on this
+    /// thread towards it. The file before is put back afterwards, since a thread can pick up
+    /// archive members while it waits on its own archive's.
+    pub fn scope<T>(input: u64, f: impl FnOnce() -> T) -> T {
+        let outer = TRACED.with(|traced| {
+            traced.replace(Self {
+                input,
+                ..Default::default()
+            })
+        });
+        let result = f();
+        TRACED.with(|traced| traced.set(outer));
+        result
+    }
+
+    /// The file being unpacked on this thread.
+    pub fn current() -> Self {
+        TRACED.with(Cell::get)
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stages = [
+            ("decompress", &self.decompress),
+            ("parse", &self.parse),
+            ("serialize", &self.serialize),
+        ]
+        .map(|(name, nanos)| (name, nanos.load(Ordering::Relaxed)));
+        let total = stages.iter().map(|(_, nanos)| nanos).sum::<u64>().max(1);
+        for (name, nanos) in stages {
+            writeln!(
+                f,
+                "{name:<12} {:>10.2?} {:>5.1}%",
+                Duration::from_nanos(nanos),
+                nanos as f64 * 100.0 / total as f64
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! Rewrites serde_yaml's output, which has no formatting options of its own
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Whether a line ends by opening a block scalar, whose content lines are left alone.
+fn opens_block_scalar(line: &str) -> bool {
+    line.trim_end()
+        .rsplit(' ')
+        .next()
+        .map(|token| {
+            token.starts_with(['|', '>'])
+                && token[1..]
+                    .chars()
+                    .all(|c| c == '+' || c == '-' || c.is_ascii_digit())
+        })
+        .unwrap_or(false)
+}
+
+/// Splits the text into lines, marking those that are block scalar content.
+fn classify(text: &str) -> Vec<(&str, bool)> {
+    let mut lines = Vec::new();
+    let mut block: Option<usize> = None;
+    for line in text.lines() {
+        if let Some(parent) = block {
+            if line.trim().is_empty() || indent_of(line) > parent {
+                lines.push((line, true));
+                continue;
+            }
+            block = None;
+        }
+        if opens_block_scalar(line) {
+            block = Some(indent_of(line));
+        }
+        lines.push((line, false));
+    }
+    lines
+}
+
+/// Whether a sequence item's text can go in a flow sequence as-is.
+fn is_flow_scalar(item: &str) -> bool {
+    let value = match item.strip_prefix('!') {
+        // A tag on its own introduces a nested collection
+        Some(tagged) => match tagged.split_once(' ') {
+            Some((_, value)) => value,
+            None => return false,
+        },
+        None => item,
+    };
+    if value.starts_with(['\'', '"']) {
+        return true;
+    }
+    !value.is_empty()
+        && !value.starts_with(['|', '>', '&', '*', '?', '!'])
+        && !value.starts_with("- ")
+        && !value.contains([',', '[', ']', '{', '}', '#', ':'])
+}
+
+/// Whether a line is a map key whose value is a sequence starting on the next line, which
+/// serde_yaml writes at the same indentation as the key.
+fn opens_sequence(line: &str, indent: usize) -> bool {
+    indent_of(line) == indent
+        && !line[indent..].starts_with("- ")
+        && (line.ends_with(':')
+            || line
+                .rsplit_once(": ")
+                .map(|(_, tag)| tag.starts_with('!') && !tag.contains(' '))
+                .unwrap_or(false))
+}
+
+/// Puts sequences of scalars that belong to a map key on the key's line, as flow sequences.
+pub(crate) fn compact_yaml(text: &str) -> String {
+    let lines = classify(text);
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let (line, content) = lines[i];
+        let indent = indent_of(line);
+        let opener = i > 0
+            && !lines[i - 1].1
+            && out
+                .last()
+                .map(|prev| opens_sequence(prev, indent))
+                .unwrap_or(false);
+        if !content && opener && line[indent..].starts_with("- ") {
+            let mut items = vec![];
+            let mut j = i;
+            while j < lines.len() {
+                let (item_line, item_content) = lines[j];
+                if item_content
+                    || indent_of(item_line) != indent
+                    || !item_line[indent..].starts_with("- ")
+                {
+                    break;
+                }
+                let item = &item_line[indent + 2..];
+                if !is_flow_scalar(item) {
+                    break;
+                }
+                items.push(item);
+                j += 1;
+            }
+            // Only compact when the whole sequence is scalars and nothing continues them
+            let ends = lines
+                .get(j)
+                .map(|(next, _)| {
+                    indent_of(next) < indent
+                        || (indent_of(next) == indent && !next[indent..].starts_with("- "))
+                })
+                .unwrap_or(true);
+            if !items.is_empty() && ends {
+                let last = out.last_mut().expect("opener line was checked");
+                last.push_str(&format!(" [{}]", items.join(", ")));
+                i = j;
+                continue;
+            }
+        }
+        out.push(line.to_owned());
+        i += 1;
+    }
+    let mut text = out.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Changes the two-space indentation serde_yaml emits to `width` spaces per level, padding
+/// sequence markers to match so nested maps stay aligned.
+pub(crate) fn reindent_yaml(text: &str, width: usize) -> String {
+    // Sequence markers take at least two columns, so narrower levels can't line up
+    let width = width.max(2);
+    let scale = |spaces: usize| " ".repeat(spaces / 2 * width + spaces % 2);
+    let mut out = String::with_capacity(text.len());
+    // Block scalar content only has its base indentation moved, keeping any spaces past it
+    let mut base = None;
+    for (line, content) in classify(text) {
+        let indent = indent_of(line);
+        if content {
+            if !line.trim().is_empty() {
+                let base = *base.get_or_insert(indent);
+                out.push_str(&scale(base));
+                out.push_str(&line[base.min(indent)..]);
+            }
+            out.push('\n');
+            continue;
+        }
+        base = None;
+        out.push_str(&scale(indent));
+        let mut rest = &line[indent..];
+        while let Some(item) = rest.strip_prefix("- ") {
+            out.push('-');
+            out.push_str(&" ".repeat(width - 1));
+            rest = item;
+        }
+        out.push_str(rest);
+        out.push('\n');
+    }
+    out
+}
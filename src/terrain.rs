@@ -0,0 +1,168 @@
+use eyre::{ensure, ContextCompat, Result};
+use serde::Serialize;
+
+const HEADER_SIZE: usize = 0x30;
+const AREA_SIZE: usize = 0x30;
+
+/// Reads values from a TSCB, where offsets are relative to where they are stored. The byte
+/// order isn't marked, so it is taken from which way round the version reads.
+struct Reader<'a> {
+    data: &'a [u8],
+    big: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        Ok(self
+            .data
+            .get(offset..offset + N)
+            .context("Unexpected end of TSCB data")?
+            .try_into()?)
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset)?;
+        Ok(if self.big {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn f32(&self, offset: usize) -> Result<f32> {
+        Ok(f32::from_bits(self.u32(offset)?))
+    }
+
+    /// Follows the relative offset stored at `offset`.
+    fn ptr(&self, offset: usize) -> Result<usize> {
+        Ok(offset + self.u32(offset)? as usize)
+    }
+
+    /// Strings are null terminated.
+    fn string(&self, ptr: usize) -> Result<String> {
+        let bytes = self.data.get(ptr..).context("TSCB string out of bounds")?;
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+
+    /// The entries of an offset table, which starts with its length.
+    fn table(&self, offset: usize) -> Result<Vec<usize>> {
+        let count = self.u32(offset)? as usize;
+        ensure!(count <= self.data.len() / 4, "Bad TSCB table length");
+        (0..count).map(|i| self.ptr(offset + 4 + i * 4)).collect()
+    }
+}
+
+/// Read-only view of a TSCB terrain scene, listing its materials and the terrain tiles
+/// of each area
+#[derive(Debug, Serialize)]
+pub struct Tscb {
+    pub version: u32,
+    pub world_scale: f32,
+    pub max_height: f32,
+    pub tile_size: f32,
+    pub materials: Vec<Material>,
+    pub areas: Vec<Area>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Material {
+    pub index: u32,
+    pub u_scale: f32,
+    pub v_scale: f32,
+    pub unknown: [f32; 2],
+}
+
+#[derive(Debug, Serialize)]
+pub struct Area {
+    /// Base name of the terrain files for the area, like `5000000000`
+    pub name: String,
+    pub x: f32,
+    pub z: f32,
+    pub size: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    pub min_water_height: f32,
+    pub max_water_height: f32,
+    pub unknown: [u32; 3],
+    /// Extra values following the area, which differ by which files it has
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<u32>,
+}
+
+impl Tscb {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"TSCB"), "Not a TSCB file");
+        ensure!(data.len() >= HEADER_SIZE, "TSCB header is truncated");
+        let reader = Reader {
+            data,
+            big: data[4] != 0,
+        };
+        let materials = reader
+            .table(HEADER_SIZE)?
+            .into_iter()
+            .map(|base| Material::read(&reader, base))
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            materials.len() == reader.u32(0x18)? as usize,
+            "TSCB material count does not match its header"
+        );
+        let areas = reader
+            .table(reader.ptr(0xC)?)?
+            .into_iter()
+            .map(|base| Area::read(&reader, base))
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            areas.len() == reader.u32(0x1C)? as usize,
+            "TSCB area count does not match its header"
+        );
+        Ok(Self {
+            version: reader.u32(0x4)?,
+            world_scale: reader.f32(0x10)?,
+            max_height: reader.f32(0x14)?,
+            tile_size: reader.f32(0x28)?,
+            materials,
+            areas,
+        })
+    }
+}
+
+impl Material {
+    fn read(reader: &Reader, base: usize) -> Result<Self> {
+        Ok(Self {
+            index: reader.u32(base)?,
+            u_scale: reader.f32(base + 0x4)?,
+            v_scale: reader.f32(base + 0x8)?,
+            unknown: [reader.f32(base + 0xC)?, reader.f32(base + 0x10)?],
+        })
+    }
+}
+
+impl Area {
+    fn read(reader: &Reader, base: usize) -> Result<Self> {
+        let extra_len = reader.u32(base + 0x2C)? as usize;
+        ensure!(
+            extra_len <= reader.data.len() / 4,
+            "Bad TSCB area extra info length"
+        );
+        let extra = (0..extra_len)
+            .map(|i| reader.u32(base + AREA_SIZE + i * 4))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            name: reader.string(reader.ptr(base + 0x20)?)?,
+            x: reader.f32(base)?,
+            z: reader.f32(base + 0x4)?,
+            size: reader.f32(base + 0x8)?,
+            min_height: reader.f32(base + 0xC)?,
+            max_height: reader.f32(base + 0x10)?,
+            min_water_height: reader.f32(base + 0x14)?,
+            max_water_height: reader.f32(base + 0x18)?,
+            unknown: [
+                reader.u32(base + 0x1C)?,
+                reader.u32(base + 0x24)?,
+                reader.u32(base + 0x28)?,
+            ],
+            extra,
+        })
+    }
+}
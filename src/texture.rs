@@ -0,0 +1,183 @@
+use eyre::{ensure, ContextCompat, Result};
+use serde::Serialize;
+
+/// Reads little endian values from a BNTX, where pointers are offsets into the file
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        Ok(self
+            .0
+            .get(offset..offset + N)
+            .context("Unexpected end of BNTX data")?
+            .try_into()?)
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8> {
+        Ok(self.bytes::<1>(offset)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(offset)?))
+    }
+
+    fn ptr(&self, offset: usize) -> Result<usize> {
+        Ok(u64::from_le_bytes(self.bytes(offset)?) as usize)
+    }
+
+    /// Strings are stored with a u16 length before them.
+    fn string(&self, ptr: usize) -> Result<String> {
+        let len = self.u16(ptr)? as usize;
+        let bytes = self
+            .0
+            .get(ptr + 2..ptr + 2 + len)
+            .context("BNTX string out of bounds")?;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+fn format_name(format: u32) -> String {
+    let kind = match format >> 8 {
+        0x02 => "R8",
+        0x07 => "R5G6B5",
+        0x09 => "R8G8",
+        0x0A => "R16",
+        0x0B => "R8G8B8A8",
+        0x0F => "R11G11B10",
+        0x14 => "R32",
+        0x1A => "BC1",
+        0x1B => "BC2",
+        0x1C => "BC3",
+        0x1D => "BC4",
+        0x1E => "BC5",
+        0x1F => "BC6H",
+        0x20 => "BC7",
+        0x2D => "ASTC_4x4",
+        0x2E => "ASTC_5x4",
+        0x2F => "ASTC_5x5",
+        0x30 => "ASTC_6x5",
+        0x31 => "ASTC_6x6",
+        0x32 => "ASTC_8x5",
+        0x33 => "ASTC_8x6",
+        0x34 => "ASTC_8x8",
+        0x35 => "ASTC_10x5",
+        0x36 => "ASTC_10x6",
+        0x37 => "ASTC_10x8",
+        0x38 => "ASTC_10x10",
+        0x39 => "ASTC_12x10",
+        0x3A => "ASTC_12x12",
+        _ => return format!("Unknown(0x{format:04x})"),
+    };
+    let component = match format & 0xFF {
+        0x01 => "UNORM",
+        0x02 => "SNORM",
+        0x03 => "UINT",
+        0x04 => "SINT",
+        0x05 => "FLOAT",
+        0x06 => "SRGB",
+        0x0A => "UFLOAT",
+        _ => return format!("{kind}_Unknown(0x{:02x})", format & 0xFF),
+    };
+    format!("{kind}_{component}")
+}
+
+/// Read-only view of a BNTX texture container's headers, leaving out the image data
+#[derive(Debug, Serialize)]
+pub struct Bntx {
+    pub version: u32,
+    pub name: Option<String>,
+    pub textures: Vec<Texture>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Texture {
+    pub name: String,
+    pub format: String,
+    /// 1, 2, or 3 dimensional
+    pub dimension: u8,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+    pub array_length: u32,
+    pub mip_count: u16,
+    /// Bytes of swizzled image data, across every mip and array layer
+    pub image_size: u32,
+    /// Where each mip level starts, as offsets into the file
+    #[serde(skip)]
+    mip_offsets: Vec<usize>,
+}
+
+impl Bntx {
+    pub fn from_binary(data: &[u8]) -> Result<Self> {
+        ensure!(data.starts_with(b"BNTX\0\0\0\0"), "Not a BNTX file");
+        ensure!(
+            data.get(0xC..0xE) == Some(&[0xFF, 0xFE][..]),
+            "Only little endian BNTX files are supported"
+        );
+        let reader = Reader(data);
+        ensure!(
+            data.get(0x20..0x24) == Some(&b"NX  "[..]),
+            "Bad BNTX container header"
+        );
+        let count = reader.u32(0x24)? as usize;
+        let infos = reader.ptr(0x28)?;
+        let textures = (0..count)
+            .map(|i| Texture::read(&reader, reader.ptr(infos + i * 8)?))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: reader.u32(0x8)?,
+            // The name offset points past the string's length
+            name: (reader.u32(0x10)? as usize)
+                .checked_sub(2)
+                .and_then(|ptr| reader.string(ptr).ok()),
+            textures,
+        })
+    }
+}
+
+impl Texture {
+    fn read(reader: &Reader, base: usize) -> Result<Self> {
+        ensure!(
+            reader.0.get(base..base + 4) == Some(&b"BRTI"[..]),
+            "Bad BNTX texture info"
+        );
+        let mip_count = reader.u16(base + 0x16)?;
+        let mips = reader.ptr(base + 0x70)?;
+        let mip_offsets = (0..mip_count as usize)
+            .map(|i| reader.ptr(mips + i * 8))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            name: reader.string(reader.ptr(base + 0x60)?)?,
+            format: format_name(reader.u32(base + 0x1C)?),
+            dimension: reader.u8(base + 0x11)?,
+            width: reader.u32(base + 0x24)?,
+            height: reader.u32(base + 0x28)?,
+            depth: reader.u32(base + 0x2C)?,
+            array_length: reader.u32(base + 0x30)?,
+            mip_count,
+            image_size: reader.u32(base + 0x50)?,
+            mip_offsets,
+        })
+    }
+
+    /// The still swizzled data of each mip level, covering every array layer.
+    pub fn mips<'a>(&self, data: &'a [u8]) -> Result<Vec<&'a [u8]>> {
+        let Some(&start) = self.mip_offsets.first() else {
+            return Ok(vec![]);
+        };
+        let end = start + self.image_size as usize;
+        self.mip_offsets
+            .iter()
+            .enumerate()
+            .map(|(i, &offset)| {
+                let next = self.mip_offsets.get(i + 1).copied().unwrap_or(end);
+                data.get(offset..next)
+                    .context("BNTX mip data out of bounds")
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,59 @@
+use parking_lot::Mutex;
+use std::{
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// How often a line of progress is logged while the progress bar is hidden
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often a progress event is written for front-ends, which redraw more often than logs
+const JSON_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Logs how many files are done every few seconds, in place of a progress bar where one
+/// can't be drawn, like when output goes to a log file.
+pub(crate) struct Ticker {
+    total: usize,
+    done: AtomicUsize,
+    last: Mutex<Instant>,
+    enabled: bool,
+    /// Write a line of JSON to stderr instead of logging, for `--progress-json`
+    json: bool,
+}
+
+impl Ticker {
+    pub fn new(total: usize, enabled: bool, json: bool) -> Self {
+        Self {
+            total,
+            done: AtomicUsize::new(0),
+            last: Mutex::new(Instant::now()),
+            enabled,
+            json,
+        }
+    }
+
+    /// Counts one more file done, logging the count if it's been long enough since the last.
+    /// As JSON, the last file always gets an event, so front-ends see the end.
+    pub fn tick(&self, current: &Path) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.enabled && !self.json {
+            return;
+        }
+        let mut last = self.last.lock();
+        if self.json {
+            if last.elapsed() >= JSON_INTERVAL || done == self.total {
+                *last = Instant::now();
+                let event = serde_json::json!({
+                    "done": done,
+                    "total": self.total,
+                    "current": crate::display_path(current),
+                });
+                eprintln!("{event}");
+            }
+        } else if last.elapsed() >= INTERVAL {
+            *last = Instant::now();
+            log::info!("{done}/{} files…", self.total);
+        }
+    }
+}
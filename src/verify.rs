@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+/// Files an existing dump writes by itself, beside the outputs of ROM files
+pub(crate) const DUMP_FILES: &[&str] = &[
+    "manifest.json",
+    "duplicates.json",
+    "_errors.json",
+    "SHA256SUMS",
+    "removed.txt",
+];
+
+/// How an existing dump differs from a fresh one, by path relative to the dump
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Outputs whose content isn't what the fresh dump has
+    pub mismatched: Vec<PathBuf>,
+    /// Outputs of the fresh dump the existing one doesn't have
+    pub missing: Vec<PathBuf>,
+    /// Files in the existing dump the fresh one didn't write
+    pub extra: Vec<PathBuf>,
+}
+
+impl Report {
+    /// Whether the dumps are the same.
+    pub fn is_match(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+
+    pub(crate) fn sort(&mut self) {
+        self.mismatched.sort();
+        self.missing.sort();
+        self.extra.sort();
+    }
+}
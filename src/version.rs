@@ -0,0 +1,72 @@
+use eyre::{bail, Result};
+use std::{fmt, fs, ops::RangeInclusive, path::Path, str::FromStr};
+
+/// The game versions the BYML, AAMP, and MSBT parsers in this release were written for:
+/// every TOTK release from 1.0.0 to 1.2.1, the latest update when it was made. It isn't
+/// backed by checking each version's files. Later versions may have changed the formats in
+/// ways the parsers don't notice.
+pub const SUPPORTED: RangeInclusive<GameVersion> = GameVersion(100)..=GameVersion(121);
+
+/// A TOTK version, numbered as in the ROM's file names, like `121` for 1.2.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GameVersion(pub u32);
+
+impl GameVersion {
+    /// Reads the version from the name of the resource size table, which every version of
+    /// the game has exactly one of, like `ResourceSizeTable.Product.121.rsizetable.zs`.
+    /// With updates overlaid, the newest is the one the dump is of.
+    pub fn detect<'a>(roots: impl IntoIterator<Item = &'a Path>) -> Option<Self> {
+        roots
+            .into_iter()
+            .filter_map(|root| fs::read_dir(root.join("System/Resource")).ok())
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                let version = name
+                    .to_str()?
+                    .strip_prefix("ResourceSizeTable.Product.")?
+                    .strip_suffix(".rsizetable.zs")?;
+                version.parse().ok().map(Self)
+            })
+            .max()
+    }
+
+    pub fn is_supported(self) -> bool {
+        SUPPORTED.contains(&self)
+    }
+}
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.0.to_string();
+        match digits.as_bytes() {
+            [major, minor, patch] => write!(
+                f,
+                "{}.{}.{}",
+                *major as char, *minor as char, *patch as char
+            ),
+            _ => f.write_str(&digits),
+        }
+    }
+}
+
+impl FromStr for GameVersion {
+    type Err = eyre::Report;
+
+    /// Parses a version either way it is written, like `1.2.1` or `121`.
+    fn from_str(s: &str) -> Result<Self> {
+        let digits = s.split('.').collect::<Vec<_>>();
+        let parsed = match digits.as_slice() {
+            [number] => number.parse().ok(),
+            [major, minor, patch] => [major, minor, patch]
+                .iter()
+                .map(|part| part.parse::<u32>().ok().filter(|&part| part < 10))
+                .try_fold(0, |version, part| Some(version * 10 + part?)),
+            _ => None,
+        };
+        match parsed {
+            Some(version) => Ok(Self(version)),
+            None => bail!("Bad game version {s}, expected one like 1.2.1 or 121"),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use eyre::{eyre, Result};
+use parking_lot::Mutex;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, SyncSender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// How many finished outputs each writer thread holds before parsing waits for it
+const QUEUE_LEN: usize = 64;
+
+type Job = Box<dyn FnOnce() -> Result<()> + Send>;
+
+/// Threads of their own for writing outputs, so parsing carries on while slow storage
+/// catches up. Each file always goes to the same thread, so its writes happen in order.
+pub(crate) struct WritePool {
+    senders: Vec<SyncSender<Job>>,
+    threads: Vec<JoinHandle<()>>,
+    error: Arc<Mutex<Option<eyre::Report>>>,
+}
+
+impl WritePool {
+    /// Starts the threads. The first write to fail sets `stop`, and its error is returned
+    /// by [`finish`](Self::finish).
+    pub fn new(threads: usize, stop: Arc<AtomicBool>) -> Result<Self> {
+        let error = Arc::new(Mutex::new(None));
+        let mut pool = Self {
+            senders: vec![],
+            threads: vec![],
+            error: error.clone(),
+        };
+        for i in 0..threads.max(1) {
+            let (sender, receiver) = sync_channel::<Job>(QUEUE_LEN);
+            let (error, stop) = (error.clone(), stop.clone());
+            let thread = thread::Builder::new()
+                .name(format!("writer-{i}"))
+                .spawn(move || {
+                    for job in receiver {
+                        if let Err(e) = job() {
+                            stop.store(true, Ordering::Relaxed);
+                            error.lock().get_or_insert(e);
+                        }
+                    }
+                })?;
+            pool.senders.push(sender);
+            pool.threads.push(thread);
+        }
+        Ok(pool)
+    }
+
+    /// Queues a job for an output, waiting if its thread is too far behind.
+    pub fn queue(&self, out: &Path, job: impl FnOnce() -> Result<()> + Send + 'static) {
+        let mut hasher = DefaultHasher::new();
+        out.hash(&mut hasher);
+        let sender = &self.senders[hasher.finish() as usize % self.senders.len()];
+        // The threads only stop once the senders are dropped, so this can't fail
+        let _ = sender.send(Box::new(job));
+    }
+
+    /// Waits for everything queued to be written.
+    pub fn finish(self) -> Result<()> {
+        drop(self.senders);
+        for thread in self.threads {
+            thread
+                .join()
+                .map_err(|_| eyre!("A writer thread panicked"))?;
+        }
+        match self.error.lock().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{dict_map, frame_size, Dictionary};
+use eyre::{ensure, Context, Result};
+use roead::sarc::Sarc;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, Read},
+    path::Path,
+};
+use zstd::{dict::DecoderDictionary, stream::read::Decoder};
+
+/// The largest window zstd allows. Some big files are compressed with windows past the
+/// default limit, which would otherwise fail to decompress however big the buffer.
+const WINDOW_LOG_MAX: u32 = 31;
+
+/// The dictionaries in a ZsDic pack, by file name, with what each is used for
+const DICTIONARIES: &[(&str, Dictionary, &str)] = &[
+    ("zs.zsdic", Dictionary::Common, "general"),
+    ("pack.zsdic", Dictionary::Pack, "pack"),
+    ("bcett.byml.zsdic", Dictionary::Map, "map"),
+];
+
+/// The zstd dictionaries from a ZsDic pack, prepared for decompression. Without any loaded,
+/// only files compressed without a dictionary can be decompressed.
+#[derive(Default)]
+pub struct ZsDic {
+    dictionaries: HashMap<Dictionary, DecoderDictionary<'static>>,
+    custom: Vec<(String, DecoderDictionary<'static>)>,
+}
+
+impl ZsDic {
+    /// Loads the dictionaries from a ZsDic pack, which may be compressed or not. Trimmed
+    /// packs missing some dictionaries still load, with a warning for each one missing.
+    pub fn from_pack(mut data: Vec<u8>) -> Result<Self> {
+        if !data.starts_with(b"SARC") {
+            data = Self::default()
+                .decompress("ZsDic.pack.zs", &data)
+                .context("Failed to decompress ZsDic pack")?;
+        }
+        let sarc = Sarc::new(data).context("Failed to open ZsDic pack")?;
+        let mut dictionaries = HashMap::new();
+        for &(file, kind, what) in DICTIONARIES {
+            match sarc.get_data(file) {
+                Some(dict) => {
+                    dictionaries.insert(kind, DecoderDictionary::copy(dict));
+                }
+                None => log::warn!("ZsDic pack is missing the {what} dictionary ({file})"),
+            }
+        }
+        Ok(Self {
+            dictionaries,
+            custom: vec![],
+        })
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = fs::read(path)
+            .with_context(|| format!("Failed to read ZsDic pack at {}", path.display()))?;
+        Self::from_pack(data).with_context(|| format!("Bad ZsDic pack at {}", path.display()))
+    }
+
+    /// Loads the dictionaries from a folder of loose files named as in a ZsDic pack, like
+    /// `zs.zsdic`. Any missing are warned about as for a trimmed pack, but not all of them.
+    pub fn open_dir(dir: &Path) -> Result<Self> {
+        let mut dictionaries = HashMap::new();
+        for &(file, kind, what) in DICTIONARIES {
+            let path = dir.join(file);
+            if !path.is_file() {
+                log::warn!(
+                    "{} is missing the {what} dictionary ({file})",
+                    dir.display()
+                );
+                continue;
+            }
+            let dict = fs::read(&path)
+                .with_context(|| format!("Failed to read dictionary {}", path.display()))?;
+            dictionaries.insert(kind, DecoderDictionary::copy(&dict));
+        }
+        ensure!(
+            !dictionaries.is_empty(),
+            "No dictionaries found in {}",
+            dir.display()
+        );
+        Ok(Self {
+            dictionaries,
+            custom: vec![],
+        })
+    }
+
+    /// Also loads the dictionaries in a dictionary map, used for the suffixes it lists.
+    pub fn with_dict_map(mut self, path: &Path) -> Result<Self> {
+        for (suffix, dict) in dict_map::load(path)? {
+            self.custom.push((suffix, DecoderDictionary::copy(&dict)));
+        }
+        Ok(self)
+    }
+
+    /// The name of the dictionary a file's name calls for, as a file in the ZsDic pack or a
+    /// suffix from the dictionary map, or None if it is compressed without one.
+    pub(crate) fn dictionary_name(&self, name: &str) -> Option<String> {
+        let kind = match Dictionary::for_name_with(name, &self.custom) {
+            Dictionary::Custom(i) => return Some(self.custom[i].0.clone()),
+            Dictionary::None => return None,
+            kind if self.dictionaries.contains_key(&kind) => kind,
+            _ => Dictionary::Common,
+        };
+        Some(
+            match kind {
+                Dictionary::Pack => "pack.zsdic",
+                Dictionary::Map => "bcett.byml.zsdic",
+                _ => "zs.zsdic",
+            }
+            .into(),
+        )
+    }
+
+    /// A streaming decoder using the dictionary a file's name calls for. It decodes every
+    /// frame in the input one after another, passing over skippable frames.
+    pub(crate) fn decoder<R: BufRead>(&self, reader: R, name: &str) -> Result<Decoder<'_, R>> {
+        let dictionary = match Dictionary::for_name_with(name, &self.custom) {
+            Dictionary::Custom(i) => Some(&self.custom[i].1),
+            Dictionary::None => None,
+            // Fall back to the general dictionary if a trimmed pack didn't have this one
+            kind => self
+                .dictionaries
+                .get(&kind)
+                .or_else(|| self.dictionaries.get(&Dictionary::Common)),
+        };
+        let mut decoder = match dictionary {
+            Some(dictionary) => Decoder::with_prepared_dictionary(reader, dictionary)?,
+            None => Decoder::with_buffer(reader)?,
+        };
+        decoder.window_log_max(WINDOW_LOG_MAX)?;
+        Ok(decoder)
+    }
+
+    /// Decompresses a file with the dictionary its name calls for.
+    pub fn decompress(&self, name: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = vec![];
+        self.decompress_into(name, data, &mut out)?;
+        Ok(out)
+    }
+
+    /// Decompresses a file into a buffer, replacing what it held, so the buffer's memory
+    /// can be reused.
+    pub(crate) fn decompress_into(&self, name: &str, data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        out.clear();
+        out.reserve(frame_size(data));
+        self.decoder(data, name)?
+            .read_to_end(out)
+            .context("Failed to decompress")?;
+        Ok(())
+    }
+}
+
+/// Decompresses a single file, given the bytes of a ZsDic pack to take dictionaries from.
+/// Load a [`ZsDic`] instead to decompress more than one.
+pub fn decompress_auto(zsdic: &[u8], name: &str, data: &[u8]) -> Result<Vec<u8>> {
+    ZsDic::from_pack(zsdic.to_vec())?.decompress(name, data)
+}
@@ -0,0 +1,92 @@
+//! Builds small synthetic ROMs in a temporary folder, so the unpacker can be run without
+//! any real game data.
+
+// Each test binary uses only some of these
+#![allow(dead_code)]
+
+use roead::{sarc::SarcWriter, Endian};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use zstd::bulk::Compressor;
+
+/// The dictionaries in the fixture's ZsDic pack, as raw content dictionaries
+pub const COMMON_DICT: &[u8] = b"common dictionary for synthetic fixtures";
+pub const PACK_DICT: &[u8] = b"pack dictionary for synthetic fixtures";
+pub const MAP_DICT: &[u8] = b"map dictionary for synthetic fixtures";
+
+/// A source romfs and output folder, removed again when dropped
+pub struct Fixture {
+    root: PathBuf,
+}
+
+impl Fixture {
+    pub fn new(name: &str) -> Self {
+        let root =
+            std::env::temp_dir().join(format!("totk-dump-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let fixture = Self { root };
+        fixture.write(
+            "Pack/ZsDic.pack.zs",
+            sarc(&[
+                ("zs.zsdic", COMMON_DICT.to_vec()),
+                ("pack.zsdic", PACK_DICT.to_vec()),
+                ("bcett.byml.zsdic", MAP_DICT.to_vec()),
+            ]),
+        );
+        fixture
+    }
+
+    pub fn romfs(&self) -> PathBuf {
+        self.root.join("romfs")
+    }
+
+    pub fn output(&self) -> PathBuf {
+        self.root.join("unpacked")
+    }
+
+    /// Writes a file into the romfs.
+    pub fn write(&self, relative: &str, data: impl AsRef<[u8]>) {
+        let path = self.romfs().join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+    }
+
+    /// Reads a file from the output folder.
+    pub fn read_output(&self, relative: impl AsRef<Path>) -> String {
+        let path = self.output().join(relative);
+        fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()))
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Builds a little endian SARC from named members.
+pub fn sarc(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut sarc = SarcWriter::new(Endian::Little);
+    for (name, data) in files {
+        sarc.add_file(*name, data.clone());
+    }
+    sarc.to_binary()
+}
+
+/// Compresses data with the dictionary the game uses for a file of this name.
+pub fn compress(name: &str, data: &[u8]) -> Vec<u8> {
+    let dict = if name.ends_with(".bcett.byml.zs") {
+        MAP_DICT
+    } else if name.ends_with(".pack.zs") {
+        PACK_DICT
+    } else {
+        COMMON_DICT
+    };
+    Compressor::with_dictionary(3, dict)
+        .unwrap()
+        .compress(data)
+        .unwrap()
+}
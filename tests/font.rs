@@ -0,0 +1,104 @@
+mod common;
+
+use common::{compress, sarc, Fixture};
+use totk_dump::{font::Bffnt, Unpacker};
+
+/// A block: its magic, then its size with the header as a little endian u32.
+fn block(magic: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut data = magic.to_vec();
+    data.extend((body.len() as u32 + 8).to_le_bytes());
+    data.extend(body);
+    data
+}
+
+/// A little endian font with two 16 byte sheets and two width blocks, of 10 and 5 glyphs.
+fn bffnt() -> Vec<u8> {
+    const INFO: u32 = 0x14;
+    const GLYPHS: u32 = INFO + 0x20;
+    const SHEETS: u32 = GLYPHS + 0x20;
+    const WIDTHS: u32 = SHEETS + 0x20;
+    const MORE_WIDTHS: u32 = WIDTHS + 0x10 + 30;
+    let mut info = vec![1, 24, 22, 20];
+    info.extend(26u16.to_le_bytes());
+    info.extend(5u16.to_le_bytes());
+    info.extend([0, 20, 22, 1]);
+    for ptr in [GLYPHS + 8, WIDTHS + 8, 0] {
+        info.extend(ptr.to_le_bytes());
+    }
+    let mut glyphs = vec![22, 24, 2, 22];
+    glyphs.extend(16u32.to_le_bytes());
+    for value in [20u16, 12, 4, 4, 128, 128] {
+        glyphs.extend(value.to_le_bytes());
+    }
+    glyphs.extend(SHEETS.to_le_bytes());
+    let widths = |start: u16, end: u16, next: u32| {
+        let mut body = start.to_le_bytes().to_vec();
+        body.extend(end.to_le_bytes());
+        body.extend(next.to_le_bytes());
+        body.extend(vec![0; (end - start + 1) as usize * 3]);
+        block(b"CWDH", &body)
+    };
+    let mut data = b"FFNT".to_vec();
+    data.extend([0xFF, 0xFE, 0x14, 0]);
+    data.extend(0x0401_0000u32.to_le_bytes());
+    data.extend(0u32.to_le_bytes());
+    data.extend(4u16.to_le_bytes());
+    data.extend([0, 0]);
+    data.extend(block(b"FINF", &info));
+    data.extend(block(b"TGLP", &glyphs));
+    data.extend([0xAB; 32]);
+    assert_eq!(data.len() as u32, WIDTHS);
+    data.extend(widths(0, 9, MORE_WIDTHS + 8));
+    assert_eq!(data.len() as u32, MORE_WIDTHS);
+    data.extend(widths(20, 24, 0));
+    data
+}
+
+#[test]
+fn reads_font_metrics() {
+    let font = Bffnt::from_binary(&bffnt()).unwrap();
+    assert_eq!(font.version, 0x0401_0000);
+    assert_eq!((font.height, font.width, font.ascent), (24, 22, 20));
+    assert_eq!(font.alternate_glyph, 5);
+    assert_eq!(font.glyph_count, 15);
+    assert_eq!((font.glyphs.cell_width, font.glyphs.cell_height), (22, 24));
+    let sheets = font
+        .glyphs
+        .sheets
+        .iter()
+        .map(|sheet| (sheet.offset, sheet.size))
+        .collect::<Vec<_>>();
+    assert_eq!(sheets, [(0x54, 16), (0x64, 16)]);
+}
+
+#[test]
+fn rejects_sheets_past_end() {
+    let mut data = bffnt();
+    // The sheet size, in the glyph block
+    data[0x40..0x44].copy_from_slice(&0x100u32.to_le_bytes());
+    assert!(Bffnt::from_binary(&data).is_err());
+}
+
+#[test]
+fn summarizes_fonts_in_font_archives() {
+    let fixture = Fixture::new("bfarc");
+    let bfarc = sarc(&[("font/Normal.bffnt", bffnt())]);
+    fixture.write("Font/Normal.bfarc.zs", compress("Normal.bfarc.zs", &bfarc));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    let summary = fixture.read_output("Font/Normal.bfarc.zs/font/Normal.bffnt.yml");
+    let summary = serde_yaml::from_str::<serde_yaml::Value>(&summary).unwrap();
+    assert_eq!(summary["encoding"].as_str(), Some("utf16"));
+    assert_eq!(summary["glyph_count"].as_u64(), Some(15));
+    assert_eq!(
+        summary["glyphs"]["sheets"][1]["offset"].as_u64(),
+        Some(0x64)
+    );
+    let raw = fixture
+        .output()
+        .join("Font/Normal.bfarc.zs/font/Normal.bffnt");
+    assert_eq!(std::fs::read(raw).unwrap(), bffnt());
+}
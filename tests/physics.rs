@@ -0,0 +1,78 @@
+use totk_dump::physics::Physics;
+
+/// A Havok section: its size with the header as a big endian u32, then its tag.
+fn section(tag: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut data = ((body.len() + 8) as u32 | 0x4000_0000)
+        .to_be_bytes()
+        .to_vec();
+    data.extend(tag);
+    data.extend(body);
+    data
+}
+
+fn tagfile() -> Vec<u8> {
+    let types = section(b"TST1", b"hkRootLevelContainer\0hknpConvexPolytopeShape\0");
+    let body = [
+        section(b"SDKV", b"20220100"),
+        section(b"DATA", &[0; 8]),
+        section(b"TYPE", &types),
+    ]
+    .concat();
+    section(b"TAG0", &body)
+}
+
+#[test]
+fn reads_bare_tagfile() {
+    let physics = Physics::from_binary(&tagfile()).unwrap();
+    assert!(physics.version.is_none());
+    assert_eq!(physics.havok.sdk_version.as_deref(), Some("20220100"));
+    assert_eq!(
+        physics.havok.types,
+        ["hkRootLevelContainer", "hknpConvexPolytopeShape"]
+    );
+    let tags = physics.havok.sections[0]
+        .children
+        .iter()
+        .map(|s| s.tag.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(tags, ["SDKV", "DATA", "TYPE"]);
+}
+
+#[test]
+fn reads_phive_container() {
+    let havok = tagfile();
+    let material = [7u32.to_le_bytes(), 0u32.to_le_bytes()].concat();
+    let material = [material, 0x30u64.to_le_bytes().to_vec()].concat();
+    let tagfile_offset = 0x28u32;
+    let material_offset = tagfile_offset + havok.len() as u32;
+    let interaction_offset = material_offset + material.len() as u32;
+    let mut data = b"Phive\0".to_vec();
+    data.extend([0xFF, 0xFE, 1, 0, 0, 0]);
+    for value in [
+        tagfile_offset,
+        material_offset,
+        interaction_offset,
+        interaction_offset,
+        havok.len() as u32,
+        material.len() as u32,
+        0,
+    ] {
+        data.extend(value.to_le_bytes());
+    }
+    assert_eq!(data.len(), 0x28);
+    data.extend(&havok);
+    data.extend(&material);
+    let physics = Physics::from_binary(&data).unwrap();
+    assert_eq!(physics.version, Some(1));
+    assert_eq!(physics.materials.len(), 1);
+    assert_eq!(physics.materials[0].index, 7);
+    assert_eq!(physics.materials[0].flags, 0x30);
+    assert_eq!(physics.havok.sdk_version.as_deref(), Some("20220100"));
+}
+
+#[test]
+fn rejects_truncated_section() {
+    let mut data = tagfile();
+    data.truncate(data.len() - 4);
+    assert!(Physics::from_binary(&data).is_err());
+}
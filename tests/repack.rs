@@ -0,0 +1,69 @@
+mod common;
+
+use common::{compress, Fixture, COMMON_DICT};
+use roead::{byml::Byml, Endian};
+use std::fs;
+use totk_dump::{Options, Repacker, Unpacker};
+use zstd::bulk::Decompressor;
+
+/// Unpacks a loose BYML, then repacks it at `level`, returning the rebuilt file's bytes.
+fn round_trip(name: &str, level: Option<i32>) -> Vec<u8> {
+    let fixture = Fixture::new(name);
+    let byml = Byml::Array(vec![Byml::String("Repacked".into()); 64]);
+    fixture.write(
+        "Data/Loose.byml.zs",
+        compress("Loose.byml.zs", &byml.to_binary(Endian::Little)),
+    );
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let repacked = fixture.output().with_file_name("repacked");
+    let mut repacker = Repacker::new(fixture.output(), repacked.clone()).unwrap();
+    if let Some(level) = level {
+        repacker = repacker.with_compression_level(level).unwrap();
+    }
+    repacker.repack().unwrap();
+    fs::read(repacked.join("Data/Loose.byml.zs")).unwrap()
+}
+
+#[test]
+fn compresses_at_chosen_level() {
+    let default = round_trip("level-default", None);
+    let fast = round_trip("level-fast", Some(1));
+    for data in [&default, &fast] {
+        // Still compressed with the dictionary its name calls for
+        let decompressed = Decompressor::with_dictionary(COMMON_DICT)
+            .unwrap()
+            .decompress(data, 1 << 20)
+            .unwrap();
+        assert!(decompressed.starts_with(b"YB"));
+    }
+}
+
+#[test]
+fn repacks_as_sidecar_meta_says() {
+    let fixture = Fixture::new("repack-meta");
+    let byml = Byml::Array(vec![Byml::String("Big".into())]);
+    let mut big = byml.to_binary(Endian::Big);
+    big[2..4].copy_from_slice(&3u16.to_be_bytes());
+    // Stored uncompressed, though its name would have it compressed otherwise
+    fixture.write("Data/Big.byml", &big);
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            sidecar_meta: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let repacked = fixture.output().with_file_name("repacked-meta");
+    Repacker::new(fixture.output(), repacked.clone())
+        .unwrap()
+        .repack()
+        .unwrap();
+    let data = fs::read(repacked.join("Data/Big.byml")).unwrap();
+    assert!(!repacked.join("Data/Big.byml.zs").exists());
+    assert_eq!(&data[..4], b"BY\0\x03");
+    assert_eq!(Byml::from_binary(&data).unwrap(), byml);
+}
@@ -0,0 +1,76 @@
+use std::{fs, path::PathBuf};
+use totk_dump::{find_romfs, find_zsdic};
+
+/// A folder tree of empty folders under a temporary root, removed again when dropped
+struct Tree(PathBuf);
+
+impl Tree {
+    fn new(name: &str, folders: &[&str]) -> Self {
+        let root =
+            std::env::temp_dir().join(format!("totk-dump-romfs-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for folder in folders {
+            fs::create_dir_all(root.join(folder)).unwrap();
+        }
+        Self(root.canonicalize().unwrap())
+    }
+}
+
+impl Drop for Tree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn finds_exact() {
+    let tree = Tree::new("exact", &["dump/romfs"]);
+    let romfs = tree.0.join("dump/romfs");
+    assert_eq!(find_romfs(&romfs).unwrap(), romfs);
+}
+
+#[test]
+fn finds_child() {
+    let tree = Tree::new("child", &["dump/romfs"]);
+    assert_eq!(
+        find_romfs(&tree.0.join("dump")).unwrap(),
+        tree.0.join("dump/romfs")
+    );
+}
+
+#[test]
+fn finds_sibling() {
+    let tree = Tree::new("sibling", &["dump/exefs", "dump/romfs"]);
+    assert_eq!(
+        find_romfs(&tree.0.join("dump/exefs")).unwrap(),
+        tree.0.join("dump/romfs")
+    );
+}
+
+#[test]
+fn finds_parent_sibling() {
+    let tree = Tree::new("parent-sibling", &["dump/exefs/main", "dump/romfs"]);
+    assert_eq!(
+        find_romfs(&tree.0.join("dump/exefs/main")).unwrap(),
+        tree.0.join("dump/romfs")
+    );
+}
+
+#[test]
+fn fails_without_romfs() {
+    let tree = Tree::new("none", &["dump/exefs"]);
+    assert!(find_romfs(&tree.0.join("dump/exefs")).is_err());
+    assert!(find_romfs(&tree.0.join("missing")).is_err());
+}
+
+#[test]
+fn finds_zsdic_above_archive() {
+    let tree = Tree::new("zsdic", &["romfs/Pack/Actor", "loose"]);
+    let zsdic = tree.0.join("romfs/Pack/ZsDic.pack.zs");
+    fs::write(&zsdic, b"").unwrap();
+    assert_eq!(
+        find_zsdic(&tree.0.join("romfs/Pack/Actor/Foo.pack.zs")),
+        Some(zsdic)
+    );
+    assert_eq!(find_zsdic(&tree.0.join("loose/Foo.pack.zs")), None);
+}
@@ -0,0 +1,1704 @@
+mod common;
+
+use common::{compress, sarc, Fixture};
+use roead::{aamp::ParameterIO, byml::Byml, Endian};
+use std::{
+    path::Path,
+    sync::{atomic::AtomicBool, Arc},
+};
+use totk_dump::{format::Format, Options, Unpacker};
+
+fn byml() -> Byml {
+    Byml::Array(vec![
+        Byml::I32(1),
+        Byml::Float(2.5),
+        Byml::String("Three".into()),
+    ])
+}
+
+fn unpack(fixture: &Fixture) -> Unpacker {
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    unpacker
+}
+
+#[test]
+fn converts_loose_byml() {
+    let fixture = Fixture::new("loose-byml");
+    fixture.write(
+        "Data/Loose.byml.zs",
+        compress("Loose.byml.zs", &byml().to_binary(Endian::Little)),
+    );
+    unpack(&fixture);
+    let text = fixture.read_output("Data/Loose.byml.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+}
+
+#[test]
+fn converts_archive_members() {
+    let fixture = Fixture::new("archive-members");
+    let pack = sarc(&[
+        ("Data/Member.bgyml", byml().to_binary(Endian::Little)),
+        ("Param/Member.bxml", ParameterIO::new().to_binary()),
+        (
+            "Data/Compressed.byml.zs",
+            compress("Compressed.byml.zs", &byml().to_binary(Endian::Little)),
+        ),
+    ]);
+    fixture.write("Pack/Test.pack.zs", compress("Test.pack.zs", &pack));
+    unpack(&fixture);
+    for member in ["Data/Member.bgyml.yml", "Data/Compressed.byml.yml"] {
+        let text = fixture.read_output(format!("Pack/Test.pack.zs/{member}"));
+        assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+    }
+    let text = fixture.read_output("Pack/Test.pack.zs/Param/Member.bxml.yml");
+    assert_eq!(
+        serde_yaml::from_str::<ParameterIO>(&text).unwrap(),
+        ParameterIO::new()
+    );
+}
+
+#[test]
+fn writes_manifest() {
+    let fixture = Fixture::new("manifest");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    unpack(&fixture);
+    let manifest: Vec<totk_dump::manifest::Entry> =
+        serde_json::from_str(&fixture.read_output("manifest.json")).unwrap();
+    let entry = manifest
+        .iter()
+        .find(|e| e.path.ends_with("Loose.byml"))
+        .expect("Loose.byml is in the manifest");
+    assert!(entry.converted);
+    assert_eq!(entry.format, totk_dump::format::Format::Byml);
+}
+
+#[test]
+fn converts_aamp_named_as_byml() {
+    let fixture = Fixture::new("aamp-bgyml");
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .init_dicts()
+        .unwrap();
+    let data = compress("Config.bgyml.zs", &ParameterIO::new().to_binary());
+    unpacker
+        .write_byml(data, Path::new("Data/Config.bgyml.zs"))
+        .unwrap();
+    let text = fixture.read_output("Data/Config.bgyml.yml");
+    assert_eq!(
+        serde_yaml::from_str::<ParameterIO>(&text).unwrap(),
+        ParameterIO::new()
+    );
+}
+
+#[test]
+fn continues_past_corrupt_archive() {
+    let fixture = Fixture::new("corrupt-sarc");
+    fixture.write("Pack/Bad.pack", b"SARC but not really an archive");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            continue_on_sarc_error: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 1);
+    assert_eq!(unpacker.errors()[0].path, Path::new("Pack/Bad.pack"));
+    fixture.read_output("Data/Loose.byml.yml");
+}
+
+#[test]
+fn unpacks_nested_archives() {
+    let fixture = Fixture::new("nested-sarc");
+    let inner = sarc(&[("Data/Inner.bgyml", byml().to_binary(Endian::Little))]);
+    let pack = sarc(&[
+        ("Inner.sarc.zs", compress("Inner.sarc.zs", &inner)),
+        ("Plain.sarc", inner.clone()),
+        // Only looks like an archive, so it is kept as it is
+        ("Fake.sarc", b"SARC but not really an archive".to_vec()),
+    ]);
+    fixture.write("Pack/Outer.pack.zs", compress("Outer.pack.zs", &pack));
+    unpack(&fixture);
+    for archive in ["Inner.sarc.zs", "Plain.sarc"] {
+        let text =
+            fixture.read_output(format!("Pack/Outer.pack.zs/{archive}/Data/Inner.bgyml.yml"));
+        assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+    }
+    assert_eq!(
+        fixture.read_output("Pack/Outer.pack.zs/Fake.sarc"),
+        "SARC but not really an archive"
+    );
+}
+
+#[test]
+fn splits_large_documents() {
+    let fixture = Fixture::new("split");
+    let array = (0..100)
+        .map(|i| Byml::String(format!("Entry{i}").into()))
+        .collect::<Vec<_>>();
+    fixture.write(
+        "Map/Large.byml",
+        Byml::Array(array.clone()).to_binary(Endian::Little),
+    );
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            split_large_yaml: Some(500),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let index: serde_json::Value =
+        serde_json::from_str(&fixture.read_output("Map/Large.byml.parts.json")).unwrap();
+    assert_eq!(index["kind"], "array");
+    let parts = index["parts"].as_array().unwrap();
+    assert!(parts.len() > 1);
+    let mut joined = vec![];
+    for part in parts {
+        let text = fixture.read_output(format!("Map/{}", part.as_str().unwrap()));
+        assert!(text.len() <= 1000, "part is still {} bytes", text.len());
+        match serde_yaml::from_str::<Byml>(&text).unwrap() {
+            Byml::Array(items) => joined.extend(items),
+            other => panic!("Part is not an array: {other:?}"),
+        }
+    }
+    assert_eq!(joined, array);
+}
+
+#[cfg(unix)]
+#[test]
+fn follows_symlinks_without_looping() {
+    let fixture = Fixture::new("symlinks");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let extra = fixture.output().with_file_name("extra");
+    std::fs::create_dir_all(&extra).unwrap();
+    std::fs::write(extra.join("Linked.byml"), byml().to_binary(Endian::Little)).unwrap();
+    std::os::unix::fs::symlink(&extra, fixture.romfs().join("Extra")).unwrap();
+    std::os::unix::fs::symlink(fixture.romfs(), fixture.romfs().join("Data/Loop")).unwrap();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            follow_symlinks: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    fixture.read_output("Data/Loose.byml.yml");
+    fixture.read_output("Extra/Linked.byml.yml");
+}
+
+#[test]
+fn reports_unhandled_files() {
+    let fixture = Fixture::new("unhandled");
+    let pack = sarc(&[
+        ("Model/A.bfres", b"FRES model one".to_vec()),
+        ("Model/B.bfres", b"FRES model two".to_vec()),
+    ]);
+    fixture.write("Pack/Models.pack", pack);
+    fixture.write("Data/Loose.xyz", [0u8, 1, 2, 3, 4]);
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            report_unhandled: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let unhandled = unpacker.unhandled();
+    assert_eq!(unhandled[0], ("bfres".into(), "FRES".into(), 2));
+    assert!(unhandled.contains(&("xyz".into(), "00010203".into(), 1)));
+}
+
+#[test]
+fn routes_formats_to_folders() {
+    let fixture = Fixture::new("routes");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    fixture.write("Param/Loose.bxml", ParameterIO::new().to_binary());
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            routes: [(Format::Aamp, "params".into())].into_iter().collect(),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    fixture.read_output("params/Param/Loose.bxml.yml");
+    fixture.read_output("Data/Loose.byml.yml");
+}
+
+#[test]
+fn writes_malformed_aamp_raw() {
+    let fixture = Fixture::new("bad-aamp");
+    // The magic and version of an AAMP file, so it is parsed as one, but nothing after them
+    let mut truncated = ParameterIO::new().to_binary();
+    truncated.truncate(8);
+    let pack = sarc(&[("Param/Bad.bxml", truncated.clone())]);
+    fixture.write("Pack/Params.pack", pack);
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 1);
+    assert_eq!(
+        std::fs::read(fixture.output().join("Pack/Params.pack/Param/Bad.bxml")).unwrap(),
+        truncated
+    );
+    fixture.read_output("Data/Loose.byml.yml");
+}
+
+#[test]
+fn only_text_skips_other_formats() {
+    let fixture = Fixture::new("only-text");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let pack = sarc(&[("Param/Member.bxml", ParameterIO::new().to_binary())]);
+    fixture.write("Pack/Params.pack", pack);
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            only_text: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert!(!fixture.output().join("Data").exists());
+    assert!(!fixture.output().join("Pack/Params.pack/Param").exists());
+    assert!(unpacker.results().is_empty());
+}
+
+#[test]
+fn stops_when_asked() {
+    let fixture = Fixture::new("stop");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_stop(Arc::new(AtomicBool::new(true)))
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert!(unpacker.is_stopped());
+    assert!(unpacker.results().is_empty());
+    // The manifest is still written, covering nothing
+    assert_eq!(fixture.read_output("manifest.json").trim(), "[]");
+}
+
+#[test]
+fn skips_other_languages() {
+    let fixture = Fixture::new("languages");
+    for lang in ["USen", "EUen", "JPja", "EUde"] {
+        let pack = sarc(&[("Data/Member.bgyml", byml().to_binary(Endian::Little))]);
+        fixture.write(&format!("Mals/{lang}.Product.100.sarc"), pack);
+    }
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            languages: Some("en,JPja".parse().unwrap()),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    for lang in ["USen", "EUen", "JPja"] {
+        fixture.read_output(format!(
+            "Mals/{lang}.Product.100.sarc/Data/Member.bgyml.yml"
+        ));
+    }
+    assert!(!fixture.output().join("Mals/EUde.Product.100.sarc").exists());
+    fixture.read_output("Data/Loose.byml.yml");
+    assert!("xx".parse::<totk_dump::language::Languages>().is_err());
+}
+
+#[test]
+fn verifies_roundtrip() {
+    let fixture = Fixture::new("roundtrip");
+    fixture.write("Data/Exact.byml", byml().to_binary(Endian::Little));
+    // Trailing padding is dropped when parsing, so this can't come back the same
+    let mut padded = byml().to_binary(Endian::Little);
+    padded.extend([0; 16]);
+    fixture.write("Data/Padded.byml", padded);
+    fixture.write("Param/Exact.bxml", ParameterIO::new().to_binary());
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            verify_roundtrip: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    assert_eq!(
+        unpacker
+            .stats()
+            .roundtrip_mismatches
+            .load(std::sync::atomic::Ordering::Relaxed),
+        1
+    );
+    fixture.read_output("Data/Padded.byml.yml");
+}
+
+#[test]
+fn converts_resource_databases() {
+    let fixture = Fixture::new("rsdb");
+    let table = Byml::Array(vec![Byml::Map(
+        [("Name".into(), Byml::String("Tag".into()))]
+            .into_iter()
+            .collect(),
+    )]);
+    fixture.write(
+        "RSDB/Tag.Product.rsdb.zs",
+        compress("Tag.Product.rsdb.zs", &table.to_binary(Endian::Little)),
+    );
+    unpack(&fixture);
+    let text = fixture.read_output("RSDB/Tag.Product.rsdb.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), table);
+}
+
+#[test]
+fn stops_over_output_limit() {
+    let fixture = Fixture::new("output-limit");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            max_output_size: Some(1),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert!(unpacker.is_over_output_limit());
+    assert!(unpacker.is_stopped());
+    // What was written before stopping is still in the manifest
+    let manifest: Vec<totk_dump::manifest::Entry> =
+        serde_json::from_str(&fixture.read_output("manifest.json")).unwrap();
+    assert!(!manifest.is_empty());
+}
+
+#[test]
+fn records_member_offsets() {
+    let fixture = Fixture::new("offsets");
+    let member = byml().to_binary(Endian::Little);
+    let pack = sarc(&[("Data/Member.bgyml", member.clone())]);
+    fixture.write("Pack/Test.pack", &pack);
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            manifest_offsets: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let manifest: Vec<totk_dump::manifest::Entry> =
+        serde_json::from_str(&fixture.read_output("manifest.json")).unwrap();
+    let entry = manifest
+        .iter()
+        .find(|e| e.path.ends_with("Member.bgyml"))
+        .expect("the member is in the manifest");
+    assert_eq!(entry.archive.as_deref(), Some(Path::new("Pack/Test.pack")));
+    let offset = entry.offset.unwrap() as usize;
+    assert_eq!(entry.size, Some(member.len() as u64));
+    assert_eq!(&pack[offset..offset + member.len()], &member[..]);
+}
+
+#[test]
+fn times_each_stage() {
+    let fixture = Fixture::new("timings");
+    fixture.write(
+        "Data/Loose.byml.zs",
+        compress("Loose.byml.zs", &byml().to_binary(Endian::Little)),
+    );
+    let unpacker = unpack(&fixture);
+    let timings = &unpacker.stats().timings;
+    for stage in [&timings.decompress, &timings.parse, &timings.serialize] {
+        assert!(stage.load(std::sync::atomic::Ordering::Relaxed) > 0);
+    }
+}
+
+#[test]
+fn bundles_archives() {
+    let fixture = Fixture::new("bundle");
+    let pack = sarc(&[
+        ("Data/Member.bgyml", byml().to_binary(Endian::Little)),
+        ("Param/Member.bxml", ParameterIO::new().to_binary()),
+        ("Data/Unknown.bin", vec![0, 1, 2, 3]),
+    ]);
+    fixture.write("Pack/Test.pack.zs", compress("Test.pack.zs", &pack));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            bundle_sarc: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert!(!fixture.output().join("Pack/Test.pack.zs").exists());
+    let bundle: serde_yaml::Value =
+        serde_yaml::from_str(&fixture.read_output("Pack/Test.pack.yml")).unwrap();
+    assert_eq!(
+        serde_yaml::from_value::<Byml>(bundle["Data/Member.bgyml"].clone()).unwrap(),
+        byml()
+    );
+    assert!(bundle.get("Param/Member.bxml").is_some());
+    let serde_yaml::Value::Tagged(raw) = &bundle["Data/Unknown.bin"] else {
+        panic!("Unknown member is not tagged");
+    };
+    assert_eq!(raw.tag, "binary");
+    assert_eq!(raw.value, "AAECAw==");
+}
+
+#[test]
+fn converts_big_endian_byml() {
+    let fixture = Fixture::new("big-endian");
+    fixture.write("Data/Big.byml", byml().to_binary(Endian::Big));
+    // A version roead doesn't read, so the header has to be patched in the right order
+    let mut newer = byml().to_binary(Endian::Big);
+    newer[2..4].copy_from_slice(&10u16.to_be_bytes());
+    fixture.write("Data/Newer.byml", newer);
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            verify_roundtrip: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    assert_eq!(
+        unpacker
+            .stats()
+            .roundtrip_mismatches
+            .load(std::sync::atomic::Ordering::Relaxed),
+        0
+    );
+    for name in ["Big", "Newer"] {
+        let text = fixture.read_output(format!("Data/{name}.byml.yml"));
+        assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+    }
+    let manifest: Vec<totk_dump::manifest::Entry> =
+        serde_json::from_str(&fixture.read_output("manifest.json")).unwrap();
+    let newer = manifest
+        .iter()
+        .find(|e| e.path.ends_with("Newer.byml"))
+        .unwrap();
+    assert_eq!(newer.endian, Some(totk_dump::format::Endian::Big));
+    assert_eq!(newer.version, Some(10));
+}
+
+#[test]
+fn keeps_one_actors_rows() {
+    let fixture = Fixture::new("actor");
+    let row = |id: &str| {
+        Byml::Map(
+            [("__RowId".into(), Byml::String(id.into()))]
+                .into_iter()
+                .collect(),
+        )
+    };
+    let table = Byml::Array(vec![row("Enemy_Bokoblin"), row("Enemy_Moblin")]);
+    fixture.write(
+        "RSDB/ActorInfo.Product.rstbl.byml",
+        table.to_binary(Endian::Little),
+    );
+    for actor in ["Enemy_Bokoblin", "Enemy_Moblin"] {
+        let pack = sarc(&[("Data/Member.bgyml", byml().to_binary(Endian::Little))]);
+        fixture.write(
+            &format!("Pack/Actor/{actor}.pack.zs"),
+            compress("Actor.pack.zs", &pack),
+        );
+    }
+    let filter =
+        totk_dump::filter::Filter::new(&totk_dump::actor::globs("Enemy_Bokoblin"), &[]).unwrap();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            actor: Some("Enemy_Bokoblin".into()),
+            filter,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let text = fixture.read_output("RSDB/ActorInfo.Product.rstbl.byml.yml");
+    assert_eq!(
+        serde_yaml::from_str::<Byml>(&text).unwrap(),
+        Byml::Array(vec![row("Enemy_Bokoblin")])
+    );
+    fixture.read_output("Pack/Actor/Enemy_Bokoblin.pack.zs/Data/Member.bgyml.yml");
+    assert!(!fixture
+        .output()
+        .join("Pack/Actor/Enemy_Moblin.pack.zs")
+        .exists());
+}
+
+#[test]
+fn maps_source_files() {
+    let fixture = Fixture::new("mmap");
+    fixture.write(
+        "Data/Loose.byml.zs",
+        compress("Loose.byml.zs", &byml().to_binary(Endian::Little)),
+    );
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            mmap: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    let text = fixture.read_output("Data/Loose.byml.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+}
+
+#[test]
+fn leaves_identical_outputs_untouched() {
+    let fixture = Fixture::new("identical");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let options = Options {
+        skip_identical: true,
+        ..Default::default()
+    };
+    let run = || {
+        Unpacker::new(fixture.romfs(), fixture.output())
+            .with_options(options.clone())
+            .init_dicts()
+            .unwrap()
+            .unpack()
+            .unwrap()
+    };
+    run();
+    let out = fixture.output().join("Data/Loose.byml.yml");
+    let old = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_mtime(&out, old).unwrap();
+    run();
+    let modified =
+        filetime::FileTime::from_last_modification_time(&std::fs::metadata(&out).unwrap());
+    assert_eq!(modified, old);
+}
+
+#[test]
+fn collapses_members_to_a_single_root() {
+    let fixture = Fixture::new("single-root");
+    for (pack, value) in [("A", 1), ("B", 2)] {
+        let member = Byml::Array(vec![Byml::I32(value)]).to_binary(Endian::Little);
+        fixture.write(
+            &format!("Pack/{pack}.pack"),
+            sarc(&[("Data/Foo.byml", member)]),
+        );
+    }
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            sarc_layout: totk_dump::SarcLayout::SingleRoot,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    let text = fixture.read_output("Data/Foo.byml.yml");
+    let Byml::Array(items) = serde_yaml::from_str::<Byml>(&text).unwrap() else {
+        panic!("Member is not an array");
+    };
+    assert!(matches!(items[..], [Byml::I32(1 | 2)]));
+    assert!(!fixture.output().join("Pack/A.pack").exists());
+}
+
+#[test]
+fn annotates_map_actors() {
+    let fixture = Fixture::new("expand-map");
+    let map = |pairs: Vec<(&str, Byml)>| {
+        Byml::Map(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        )
+    };
+    let info = Byml::Array(vec![map(vec![
+        ("__RowId", Byml::String("Enemy_Bokoblin".into())),
+        ("Category", Byml::String("Enemy".into())),
+        ("Life", Byml::I32(13)),
+    ])]);
+    fixture.write(
+        "RSDB/ActorInfo.Product.rstbl.byml",
+        info.to_binary(Endian::Little),
+    );
+    let actors = map(vec![(
+        "Actors",
+        Byml::Array(vec![
+            map(vec![
+                ("Gyaml", Byml::String("Enemy_Bokoblin".into())),
+                ("Hash", Byml::U64(1)),
+                (
+                    "Links",
+                    Byml::Array(vec![map(vec![
+                        ("Dst", Byml::U64(2)),
+                        ("Name", Byml::String("Basic".into())),
+                        ("Src", Byml::U64(1)),
+                    ])]),
+                ),
+            ]),
+            map(vec![
+                ("Gyaml", Byml::String("Obj_TreasureBox".into())),
+                ("Hash", Byml::U64(2)),
+            ]),
+        ]),
+    )]);
+    fixture.write(
+        "Banc/MainField/A-1_Static.bcett.byml",
+        actors.to_binary(Endian::Little),
+    );
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            expand_map: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    let text = fixture.read_output("Banc/MainField/A-1_Static.bcett.byml.yml");
+    let Byml::Map(root) = serde_yaml::from_str::<Byml>(&text).unwrap() else {
+        panic!("Map isn't a map");
+    };
+    let actors = root["Actors"].as_array().unwrap();
+    let bokoblin = actors[0].as_map().unwrap();
+    assert_eq!(
+        bokoblin["ActorInfo"],
+        map(vec![("Category", Byml::String("Enemy".into()))])
+    );
+    let link = bokoblin["Links"].as_array().unwrap()[0].as_map().unwrap();
+    assert_eq!(link["DstActor"], Byml::String("Obj_TreasureBox".into()));
+    assert!(!actors[1].as_map().unwrap().contains_key("ActorInfo"));
+}
+
+#[test]
+fn writes_on_io_threads() {
+    let fixture = Fixture::new("io-threads");
+    for i in 0..8 {
+        fixture.write(
+            &format!("Data/Loose{i}.byml"),
+            byml().to_binary(Endian::Little),
+        );
+    }
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            io_threads: Some(2),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    for i in 0..8 {
+        let text = fixture.read_output(format!("Data/Loose{i}.byml.yml"));
+        assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+    }
+    fixture.read_output("manifest.json");
+}
+
+#[test]
+fn summarizes_output_sizes() {
+    let fixture = Fixture::new("sizes");
+    fixture.write(
+        "Data/Small.byml",
+        Byml::Array(vec![]).to_binary(Endian::Little),
+    );
+    fixture.write("Data/Large.byml", byml().to_binary(Endian::Little));
+    // Loose files that aren't converted aren't written, but archive members are
+    fixture.write(
+        "Pack/Other.pack",
+        sarc(&[("Data/Other.bin", b"raw".to_vec())]),
+    );
+    let unpacker = unpack(&fixture);
+    let summary = unpacker.summary(std::time::Duration::ZERO);
+    let byml = &summary.by_format[&Format::Byml];
+    assert_eq!(byml.count, 2);
+    assert!(byml.min < byml.max);
+    assert_eq!(byml.mean, byml.total as f64 / 2.0);
+    assert_eq!(summary.by_extension["byml"].count, 2);
+    assert_eq!(summary.by_extension["bin"].total, 3);
+}
+
+#[test]
+fn loads_loose_dictionaries() {
+    let fixture = Fixture::new("zsdic-dir");
+    fixture.write(
+        "Pack/Packed.pack.zs",
+        compress(
+            "Packed.pack.zs",
+            &sarc(&[("Member.bin", b"member".to_vec())]),
+        ),
+    );
+    std::fs::remove_file(fixture.romfs().join("Pack/ZsDic.pack.zs")).unwrap();
+    let dicts = fixture.output().with_file_name("dicts");
+    std::fs::create_dir_all(&dicts).unwrap();
+    for (name, dict) in [
+        ("zs.zsdic", common::COMMON_DICT),
+        ("pack.zsdic", common::PACK_DICT),
+        ("bcett.byml.zsdic", common::MAP_DICT),
+    ] {
+        std::fs::write(dicts.join(name), dict).unwrap();
+    }
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            zsdic_dir: Some(dicts),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    assert_eq!(
+        fixture.read_output("Pack/Packed.pack.zs/Member.bin"),
+        "member"
+    );
+}
+
+#[test]
+fn flattens_deep_folders() {
+    let fixture = Fixture::new("flatten");
+    fixture.write("A/B/C/D/Deep.byml", byml().to_binary(Endian::Little));
+    fixture.write("A/Shallow.byml", byml().to_binary(Endian::Little));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            flatten_depth: Some(2),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    fixture.read_output("A/B/C__D__Deep.byml.yml");
+    fixture.read_output("A/Shallow.byml.yml");
+}
+
+#[test]
+fn opens_layout_archives() {
+    let fixture = Fixture::new("blarc");
+    let blarc = sarc(&[("blyt/Menu.bgyml", byml().to_binary(Endian::Little))]);
+    fixture.write(
+        "UI/LayoutArchive/Menu.blarc.zs",
+        compress("Menu.blarc.zs", &blarc),
+    );
+    unpack(&fixture);
+    let text = fixture.read_output("UI/LayoutArchive/Menu.blarc.zs/blyt/Menu.bgyml.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+}
+
+#[test]
+fn writes_gitattributes() {
+    let fixture = Fixture::new("gitattributes");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    // Raw files are only written from archives
+    fixture.write(
+        "Pack/Models.pack",
+        sarc(&[("Model/Thing.bfres", b"FRES".to_vec())]),
+    );
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            gitattributes: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let attributes = fixture.read_output(".gitattributes");
+    let lines = attributes.lines().skip(1).collect::<Vec<_>>();
+    assert!(lines.contains(&"*.yml text eol=lf"));
+    assert!(lines.contains(&"*.json text eol=lf"));
+    assert!(lines.contains(&"*.bfres binary"));
+}
+
+#[test]
+fn parses_only_listed_formats() {
+    let fixture = Fixture::new("parse-only");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let params = ParameterIO::new().to_binary();
+    fixture.write("Param/Loose.bxml", &params);
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            parse_only: Some("aamp".parse().unwrap()),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    fixture.read_output("Param/Loose.bxml.yml");
+    let raw = std::fs::read(fixture.output().join("Data/Loose.byml")).unwrap();
+    assert_eq!(raw, byml().to_binary(Endian::Little));
+    assert!(!fixture.output().join("Data/Loose.byml.yml").exists());
+}
+
+#[test]
+fn decompresses_data_bigger_compressed() {
+    let fixture = Fixture::new("incompressible");
+    // Noise from a simple generator, which zstd can only store with its framing on top
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let noise = (0..4096)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect::<Vec<_>>();
+    // Loose raw files aren't written, so the noise goes in a BYML that is
+    let noisy = Byml::Map(
+        [("Noise".into(), Byml::BinaryData(noise))]
+            .into_iter()
+            .collect(),
+    );
+    let binary = noisy.to_binary(Endian::Little);
+    let compressed = compress("Noise.byml.zs", &binary);
+    assert!(compressed.len() > binary.len());
+    fixture.write("Data/Noise.byml.zs", &compressed);
+    let tiny = Byml::Array(vec![]);
+    fixture.write(
+        "Data/Tiny.byml.zs",
+        compress("Tiny.byml.zs", &tiny.to_binary(Endian::Little)),
+    );
+    unpack(&fixture);
+    let text = fixture.read_output("Data/Noise.byml.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), noisy);
+    let text = fixture.read_output("Data/Tiny.byml.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), tiny);
+}
+
+#[test]
+fn places_outputs_relative_to_another_base() {
+    let fixture = Fixture::new("relative-to");
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let base = fixture.romfs().parent().unwrap().to_path_buf();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            relative_to: Some(base),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    fixture.read_output("romfs/Data/Loose.byml.yml");
+
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            relative_to: Some(fixture.output()),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    let error = unpacker.unpack().unwrap_err();
+    assert!(error.to_string().contains("is not under"));
+}
+
+#[test]
+fn expands_nested_files() {
+    let fixture = Fixture::new("expand-nested");
+    let inner = Byml::Array(vec![Byml::I32(7)]).to_binary(Endian::Little);
+    let outer = Byml::Map(
+        [
+            (
+                "Params".into(),
+                Byml::BinaryData(ParameterIO::new().to_binary()),
+            ),
+            ("Inner".into(), Byml::BinaryData(inner)),
+            ("Broken".into(), Byml::BinaryData(b"AAMP broken".to_vec())),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    fixture.write("Data/Outer.byml", outer.to_binary(Endian::Little));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            expand_nested: true,
+            sort_keys: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    let text = fixture.read_output("Data/Outer.byml.yml");
+    assert_eq!(text.matches("!BinaryAamp").count(), 1);
+    assert_eq!(text.matches("!BinaryByml").count(), 1);
+    assert!(text.contains("Broken"));
+}
+
+#[test]
+fn resumes_from_checkpoint() {
+    let fixture = Fixture::new("checkpoint");
+    fixture.write("Data/Done.byml", byml().to_binary(Endian::Little));
+    fixture.write("Data/Cut.byml", byml().to_binary(Endian::Little));
+    fixture.write("Data/Todo.byml", byml().to_binary(Endian::Little));
+    let checkpoint = fixture.output().with_file_name("checkpoint.txt");
+    // The last line was cut off before its newline, so that file isn't finished
+    std::fs::write(&checkpoint, "Data/Done.byml\nData/Cut.byml").unwrap();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            checkpoint: Some(checkpoint.clone()),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert!(!fixture.output().join("Data/Done.byml.yml").exists());
+    fixture.read_output("Data/Cut.byml.yml");
+    fixture.read_output("Data/Todo.byml.yml");
+    let listed = std::fs::read_to_string(&checkpoint).unwrap();
+    let lines = listed.lines().collect::<Vec<_>>();
+    assert!(lines.contains(&"Data/Done.byml"));
+    assert!(lines.contains(&"Data/Cut.byml"));
+    assert!(lines.contains(&"Data/Todo.byml"));
+    assert!(listed.ends_with('\n'));
+}
+
+#[test]
+fn cleans_stale_outputs() {
+    let fixture = Fixture::new("clean");
+    fixture.write("Data/Kept.byml", byml().to_binary(Endian::Little));
+    let stale = fixture.output().join("Old/Gone.byml.yml");
+    let hidden = fixture.output().join(".git/HEAD");
+    for path in [&stale, &hidden] {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "old").unwrap();
+    }
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            clean: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    fixture.read_output("Data/Kept.byml.yml");
+    fixture.read_output("manifest.json");
+    assert!(!stale.exists());
+    assert!(!fixture.output().join("Old").exists());
+    assert!(hidden.exists());
+}
+
+#[test]
+fn follows_magic_table() {
+    let fixture = Fixture::new("magic-table");
+    let pack = sarc(&[("Member.bgyml", byml().to_binary(Endian::Little))]);
+    fixture.write("Pack/Test.pack", &pack);
+    let table = fixture.output().with_file_name("magics.yml");
+    // SARC, in hex
+    std::fs::write(&table, "'0x53415243': raw\n").unwrap();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            magic_table: totk_dump::magic_table::MagicTable::load(&table).unwrap(),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(
+        std::fs::read(fixture.output().join("Pack/Test.pack")).unwrap(),
+        pack
+    );
+    assert!(!fixture
+        .output()
+        .join("Pack/Test.pack/Member.bgyml.yml")
+        .exists());
+
+    std::fs::write(&table, "XYZ: bntx\n").unwrap();
+    assert!(totk_dump::magic_table::MagicTable::load(&table).is_err());
+}
+
+#[test]
+fn traces_each_file() {
+    let fixture = Fixture::new("trace");
+    let data = byml().to_binary(Endian::Little);
+    fixture.write("Data/Big,Name.byml", data.clone());
+    let compressed = compress("Loose.byml.zs", &data);
+    fixture.write("Data/Loose.byml.zs", compressed.clone());
+    let trace = fixture.output().with_file_name("trace.csv");
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            trace: Some(trace.clone()),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let trace = std::fs::read_to_string(&trace).unwrap();
+    let mut lines = trace.lines();
+    assert_eq!(
+        lines.next(),
+        Some("path,input_bytes,output_bytes,decompress_ms,parse_ms,serialize_ms")
+    );
+    // The fixture's dictionaries are unpacked too
+    let rows = lines
+        .filter(|row| !row.starts_with("Pack/ZsDic.pack.zs/"))
+        .collect::<Vec<_>>();
+    assert_eq!(rows.len(), 2);
+    let plain = rows
+        .iter()
+        .find(|row| row.starts_with("\"Data/Big,Name.byml\","))
+        .unwrap();
+    let fields = plain.rsplitn(6, ',').collect::<Vec<_>>();
+    assert_eq!(fields[4], data.len().to_string());
+    let written = fixture.read_output("Data/Big,Name.byml.yml").len();
+    assert_eq!(fields[3], written.to_string());
+    let loose = rows
+        .iter()
+        .find(|row| row.starts_with("Data/Loose.byml.zs,"))
+        .unwrap();
+    let fields = loose.split(',').collect::<Vec<_>>();
+    assert_eq!(fields[1], compressed.len().to_string());
+    assert!(fields[3].parse::<f64>().unwrap() >= 0.0);
+}
+
+#[test]
+fn compares_against_existing_dump() {
+    let fixture = Fixture::new("compare");
+    fixture.write("Data/Same.byml", byml().to_binary(Endian::Little));
+    fixture.write("Data/Changed.byml", byml().to_binary(Endian::Little));
+    fixture.write("Data/Gone.byml", byml().to_binary(Endian::Little));
+    unpack(&fixture);
+    let output = fixture.output();
+    std::fs::write(output.join("Data/Changed.byml.yml"), "edited").unwrap();
+    std::fs::remove_file(output.join("Data/Gone.byml.yml")).unwrap();
+    std::fs::write(output.join("Data/Extra.yml"), "extra").unwrap();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            compare: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let report = unpacker.comparison();
+    assert_eq!(report.mismatched, [Path::new("Data/Changed.byml.yml")]);
+    assert_eq!(report.missing, [Path::new("Data/Gone.byml.yml")]);
+    assert_eq!(report.extra, [Path::new("Data/Extra.yml")]);
+    assert!(!report.is_match());
+    // Nothing was written back
+    assert_eq!(fixture.read_output("Data/Changed.byml.yml"), "edited");
+    assert!(!output.join("Data/Gone.byml.yml").exists());
+}
+
+#[test]
+fn compresses_outputs() {
+    use std::io::Read;
+    use totk_dump::OutputCompression;
+
+    let fixture = Fixture::new("compress-output");
+    fixture.write("Data/Test.byml", byml().to_binary(Endian::Little));
+    unpack(&fixture);
+    let text = fixture.read_output("Data/Test.byml.yml");
+    for (compression, suffix) in [
+        (OutputCompression::Gzip, "gz"),
+        (OutputCompression::Zstd, "zst"),
+    ] {
+        let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+            .with_options(Options {
+                compress_output: compression,
+                ..Default::default()
+            })
+            .init_dicts()
+            .unwrap();
+        unpacker.unpack().unwrap();
+        let out = fixture
+            .output()
+            .join(format!("Data/Test.byml.yml.{suffix}"));
+        let data = std::fs::read(&out).unwrap();
+        let decoded = match compression {
+            OutputCompression::Gzip => {
+                let mut decoded = String::new();
+                flate2::read::GzDecoder::new(&data[..])
+                    .read_to_string(&mut decoded)
+                    .unwrap();
+                decoded
+            }
+            _ => String::from_utf8(zstd::decode_all(&data[..]).unwrap()).unwrap(),
+        };
+        assert_eq!(decoded, text);
+        let manifest = fixture.read_output("manifest.json");
+        assert!(manifest.contains(&format!("Test.byml.yml.{suffix}")));
+    }
+}
+
+#[test]
+fn resolves_actor_names() {
+    use totk_dump::names::{crc32, murmur3};
+
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(murmur3(b"hello"), 0x248B_FA47);
+    assert_eq!(
+        murmur3(b"The quick brown fox jumps over the lazy dog"),
+        0x2E4F_F723
+    );
+
+    let fixture = Fixture::new("resolve-names");
+    let map = |pairs: Vec<(&str, Byml)>| {
+        Byml::Map(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        )
+    };
+    let info = Byml::Array(vec![map(vec![(
+        "__RowId",
+        Byml::String("Enemy_Bokoblin".into()),
+    )])]);
+    fixture.write(
+        "RSDB/ActorInfo.Product.rstbl.byml",
+        info.to_binary(Endian::Little),
+    );
+    let list = map(vec![(
+        "Data",
+        map(vec![(
+            "Bool",
+            Byml::Array(vec![
+                map(vec![
+                    ("Hash", Byml::U32(murmur3(b"Enemy_Bokoblin"))),
+                    ("DefaultValue", Byml::Bool(false)),
+                ]),
+                map(vec![
+                    ("Hash", Byml::U32(1)),
+                    ("DefaultValue", Byml::Bool(true)),
+                ]),
+            ]),
+        )]),
+    )]);
+    fixture.write(
+        "GameData/GameDataList.Product.byml",
+        list.to_binary(Endian::Little),
+    );
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            expand_gamedata: true,
+            resolve_names: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.actor_names().unwrap().len(), 2);
+    let text = fixture.read_output("GameData/GameDataList.Product.byml.yml");
+    assert_eq!(text.matches("actor: Enemy_Bokoblin").count(), 1);
+}
+
+#[test]
+fn tests_one_member() {
+    let fixture = Fixture::new("test-one");
+    let member = compress("Compressed.byml.zs", &byml().to_binary(Endian::Little));
+    let pack = sarc(&[
+        ("Data/Other.bgyml", byml().to_binary(Endian::Little)),
+        ("Data/Compressed.byml.zs", member.clone()),
+    ]);
+    fixture.write("Pack/Test.pack.zs", compress("Test.pack.zs", &pack));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .init_dicts()
+        .unwrap();
+    unpacker
+        .test_one(Path::new("Pack/Test.pack.zs/Data/Compressed.byml.zs"))
+        .unwrap();
+    let text = fixture.read_output("Pack/Test.pack.zs/Data/Compressed.byml.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+    assert!(!fixture
+        .output()
+        .join("Pack/Test.pack.zs/Data/Other.bgyml.yml")
+        .exists());
+    let kept = fixture.output().join("_test-one");
+    assert_eq!(
+        std::fs::read(kept.join("Compressed.byml.zs")).unwrap(),
+        member
+    );
+    assert_eq!(
+        std::fs::read(kept.join("Compressed.byml.zs.decompressed")).unwrap(),
+        byml().to_binary(Endian::Little)
+    );
+    assert!(unpacker
+        .test_one(Path::new("Pack/Test.pack.zs/Data/Missing.byml"))
+        .is_err());
+}
+
+#[test]
+fn converts_environment_files() {
+    let fixture = Fixture::new("environment");
+    fixture.write(
+        "Env/Field.genvb.zs",
+        compress("Field.genvb.zs", &byml().to_binary(Endian::Little)),
+    );
+    fixture.write("Env/Sky.bgenv", byml().to_binary(Endian::Little));
+    fixture.write("Env/Light.genvb", ParameterIO::new().to_binary());
+    assert_eq!(Format::from_name("Field.genvb.zs"), Some(Format::Byml));
+    assert_eq!(Format::from_name("Sky.bgenv"), Some(Format::Byml));
+    unpack(&fixture);
+    for out in ["Env/Field.genvb.yml", "Env/Sky.bgenv.yml"] {
+        let text = fixture.read_output(out);
+        assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+    }
+    let text = fixture.read_output("Env/Light.genvb.yml");
+    assert_eq!(
+        serde_yaml::from_str::<ParameterIO>(&text).unwrap(),
+        ParameterIO::new()
+    );
+}
+
+#[test]
+fn detects_members_by_header_not_name() {
+    let fixture = Fixture::new("aamp-header");
+    let mut fake = b"AAMP".to_vec();
+    fake.extend([9, 0, 0, 0]);
+    fake.extend([0; 24]);
+    let pack = sarc(&[
+        ("Param/Byml.bxml", byml().to_binary(Endian::Little)),
+        ("Param/Fake.bxml", fake.clone()),
+        ("Param/Real.bxml", ParameterIO::new().to_binary()),
+    ]);
+    fixture.write("Pack/Test.pack.zs", compress("Test.pack.zs", &pack));
+    unpack(&fixture);
+    let text = fixture.read_output("Pack/Test.pack.zs/Param/Byml.bxml.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+    let text = fixture.read_output("Pack/Test.pack.zs/Param/Real.bxml.yml");
+    assert_eq!(
+        serde_yaml::from_str::<ParameterIO>(&text).unwrap(),
+        ParameterIO::new()
+    );
+    // Not a known AAMP version, so it isn't parsed as one
+    assert_eq!(
+        std::fs::read(fixture.output().join("Pack/Test.pack.zs/Param/Fake.bxml")).unwrap(),
+        fake
+    );
+    assert_eq!(totk_dump::format::detect_format(&fake), Format::Raw);
+}
+
+#[test]
+fn writes_repeated_nodes_in_full() {
+    let fixture = Fixture::new("no-anchors");
+    let repeated = Byml::Map([("Value".into(), Byml::I32(1))].into_iter().collect());
+    let root = Byml::Array(vec![repeated.clone(), repeated.clone(), repeated]);
+    fixture.write("Data/Repeated.byml", root.to_binary(Endian::Little));
+    unpack(&fixture);
+    let text = fixture.read_output("Data/Repeated.byml.yml");
+    // Repeated subtrees are never written as anchors and aliases
+    assert!(!text.contains('&') && !text.contains('*'));
+    assert_eq!(text.matches("Value").count(), 3);
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), root);
+}
+
+#[test]
+fn diffs_manifests() {
+    use totk_dump::{filter::Filter, manifest};
+
+    let fixture = Fixture::new("diff-manifest");
+    // Source hashes, which tell changed files apart, are only kept with skip_unchanged
+    let dump = || -> Vec<manifest::Entry> {
+        let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+            .with_options(Options {
+                skip_unchanged: true,
+                ..Default::default()
+            })
+            .init_dicts()
+            .unwrap();
+        unpacker.unpack().unwrap();
+        assert_eq!(unpacker.failures(), 0);
+        serde_json::from_str(&fixture.read_output("manifest.json")).unwrap()
+    };
+    fixture.write("Data/Same.byml", byml().to_binary(Endian::Little));
+    fixture.write("Data/Gone.byml", byml().to_binary(Endian::Little));
+    let pack = sarc(&[("Data/Member[1].bgyml", byml().to_binary(Endian::Little))]);
+    fixture.write("Pack/Test.pack", pack);
+    let old = dump();
+
+    std::fs::remove_file(fixture.romfs().join("Data/Gone.byml")).unwrap();
+    fixture.write("Data/New.byml", byml().to_binary(Endian::Little));
+    let bigger = Byml::Array(vec![byml(), byml()]);
+    let pack = sarc(&[("Data/Member[1].bgyml", bigger.to_binary(Endian::Little))]);
+    fixture.write("Pack/Test.pack", pack);
+    let new = dump();
+
+    let diff = manifest::Diff::new(&old, &new);
+    assert_eq!(diff.added, [Path::new("Data/New.byml")]);
+    assert_eq!(diff.removed, [Path::new("Data/Gone.byml")]);
+    assert!(diff
+        .changed
+        .contains(&Path::new("Pack/Test.pack/Data/Member[1].bgyml").to_path_buf()));
+    assert!(!diff
+        .changed
+        .contains(&Path::new("Data/Same.byml").to_path_buf()));
+
+    let globs = diff.globs();
+    let filter = Filter::new(&globs.lines().map(str::to_owned).collect::<Vec<_>>(), &[]).unwrap();
+    assert!(filter.matches(Path::new("Data/New.byml")));
+    assert!(filter.matches(Path::new("Pack/Test.pack/Data/Member[1].bgyml")));
+    assert!(!filter.matches(Path::new("Pack/Test.pack/Data/Member1.bgyml")));
+    assert!(!filter.matches(Path::new("Data/Same.byml")));
+}
+
+#[test]
+fn writes_binary_nodes_as_hex_or_files() {
+    use totk_dump::BinaryNodes;
+
+    let fixture = Fixture::new("binary-nodes");
+    let blob = Byml::Map(
+        [
+            (
+                "First".into(),
+                Byml::BinaryData(vec![0xde, 0xad, 0xbe, 0xef]),
+            ),
+            ("Second".into(), Byml::BinaryData(vec![1, 2, 3])),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    fixture.write("Data/Blob.byml", blob.to_binary(Endian::Little));
+    let dump = |binary_nodes| {
+        Unpacker::new(fixture.romfs(), fixture.output())
+            .with_options(Options {
+                binary_nodes,
+                sort_keys: true,
+                ..Default::default()
+            })
+            .init_dicts()
+            .unwrap()
+            .unpack()
+            .unwrap();
+        fixture.read_output("Data/Blob.byml.yml")
+    };
+    let text = dump(BinaryNodes::Hex);
+    assert!(text.contains("!BinaryHex deadbeef"));
+    // Quoted or not, as it could be read as a number
+    assert!(text.contains("010203"));
+    assert_eq!(text.matches("!BinaryHex").count(), 2);
+    let text = dump(BinaryNodes::External);
+    assert!(text.contains("!BinaryFile Blob.byml.0.bin"));
+    assert!(text.contains("!BinaryFile Blob.byml.1.bin"));
+    let output = fixture.output();
+    assert_eq!(
+        std::fs::read(output.join("Data/Blob.byml.0.bin")).unwrap(),
+        [0xde, 0xad, 0xbe, 0xef]
+    );
+    assert_eq!(
+        std::fs::read(output.join("Data/Blob.byml.1.bin")).unwrap(),
+        [1, 2, 3]
+    );
+}
+
+#[test]
+fn reads_misnamed_zs_files_as_they_are() {
+    let fixture = Fixture::new("misnamed-zs");
+    let data = byml().to_binary(Endian::Little);
+    fixture.write("Data/Plain.byml.zs", data.clone());
+    let unpacker = unpack(&fixture);
+    let text = fixture.read_output("Data/Plain.byml.yml");
+    assert_eq!(serde_yaml::from_str::<Byml>(&text).unwrap(), byml());
+    assert_eq!(unpacker.decompress("Plain.byml.zs", &data).unwrap(), data);
+}
+
+#[test]
+fn reads_globs_from_files() {
+    let fixture = Fixture::new("globs-from");
+    fixture.write("Data/Kept.byml", byml().to_binary(Endian::Little));
+    fixture.write("Data/Dropped.byml", byml().to_binary(Endian::Little));
+    fixture.write("Other/Left.byml", byml().to_binary(Endian::Little));
+    let include = fixture.output().with_file_name("include.txt");
+    std::fs::write(&include, "# Everything under Data\n\n  Data/**  \n").unwrap();
+    let exclude = fixture.output().with_file_name("exclude.txt");
+    std::fs::write(&exclude, "Data/Dropped.*\n").unwrap();
+    let filter = totk_dump::filter::Filter::new(
+        &totk_dump::filter::read_globs(&include).unwrap(),
+        &totk_dump::filter::read_globs(&exclude).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        totk_dump::filter::read_globs(&include).unwrap(),
+        ["Data/**"]
+    );
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            filter,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let output = fixture.output();
+    assert!(output.join("Data/Kept.byml.yml").exists());
+    assert!(!output.join("Data/Dropped.byml.yml").exists());
+    assert!(!output.join("Other/Left.byml.yml").exists());
+}
+
+#[test]
+fn canonicalizes_floats() {
+    use totk_dump::FloatStyle;
+    let fixture = Fixture::new("canonical-floats");
+    let floats = Byml::Array(vec![
+        Byml::Float(-0.0),
+        Byml::Float(1.23456),
+        Byml::Double(-0.0001),
+    ]);
+    fixture.write("Data/Floats.byml", floats.to_binary(Endian::Little));
+    let dump = |style| {
+        let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+            .with_options(Options {
+                canonicalize_floats: Some(style),
+                ..Default::default()
+            })
+            .init_dicts()
+            .unwrap();
+        unpacker.unpack().unwrap();
+        assert_eq!(unpacker.failures(), 0);
+        let text = fixture.read_output("Data/Floats.byml.yml");
+        match serde_yaml::from_str::<Byml>(&text).unwrap() {
+            Byml::Array(array) => array,
+            other => panic!("Expected an array, got {other:?}"),
+        }
+    };
+    let shortest = dump(FloatStyle::Shortest);
+    assert!(matches!(shortest[0], Byml::Float(zero) if zero.to_bits() == 0));
+    assert_eq!(shortest[1], Byml::Float(1.23456));
+    let fixed = dump(FloatStyle::Fixed(2));
+    assert_eq!(fixed[1], Byml::Float(1.23));
+    // Rounds to zero, not negative zero
+    assert!(matches!(fixed[2], Byml::Double(zero) if zero.to_bits() == 0));
+}
+
+#[test]
+fn converts_whole_archives_to_text() {
+    let fixture = Fixture::new("convert-pack");
+    let pack = sarc(&[("Actor/Thing.bgyml", byml().to_binary(Endian::Little))]);
+    // Read on its own, as from stdin, with nothing but the dictionaries to go on
+    let unpacker = Unpacker::new(Default::default(), Default::default())
+        .with_options(Options {
+            zsdic: Some(fixture.romfs().join("Pack/ZsDic.pack.zs")),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    let text = unpacker
+        .convert_to_text("stdin.pack.zs", compress("Thing.pack.zs", &pack))
+        .unwrap();
+    let bundle: serde_yaml::Value = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(
+        serde_yaml::from_value::<Byml>(bundle["Actor/Thing.bgyml"].clone()).unwrap(),
+        byml()
+    );
+}
+
+#[test]
+fn selects_languages_from_language_map() {
+    use totk_dump::language::LanguageMap;
+    let fixture = Fixture::new("language-map");
+    for lang in ["USen", "EUpt", "XXzz"] {
+        let pack = sarc(&[("Data/Member.bgyml", byml().to_binary(Endian::Little))]);
+        fixture.write(&format!("Mals/{lang}.Product.100.sarc"), pack);
+    }
+    let map = LanguageMap::default().with_entries("EUpt=pt").unwrap();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            // Codes the map doesn't know are taken as they are
+            languages: Some(map.languages("pt,XXzz")),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    fixture.read_output("Mals/EUpt.Product.100.sarc/Data/Member.bgyml.yml");
+    fixture.read_output("Mals/XXzz.Product.100.sarc/Data/Member.bgyml.yml");
+    assert!(!fixture.output().join("Mals/USen.Product.100.sarc").exists());
+    assert!(LanguageMap::default().with_entries("EUpt").is_err());
+}
+
+#[test]
+fn detects_game_version() {
+    use totk_dump::version::GameVersion;
+    let fixture = Fixture::new("game-version");
+    fixture.write(
+        "System/Resource/ResourceSizeTable.Product.130.rsizetable.zs",
+        b"",
+    );
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output());
+    let version = unpacker.game_version().unwrap();
+    assert_eq!(version, GameVersion(130));
+    assert_eq!(version.to_string(), "1.3.0");
+    assert!(!version.is_supported());
+    assert_eq!("1.2.1".parse::<GameVersion>().unwrap(), GameVersion(121));
+    assert_eq!("121".parse::<GameVersion>().unwrap(), GameVersion(121));
+    assert!("1.2".parse::<GameVersion>().is_err());
+    let hinted = Unpacker::new(fixture.romfs(), fixture.output()).with_options(Options {
+        game_version: Some(GameVersion(110)),
+        ..Default::default()
+    });
+    assert!(hinted.game_version().unwrap().is_supported());
+}
+
+#[test]
+fn writes_paths_file() {
+    let fixture = Fixture::new("paths-file");
+    let pack = sarc(&[("Data/Member.bgyml", byml().to_binary(Endian::Little))]);
+    fixture.write("Pack/Test.pack.zs", compress("Test.pack.zs", &pack));
+    fixture.write("Data/Loose.byml", byml().to_binary(Endian::Little));
+    let paths = fixture.output().with_file_name("paths-file.txt");
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            paths_file: Some(paths.clone()),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    let text = std::fs::read_to_string(&paths).unwrap();
+    let _ = std::fs::remove_file(&paths);
+    let lines = text.lines().collect::<Vec<_>>();
+    assert!(lines.windows(2).all(|pair| pair[0] < pair[1]));
+    for path in [
+        "Data/Loose.byml.yml",
+        "Pack/Test.pack.zs/Data/Member.bgyml.yml",
+    ] {
+        assert!(lines.contains(&path), "{path} missing from {lines:?}");
+    }
+    // Only outputs of ROM files, not the manifest
+    assert!(lines
+        .iter()
+        .all(|line| fixture.output().join(line).is_file()));
+    assert!(!lines.contains(&"manifest.json"));
+}
+
+#[test]
+fn decompresses_frames_with_or_without_content_size() {
+    use zstd::bulk::Compressor;
+    let fixture = Fixture::new("content-size");
+    // Compresses far past the 30x that buffers sized by guessing would have allowed
+    let data =
+        Byml::Array(vec![Byml::String("Repeated".into()); 100_000]).to_binary(Endian::Little);
+    for (name, include) in [("Sized", true), ("Unsized", false)] {
+        let mut compressor = Compressor::with_dictionary(3, common::COMMON_DICT).unwrap();
+        compressor.include_contentsize(include).unwrap();
+        let compressed = compressor.compress(&data).unwrap();
+        assert!(compressed.len() * 30 < data.len());
+        fixture.write(&format!("Data/{name}.byml.zs"), compressed);
+    }
+    unpack(&fixture);
+    assert_eq!(
+        fixture.read_output("Data/Sized.byml.yml"),
+        fixture.read_output("Data/Unsized.byml.yml")
+    );
+}
+
+#[test]
+fn extracts_raw_copies_too() {
+    let fixture = Fixture::new("extract-raw-also");
+    let data = byml().to_binary(Endian::Little);
+    let pack = sarc(&[("Data/Member.bgyml", data.clone())]);
+    fixture.write("Pack/Test.pack.zs", compress("Test.pack.zs", &pack));
+    fixture.write("Data/Loose.byml.zs", compress("Loose.byml.zs", &data));
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            extract_raw_also: true,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    let raw = fixture.output().join("_raw");
+    // Decompressed, as they were parsed
+    assert_eq!(std::fs::read(raw.join("Data/Loose.byml")).unwrap(), data);
+    assert_eq!(
+        std::fs::read(raw.join("Pack/Test.pack.zs/Data/Member.bgyml")).unwrap(),
+        data
+    );
+    fixture.read_output("Data/Loose.byml.yml");
+    // Archives are only unpacked, not copied
+    assert!(!raw.join("Pack/Test.pack").exists());
+}
+
+#[test]
+fn extracts_raw_copies_where_outputs_go() {
+    let fixture = Fixture::new("extract-raw-layout");
+    let data = byml().to_binary(Endian::Little);
+    fixture.write("A/B/C/D/Deep.byml", data.clone());
+    let mut bars = b"BARS".to_vec();
+    bars.extend(16u32.to_le_bytes());
+    bars.extend([0xFF, 0xFE, 1, 1]);
+    bars.extend(0u32.to_le_bytes());
+    fixture.write("Sound/Bank.bars", bars);
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            extract_raw_also: true,
+            flatten_depth: Some(2),
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    let raw = fixture.output().join("_raw");
+    fixture.read_output("A/B/C__D__Deep.byml.yml");
+    assert_eq!(
+        std::fs::read(raw.join("A/B/C__D__Deep.byml")).unwrap(),
+        data
+    );
+    // Summaries already sit next to a copy of their file
+    fixture.read_output("Sound/Bank.bars.yml");
+    assert!(!raw.join("Sound").exists());
+}
+
+#[test]
+fn keeps_uncompressed_summarized_files_once() {
+    let fixture = Fixture::new("keep-compressed-summary");
+    let mut bars = b"BARS".to_vec();
+    bars.extend(16u32.to_le_bytes());
+    bars.extend([0xFF, 0xFE, 1, 1]);
+    bars.extend(0u32.to_le_bytes());
+    fixture.write("Sound/Bank.bars", &bars);
+    // Leaves out the fixture's dictionaries, so only the file's own outputs are counted
+    let filter = totk_dump::filter::Filter::new(&["Sound/**".to_owned()], &[]).unwrap();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            keep_compressed: true,
+            filter,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 0);
+    let text = fixture.read_output("Sound/Bank.bars.yml");
+    assert_eq!(
+        std::fs::read(fixture.output().join("Sound/Bank.bars")).unwrap(),
+        bars
+    );
+    // The file next to the summary is the original, so it isn't written a second time
+    assert_eq!(
+        unpacker
+            .stats()
+            .bytes_written
+            .load(std::sync::atomic::Ordering::Relaxed),
+        (text.len() + bars.len()) as u64
+    );
+}
+
+#[test]
+fn writes_unparsed_summarized_files_once() {
+    let fixture = Fixture::new("bad-summary");
+    fixture.write("Sound/Bad.bars", b"BARS but truncated");
+    let filter = totk_dump::filter::Filter::new(&["Sound/**".to_owned()], &[]).unwrap();
+    let unpacker = Unpacker::new(fixture.romfs(), fixture.output())
+        .with_options(Options {
+            filter,
+            ..Default::default()
+        })
+        .init_dicts()
+        .unwrap();
+    unpacker.unpack().unwrap();
+    assert_eq!(unpacker.failures(), 1);
+    assert_eq!(fixture.read_output("Sound/Bad.bars"), "BARS but truncated");
+    assert!(!fixture.output().join("Sound/Bad.bars.yml").exists());
+    assert_eq!(
+        unpacker
+            .stats()
+            .bytes_written
+            .load(std::sync::atomic::Ordering::Relaxed),
+        "BARS but truncated".len() as u64
+    );
+}
@@ -0,0 +1,12 @@
+use totk_dump::version::{GameVersion, SUPPORTED};
+
+#[test]
+fn supports_released_versions_only() {
+    assert_eq!(SUPPORTED.start().to_string(), "1.0.0");
+    assert_eq!(SUPPORTED.end().to_string(), "1.2.1");
+    assert!(GameVersion(100).is_supported());
+    assert!(GameVersion(121).is_supported());
+    // One either side of the range
+    assert!(!GameVersion(99).is_supported());
+    assert!(!GameVersion(122).is_supported());
+}